@@ -0,0 +1,445 @@
+use crate::models::{PracticeSessionData, Receipt};
+use crate::profile::Profile;
+use crate::scales::key_from_name;
+use crate::selection::Strategy;
+use crate::statistics::SessionStatistics;
+
+use chrono::NaiveDate;
+use color_eyre::eyre::eyre;
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+
+/*
+ * Assignments
+ *
+ * Teachers write practice plans as JSON or TOML files and hand them to students. A plan holds
+ * assignments: which keys to practice, how they are picked, how long and how often. Sessions
+ * practiced for an assignment carry its title in their receipt, and the receipts are gathered
+ * into a report the student sends back.
+ *
+ * A plan in TOML looks like this:
+ *
+ *     title = "Week 12"
+ *     teacher = "Ms. Ozerova"
+ *
+ *     [[assignments]]
+ *     title = "Flat keys"
+ *     pool = ["F", "Bb", "Eb", "Ab"]
+ *     strategy = "in_order"
+ *     minutes = 15
+ *     sessions = 3
+ *     due = "2024-09-30"
+ */
+
+/// A practice routine assigned by a teacher.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Assignment {
+    /// Identifies the assignment in receipts and reports.
+    pub title: String,
+    #[serde(default)]
+    pub teacher: String,
+    /// Keys to practice, written as note names such as `"C"`, `"F#"` or `"Bb"`.
+    pub pool: Vec<String>,
+    #[serde(default)]
+    pub strategy: Strategy,
+    /// Minutes of work a session needs to count towards the assignment.
+    #[serde(default)]
+    pub minutes: u32,
+    /// Number of sessions to complete.
+    #[serde(default = "one")]
+    pub sessions: u32,
+    #[serde(default)]
+    pub due: Option<NaiveDate>,
+    #[serde(default)]
+    pub notes: String,
+}
+
+fn one() -> u32 {
+    1
+}
+
+/// A set of assignments, as written by a teacher.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PracticePlan {
+    pub title: String,
+    #[serde(default)]
+    pub teacher: String,
+    pub assignments: Vec<Assignment>,
+}
+
+/// Format of a practice plan file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlanFormat {
+    Json,
+    Toml,
+}
+
+impl PlanFormat {
+    /// Format matching the extension of `path`, `None` for anything but `.json` and `.toml`.
+    pub fn from_path(path: &str) -> Option<Self> {
+        let extension = path.rsplit_once('.')?.1.to_ascii_lowercase();
+        match extension.as_str() {
+            "json" => Some(PlanFormat::Json),
+            "toml" => Some(PlanFormat::Toml),
+            _ => None,
+        }
+    }
+}
+
+impl PracticePlan {
+    /// Reads a plan and checks that every assignment can be practiced.
+    ///
+    /// Assignments without a teacher are given the teacher of the plan.
+    pub fn parse(text: &str, format: PlanFormat) -> Result<Self> {
+        let mut plan: PracticePlan = match format {
+            PlanFormat::Json => serde_json::from_str(text)?,
+            PlanFormat::Toml => toml::from_str(text)?,
+        };
+
+        for assignment in plan.assignments.iter_mut() {
+            if assignment.title.trim().is_empty() {
+                return Err(eyre!("Every assignment needs a title."));
+            }
+            assignment.pool_keys()?;
+            if assignment.teacher.is_empty() {
+                assignment.teacher = plan.teacher.clone();
+            }
+        }
+
+        Ok(plan)
+    }
+}
+
+impl Assignment {
+    /// Keys of the pool, in the order they were written.
+    pub fn pool_keys(&self) -> Result<Vec<usize>> {
+        if self.pool.is_empty() {
+            return Err(eyre!("Assignment '{}' has no keys.", self.title));
+        }
+
+        self.pool
+            .iter()
+            .map(|name| {
+                key_from_name(name).ok_or_else(|| {
+                    eyre!("Assignment '{}' has an unknown key '{}'.", self.title, name)
+                })
+            })
+            .collect()
+    }
+
+    /// Name of `key` as written in the pool, or its number for keys outside the pool.
+    pub fn key_name(&self, key: usize) -> String {
+        self.pool
+            .iter()
+            .find(|name| key_from_name(name) == Some(key))
+            .map(|name| name.trim().to_string())
+            .unwrap_or_else(|| key.to_string())
+    }
+
+    /// Sets `data` up to practice the assignment: only keys of the pool can be selected, in the
+    /// way the assignment asks for, and receipts record the assignment.
+    pub fn apply(&self, data: &mut PracticeSessionData) -> Result<()> {
+        let pool = self.pool_keys()?;
+
+        data.all_keys_map.0.iter_mut().for_each(|key| {
+            key.weight = if pool.contains(&key.nid) { 100 } else { 0 };
+            key.pinned = false;
+        });
+        data.strategy = self.strategy;
+        data.key_order = pool;
+        data.assignment = Some(self.title.clone());
+
+        Ok(())
+    }
+
+    /// Receipts in `history` that were practiced for the assignment and not withdrawn.
+    pub fn receipts<'a>(&'a self, history: &'a [Receipt]) -> impl Iterator<Item = &'a Receipt> {
+        history.iter().filter(|receipt| {
+            receipt.assignment.as_deref() == Some(self.title.as_str()) && !receipt.is_withdrawn()
+        })
+    }
+
+    /// Whether `receipt` was practiced for the assignment, lasted long enough and was neither
+    /// excluded from statistics nor withdrawn.
+    pub fn is_fulfilled_by(&self, receipt: &Receipt) -> bool {
+        receipt.assignment.as_deref() == Some(self.title.as_str())
+            && !receipt.is_excluded()
+            && !receipt.is_withdrawn()
+            && SessionStatistics::from_receipt(receipt).working_seconds >= self.minutes as i64 * 60
+    }
+
+    /// Number of sessions in `history` that count towards the assignment.
+    pub fn completed_sessions(&self, history: &[Receipt]) -> u32 {
+        history
+            .iter()
+            .filter(|receipt| self.is_fulfilled_by(receipt))
+            .count() as u32
+    }
+}
+
+/*
+ * Reports
+ */
+
+/// One practiced session, as shown to the teacher.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionSummary {
+    /// Unix timestamp (in seconds) at which the session finished.
+    pub finished_at: Option<i64>,
+    pub working_seconds: i64,
+    pub resting_seconds: i64,
+    pub total_repetitions: i32,
+    /// Keys in the order they were selected, spelled as in the pool of the assignment.
+    pub keys: Vec<String>,
+    /// Whether the session lasted long enough to count.
+    pub fulfilled: bool,
+    /// Whether the student excluded the session from statistics after it finished.
+    #[serde(default)]
+    pub excluded: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AssignmentProgress {
+    pub title: String,
+    pub teacher: String,
+    pub due: Option<NaiveDate>,
+    pub sessions_required: u32,
+    pub sessions_completed: u32,
+    pub sessions: Vec<SessionSummary>,
+}
+
+/// Progress of a student on every assignment, to be sent back to the teacher.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AssignmentReport {
+    pub student: String,
+    pub assignments: Vec<AssignmentProgress>,
+}
+
+impl AssignmentReport {
+    pub fn new(profile: &Profile) -> Self {
+        let assignments = profile
+            .assignments
+            .iter()
+            .map(|assignment| AssignmentProgress {
+                title: assignment.title.clone(),
+                teacher: assignment.teacher.clone(),
+                due: assignment.due,
+                sessions_required: assignment.sessions,
+                sessions_completed: assignment.completed_sessions(&profile.history),
+                sessions: assignment
+                    .receipts(&profile.history)
+                    .map(|receipt| {
+                        let statistics = SessionStatistics::from_receipt(receipt);
+                        SessionSummary {
+                            finished_at: receipt
+                                .time_stamp_archive
+                                .as_ref()
+                                .and_then(|time_stamps| time_stamps.last())
+                                .map(|(_, timestamp)| *timestamp),
+                            working_seconds: statistics.working_seconds,
+                            resting_seconds: statistics.resting_seconds,
+                            total_repetitions: statistics.total_repetitions,
+                            keys: receipt
+                                .corrected()
+                                .key_sequence
+                                .iter()
+                                .map(|key| assignment.key_name(*key))
+                                .collect(),
+                            fulfilled: assignment.is_fulfilled_by(receipt),
+                            excluded: receipt.is_excluded(),
+                        }
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        AssignmentReport {
+            student: profile.name.clone(),
+            assignments,
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edits::ReceiptChange;
+
+    const TOML_PLAN: &str = r#"
+        title = "Week 12"
+        teacher = "Ms. Ozerova"
+
+        [[assignments]]
+        title = "Flat keys"
+        pool = ["F", "Bb", "Eb", "Ab"]
+        strategy = "in_order"
+        minutes = 15
+        sessions = 3
+        due = "2024-09-30"
+
+        [[assignments]]
+        title = "Sharps"
+        teacher = "Mr. Petrov"
+        pool = ["G", "D", "F#"]
+    "#;
+
+    fn error_of(text: &str, format: PlanFormat) -> String {
+        PracticePlan::parse(text, format).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn reads_toml_plans() {
+        let plan = PracticePlan::parse(TOML_PLAN, PlanFormat::Toml).unwrap();
+        assert_eq!(plan.title, "Week 12");
+        assert_eq!(plan.assignments.len(), 2);
+
+        let flat = &plan.assignments[0];
+        assert_eq!(flat.pool_keys().unwrap(), vec![5, 10, 3, 8]);
+        assert_eq!(flat.strategy, Strategy::InOrder);
+        assert_eq!((flat.minutes, flat.sessions), (15, 3));
+        assert_eq!(flat.due, NaiveDate::from_ymd_opt(2024, 9, 30));
+        // Assignments without a teacher take the teacher of the plan.
+        assert_eq!(flat.teacher, "Ms. Ozerova");
+
+        let sharps = &plan.assignments[1];
+        assert_eq!(sharps.teacher, "Mr. Petrov");
+        assert_eq!(sharps.strategy, Strategy::Weighted);
+        assert_eq!((sharps.minutes, sharps.sessions, sharps.due), (0, 1, None));
+    }
+
+    #[test]
+    fn reads_json_plans_like_toml_ones() {
+        let json = r#"{
+            "title": "Week 12",
+            "teacher": "Ms. Ozerova",
+            "assignments": [
+                {
+                    "title": "Flat keys",
+                    "pool": ["F", "Bb", "Eb", "Ab"],
+                    "strategy": "in_order",
+                    "minutes": 15,
+                    "sessions": 3,
+                    "due": "2024-09-30"
+                },
+                {"title": "Sharps", "teacher": "Mr. Petrov", "pool": ["G", "D", "F#"]}
+            ]
+        }"#;
+        assert_eq!(
+            PracticePlan::parse(json, PlanFormat::Json).unwrap(),
+            PracticePlan::parse(TOML_PLAN, PlanFormat::Toml).unwrap()
+        );
+    }
+
+    #[test]
+    fn rejects_plans_that_cannot_be_practiced() {
+        let plan = |assignment: &str| format!("title = \"Plan\"\n[[assignments]]\n{assignment}");
+
+        let error = error_of(
+            &plan("title = \"Odd\"\npool = [\"C\", \"H\"]"),
+            PlanFormat::Toml,
+        );
+        assert_eq!(error, "Assignment 'Odd' has an unknown key 'H'.");
+        let error = error_of(&plan("title = \"Empty\"\npool = []"), PlanFormat::Toml);
+        assert_eq!(error, "Assignment 'Empty' has no keys.");
+        let error = error_of(&plan("title = \" \"\npool = [\"C\"]"), PlanFormat::Toml);
+        assert_eq!(error, "Every assignment needs a title.");
+
+        // Syntax errors and missing fields come from the format itself.
+        assert!(PracticePlan::parse("title = ", PlanFormat::Toml).is_err());
+        assert!(PracticePlan::parse(r#"{"title": "Plan"}"#, PlanFormat::Json).is_err());
+        assert!(PracticePlan::parse(TOML_PLAN, PlanFormat::Json).is_err());
+    }
+
+    #[test]
+    fn tells_plan_formats_from_the_extension() {
+        assert_eq!(PlanFormat::from_path("plan.json"), Some(PlanFormat::Json));
+        assert_eq!(
+            PlanFormat::from_path("Week.12.TOML"),
+            Some(PlanFormat::Toml)
+        );
+        assert_eq!(
+            PlanFormat::from_path("/home/me/plans/week.toml"),
+            Some(PlanFormat::Toml)
+        );
+        assert_eq!(PlanFormat::from_path("plan.yaml"), None);
+        assert_eq!(PlanFormat::from_path("plan"), None);
+        assert_eq!(PlanFormat::from_path(""), None);
+    }
+
+    #[test]
+    fn applying_an_assignment_limits_the_keys_to_its_pool() {
+        let plan = PracticePlan::parse(TOML_PLAN, PlanFormat::Toml).unwrap();
+        let mut data = PracticeSessionData::new();
+        data.all_keys_map.0[0].pinned = true;
+        plan.assignments[0].apply(&mut data).unwrap();
+
+        let weights: Vec<i32> = data.all_keys_map.0.iter().map(|key| key.weight).collect();
+        assert_eq!(weights, vec![0, 0, 0, 100, 0, 100, 0, 0, 100, 0, 100, 0]);
+        assert!(data.all_keys_map.0.iter().all(|key| !key.pinned));
+        assert_eq!(data.key_order, vec![5, 10, 3, 8]);
+        assert_eq!(data.strategy, Strategy::InOrder);
+        assert_eq!(data.assignment.as_deref(), Some("Flat keys"));
+    }
+
+    #[test]
+    fn reports_progress_on_every_assignment() {
+        let plan = PracticePlan::parse(TOML_PLAN, PlanFormat::Toml).unwrap();
+        let mut profile = Profile::new("Anna");
+        profile.assign(plan.assignments);
+
+        // Long enough, too short, excluded, withdrawn, and practiced on her own.
+        [
+            (1_000, 900),
+            (2_000, 600),
+            (3_000, 1200),
+            (4_000, 1200),
+            (5_000, 1200),
+        ]
+        .iter()
+        .for_each(|(finished_at, seconds)| {
+            profile
+                .log_practice(*finished_at, *seconds, &[(10, 2), (3, 1)])
+                .unwrap();
+        });
+        (0..4).for_each(|index| profile.history[index].assignment = Some("Flat keys".into()));
+        profile.history[2].edit(ReceiptChange::Excluded { excluded: true }, 6_000);
+        let withdrawn = profile.history[3].clone();
+        profile.withdraw(&withdrawn, 6_000);
+
+        let report = AssignmentReport::new(&profile);
+        assert_eq!(report.student, "Anna");
+        assert_eq!(report.assignments.len(), 2);
+
+        let flat = &report.assignments[0];
+        assert_eq!(flat.title, "Flat keys");
+        assert_eq!((flat.sessions_required, flat.sessions_completed), (3, 1));
+        let summaries: Vec<(Option<i64>, bool, bool)> = flat
+            .sessions
+            .iter()
+            .map(|session| (session.finished_at, session.fulfilled, session.excluded))
+            .collect();
+        assert_eq!(
+            summaries,
+            vec![
+                (Some(1_000), true, false),
+                (Some(2_000), false, false),
+                (Some(3_000), false, true),
+            ]
+        );
+        // Keys are spelled as the teacher wrote them.
+        assert_eq!(flat.sessions[0].keys, vec!["Bb", "Bb", "Eb"]);
+        assert_eq!(flat.sessions[0].working_seconds, 900);
+        assert_eq!(flat.sessions[0].total_repetitions, 3);
+
+        let sharps = &report.assignments[1];
+        assert_eq!((sharps.sessions_completed, sharps.sessions.len()), (0, 0));
+
+        let json = report.to_json().unwrap();
+        let read: AssignmentReport = serde_json::from_str(&json).unwrap();
+        assert_eq!(read, report);
+    }
+}
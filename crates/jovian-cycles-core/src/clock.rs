@@ -0,0 +1,30 @@
+use chrono::Utc;
+
+/*
+ * Clock
+ */
+
+/// Source of the timestamps recorded by a practice session.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Clock {
+    /// Reads the system time.
+    #[default]
+    System,
+    /// Always reports the stored unix timestamp (in seconds) until it is changed.
+    Manual(i64),
+}
+
+impl Clock {
+    /// Current unix timestamp in seconds.
+    pub fn now(&self) -> i64 {
+        match self {
+            Clock::System => Utc::now().timestamp(),
+            Clock::Manual(timestamp) => *timestamp,
+        }
+    }
+
+    /// Makes the clock report `timestamp` from now on.
+    pub fn set(&mut self, timestamp: i64) {
+        *self = Clock::Manual(timestamp);
+    }
+}
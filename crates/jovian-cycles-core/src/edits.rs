@@ -0,0 +1,302 @@
+use crate::models::Receipt;
+
+use serde::{Deserialize, Serialize};
+
+/*
+ * Receipt Edits
+ *
+ * A finished session can be corrected afterwards: repetition counts and working times of keys
+ * changed, keys played by mistake removed, or the whole session excluded from statistics. The
+ * recorded data of a receipt is never overwritten. Corrections are appended to the receipt as an
+ * audit trail and applied in order whenever the receipt is read through [`Receipt::corrected`].
+ *
+ * Undoing the end of a session withdraws its receipt the same way, so the withdrawal reaches
+ * every device the receipt was synced to instead of the receipt coming back with the next sync.
+ */
+
+/// A correction to a finished session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiptChange {
+    /// Sets the number of repetitions of `key`.
+    Repetitions { key: usize, repetitions: i32 },
+    /// Sets the seconds worked on `key`.
+    WorkingSeconds { key: usize, seconds: i64 },
+    /// Removes `key` from the session, as if it was never selected.
+    RemoveKey { key: usize },
+    /// Leaves the session out of statistics, goals and assignments, or counts it again.
+    Excluded { excluded: bool },
+    /// Takes the session back because its end was undone, or brings it back when redone.
+    Withdrawn { withdrawn: bool },
+}
+
+/// One entry of the audit trail of a receipt.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReceiptEdit {
+    /// Unix timestamp (in seconds) at which the correction was made.
+    pub edited_at: i64,
+    pub change: ReceiptChange,
+}
+
+impl ReceiptChange {
+    /// Describes the change for the audit trail, naming keys with `note_names`.
+    pub fn describe(&self, note_names: &[String]) -> String {
+        let name = |key: &usize| {
+            note_names
+                .get(*key)
+                .cloned()
+                .unwrap_or_else(|| key.to_string())
+        };
+        match self {
+            ReceiptChange::Repetitions { key, repetitions } => {
+                format!("{}: repetitions set to {}", name(key), repetitions)
+            }
+            ReceiptChange::WorkingSeconds { key, seconds } => {
+                format!(
+                    "{}: duration set to {}",
+                    name(key),
+                    crate::statistics::format_duration(*seconds)
+                )
+            }
+            ReceiptChange::RemoveKey { key } => format!("{}: removed", name(key)),
+            ReceiptChange::Excluded { excluded: true } => "Excluded from statistics".to_string(),
+            ReceiptChange::Excluded { excluded: false } => "Included in statistics".to_string(),
+            ReceiptChange::Withdrawn { withdrawn: true } => "Undone".to_string(),
+            ReceiptChange::Withdrawn { withdrawn: false } => "Redone".to_string(),
+        }
+    }
+}
+
+impl Receipt {
+    /// Appends `change` to the audit trail.
+    ///
+    /// A correction made in the same second as the last one, or seemingly before it, is dated
+    /// right after it, so merging audit trails by date keeps the corrections in order.
+    pub fn edit(&mut self, change: ReceiptChange, edited_at: i64) {
+        let edited_at = match self.edits.last() {
+            Some(last) => edited_at.max(last.edited_at + 1),
+            None => edited_at,
+        };
+        self.edits.push(ReceiptEdit { edited_at, change });
+    }
+
+    /// The receipt as recorded, with every correction applied.
+    ///
+    /// ```
+    /// use jovian_cycles_core::edits::ReceiptChange;
+    /// use jovian_cycles_core::PracticeSessionState;
+    ///
+    /// let mut state = PracticeSessionState::default();
+    /// state.request_new_key();
+    /// state.finish();
+    /// let mut receipt = state.take_finished_receipts().remove(0);
+    /// let key = receipt.key_sequence[0];
+    ///
+    /// receipt.edit(ReceiptChange::Repetitions { key, repetitions: 4 }, 0);
+    /// assert_eq!(receipt.corrected().key_data_archive.unwrap().0[key].repetitions, 4);
+    /// assert_eq!(receipt.key_data_archive.as_ref().unwrap().0[key].repetitions, 1);
+    ///
+    /// receipt.edit(ReceiptChange::RemoveKey { key }, 0);
+    /// assert!(receipt.corrected().key_sequence.is_empty());
+    /// ```
+    pub fn corrected(&self) -> Receipt {
+        let mut receipt = self.clone();
+        self.edits.iter().for_each(|edit| {
+            let keys = receipt.key_data_archive.as_mut().map(|keys| &mut keys.0);
+            match (edit.change, keys) {
+                (ReceiptChange::Repetitions { key, repetitions }, Some(keys)) => {
+                    if let Some(data) = keys.get_mut(key) {
+                        data.repetitions = repetitions;
+                    }
+                }
+                (ReceiptChange::WorkingSeconds { key, seconds }, Some(keys)) => {
+                    if let Some(data) = keys.get_mut(key) {
+                        data.working_seconds = seconds;
+                    }
+                }
+                (ReceiptChange::RemoveKey { key }, keys) => {
+                    if let Some(data) = keys.and_then(|keys| keys.get_mut(key)) {
+                        data.repetitions = 0;
+                        data.working_seconds = 0;
+                        data.skips = 0;
+                    }
+                    receipt.key_sequence.retain(|selected| *selected != key);
+                    receipt.skips.retain(|skip| skip.key != key);
+                }
+                _ => {}
+            }
+        });
+        receipt
+    }
+
+    /// Whether the session was excluded from statistics by its last correction saying so.
+    pub fn is_excluded(&self) -> bool {
+        self.edits
+            .iter()
+            .rev()
+            .find_map(|edit| match edit.change {
+                ReceiptChange::Excluded { excluded } => Some(excluded),
+                _ => None,
+            })
+            .unwrap_or(false)
+    }
+
+    /// Whether the session was taken back by its last correction saying so.
+    pub fn is_withdrawn(&self) -> bool {
+        self.edits
+            .iter()
+            .rev()
+            .find_map(|edit| match edit.change {
+                ReceiptChange::Withdrawn { withdrawn } => Some(withdrawn),
+                _ => None,
+            })
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::Clock;
+    use crate::transitions::{PracticeSessionState, SessionCommand};
+
+    // A finished session with a skipped key, then two keys practiced for 60 seconds each.
+    fn finished() -> Receipt {
+        let mut state = PracticeSessionState::default();
+        state.session_data.clock = Clock::Manual(0);
+        state.session_data.set_seed(Some(5));
+        state.apply(SessionCommand::NewKey);
+        state.session_data.clock.set(10);
+        state.apply(SessionCommand::Skip);
+        state.session_data.clock.set(70);
+        state.apply(SessionCommand::NewKey);
+        state.session_data.clock.set(130);
+        state.apply(SessionCommand::Finish);
+        state.take_finished_receipts().remove(0)
+    }
+
+    fn key_data(receipt: &Receipt, key: usize) -> crate::models::KeyData {
+        receipt.key_data_archive.as_ref().unwrap().0[key]
+    }
+
+    #[test]
+    fn corrects_counts_and_times_without_touching_the_recording() {
+        let mut receipt = finished();
+        let recorded = receipt.clone();
+        let key = *receipt.key_sequence.last().unwrap();
+
+        receipt.edit(
+            ReceiptChange::Repetitions {
+                key,
+                repetitions: 7,
+            },
+            200,
+        );
+        receipt.edit(ReceiptChange::WorkingSeconds { key, seconds: 90 }, 200);
+        let corrected = receipt.corrected();
+        assert_eq!(key_data(&corrected, key).repetitions, 7);
+        assert_eq!(key_data(&corrected, key).working_seconds, 90);
+
+        // Everything else, and the recorded data itself, stays as it was.
+        assert_eq!(corrected.key_sequence, recorded.key_sequence);
+        assert_eq!(receipt.key_data_archive, recorded.key_data_archive);
+        assert_eq!(recorded.corrected(), recorded);
+    }
+
+    #[test]
+    fn later_corrections_override_earlier_ones() {
+        let mut receipt = finished();
+        let key = receipt.key_sequence[0];
+        let recorded = key_data(&receipt, key).repetitions;
+
+        receipt.edit(
+            ReceiptChange::Repetitions {
+                key,
+                repetitions: 9,
+            },
+            200,
+        );
+        receipt.edit(
+            ReceiptChange::Repetitions {
+                key,
+                repetitions: recorded,
+            },
+            300,
+        );
+        assert_eq!(key_data(&receipt.corrected(), key), key_data(&receipt, key));
+    }
+
+    #[test]
+    fn removing_a_key_drops_its_data_selections_and_skips() {
+        let mut receipt = finished();
+        let skipped = receipt.skips[0].key;
+        assert!(receipt.key_sequence.contains(&skipped));
+
+        receipt.edit(ReceiptChange::RemoveKey { key: skipped }, 200);
+        let corrected = receipt.corrected();
+        let data = key_data(&corrected, skipped);
+        assert_eq!(
+            (data.repetitions, data.working_seconds, data.skips),
+            (0, 0, 0)
+        );
+        assert!(corrected.skips.is_empty());
+        let kept: Vec<usize> = receipt
+            .key_sequence
+            .iter()
+            .copied()
+            .filter(|key| *key != skipped)
+            .collect();
+        assert_eq!(corrected.key_sequence, kept);
+    }
+
+    #[test]
+    fn ignores_corrections_to_keys_that_do_not_exist() {
+        let mut receipt = finished();
+        receipt.edit(
+            ReceiptChange::Repetitions {
+                key: 12,
+                repetitions: 3,
+            },
+            200,
+        );
+        receipt.edit(ReceiptChange::RemoveKey { key: 12 }, 200);
+        let mut expected = receipt.clone();
+        expected.edits.clear();
+        let mut corrected = receipt.corrected();
+        corrected.edits.clear();
+        assert_eq!(corrected, expected);
+    }
+
+    #[test]
+    fn exclusion_and_withdrawal_follow_the_last_correction() {
+        let mut receipt = finished();
+        assert!(!receipt.is_excluded());
+        assert!(!receipt.is_withdrawn());
+
+        receipt.edit(ReceiptChange::Excluded { excluded: true }, 200);
+        receipt.edit(ReceiptChange::Withdrawn { withdrawn: true }, 200);
+        assert!(receipt.is_excluded());
+        assert!(receipt.is_withdrawn());
+
+        receipt.edit(ReceiptChange::Excluded { excluded: false }, 300);
+        receipt.edit(ReceiptChange::Withdrawn { withdrawn: false }, 300);
+        assert!(!receipt.is_excluded());
+        assert!(!receipt.is_withdrawn());
+
+        // Neither changes the practice data.
+        let mut corrected = receipt.corrected();
+        corrected.edits.clear();
+        receipt.edits.clear();
+        assert_eq!(corrected, receipt);
+    }
+
+    #[test]
+    fn dates_corrections_after_the_previous_one() {
+        let mut receipt = finished();
+        receipt.edit(ReceiptChange::Excluded { excluded: true }, 500);
+        receipt.edit(ReceiptChange::Excluded { excluded: false }, 500);
+        receipt.edit(ReceiptChange::Excluded { excluded: true }, 100);
+        let dates: Vec<i64> = receipt.edits.iter().map(|edit| edit.edited_at).collect();
+        assert_eq!(dates, vec![500, 501, 502]);
+    }
+}
@@ -0,0 +1,471 @@
+use crate::scales::{pitch_class, ScaleKind};
+
+use serde::{Deserialize, Serialize};
+
+/*
+ * Scale Evaluation
+ *
+ * Compares the notes of a played run against the scale expected for the current key. Runs may
+ * start on the root in any octave; the octave of the first played note decides where the
+ * expected scale starts.
+ */
+
+/// A note being pressed, with the time in milliseconds at which it was played.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NoteEvent {
+    pub timestamp_ms: u64,
+    pub note: u8,
+}
+
+/// Order in which the notes of the scale are expected.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum RunDirection {
+    #[default]
+    Ascending,
+    Descending,
+    /// Up to the top and back down to the root.
+    UpAndDown,
+}
+
+impl RunDirection {
+    pub const ALL: [RunDirection; 3] = [
+        RunDirection::Ascending,
+        RunDirection::Descending,
+        RunDirection::UpAndDown,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            RunDirection::Ascending => "Ascending",
+            RunDirection::Descending => "Descending",
+            RunDirection::UpAndDown => "Up and down",
+        }
+    }
+}
+
+/// The scale run a player is expected to perform.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ScaleExercise {
+    /// Pitch class of the root, counted in semitones from C.
+    pub root: usize,
+    pub kind: ScaleKind,
+    pub direction: RunDirection,
+    /// Number of octaves spanned by the run, at least one.
+    pub octaves: u8,
+}
+
+impl ScaleExercise {
+    /// Number of notes in a correct run.
+    pub fn note_count(&self) -> usize {
+        let one_way = 7 * self.octaves.max(1) as usize + 1;
+        match self.direction {
+            RunDirection::Ascending | RunDirection::Descending => one_way,
+            RunDirection::UpAndDown => 2 * one_way - 1,
+        }
+    }
+
+    /// Expected notes of a run whose first note is `first_note`.
+    ///
+    /// The run starts on the root closest to `first_note`, or on the top of the scale for
+    /// descending runs.
+    pub fn expected_notes(&self, first_note: u8) -> Vec<u8> {
+        let octaves = self.octaves.max(1) as i32;
+        let span = 12 * octaves;
+
+        let offset = (self.root as i32 - pitch_class(first_note) as i32).rem_euclid(12);
+        let offset = if offset > 6 { offset - 12 } else { offset };
+        let start = first_note as i32 + offset;
+        let bottom = match self.direction {
+            RunDirection::Descending => start - span,
+            _ => start,
+        };
+
+        let mut ascending: Vec<i32> = (0..octaves)
+            .flat_map(|octave| {
+                self.kind
+                    .intervals()
+                    .into_iter()
+                    .map(move |interval| bottom + 12 * octave + interval as i32)
+            })
+            .collect();
+        ascending.push(bottom + span);
+
+        let notes: Vec<i32> = match self.direction {
+            RunDirection::Ascending => ascending,
+            RunDirection::Descending => ascending.into_iter().rev().collect(),
+            RunDirection::UpAndDown => {
+                let descending: Vec<i32> = ascending.iter().rev().skip(1).copied().collect();
+                ascending.into_iter().chain(descending).collect()
+            }
+        };
+
+        notes
+            .into_iter()
+            .map(|note| note.clamp(0, 127) as u8)
+            .collect()
+    }
+}
+
+/// A played note that does not match the expected one at the same position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WrongNote {
+    /// Position of the note in the run.
+    pub index: usize,
+    /// Note expected at this position, `None` for notes played after the run should have ended.
+    pub expected: Option<u8>,
+    pub played: u8,
+}
+
+/// Result of comparing a played run against a [`ScaleExercise`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Evaluation {
+    pub expected: Vec<u8>,
+    pub wrong_notes: Vec<WrongNote>,
+    /// Number of expected notes that were never played.
+    pub missing_notes: usize,
+    /// Notes per minute, from the average time between notes.
+    pub tempo_bpm: Option<f32>,
+    /// How regular the time between notes is, from 0 (erratic) to 1 (perfectly even).
+    pub evenness: Option<f32>,
+}
+
+impl Evaluation {
+    /// Whether every expected note was played in order with no extra notes.
+    pub fn is_correct(&self) -> bool {
+        self.wrong_notes.is_empty() && self.missing_notes == 0
+    }
+}
+
+/// Evaluates a run of played notes, given in the order they were played.
+pub fn evaluate(exercise: &ScaleExercise, events: &[NoteEvent]) -> Evaluation {
+    let expected: Vec<u8> = match events.first() {
+        Some(first) => exercise.expected_notes(first.note),
+        None => Vec::new(),
+    };
+
+    let wrong_notes: Vec<WrongNote> = events
+        .iter()
+        .enumerate()
+        .filter_map(|(index, event)| {
+            let expected_note = expected.get(index).copied();
+            if expected_note == Some(event.note) {
+                None
+            } else {
+                Some(WrongNote {
+                    index,
+                    expected: expected_note,
+                    played: event.note,
+                })
+            }
+        })
+        .collect();
+
+    let missing_notes = if events.is_empty() {
+        exercise.note_count()
+    } else {
+        expected.len().saturating_sub(events.len())
+    };
+
+    let intervals: Vec<f32> = events
+        .windows(2)
+        .map(|pair| pair[1].timestamp_ms.saturating_sub(pair[0].timestamp_ms) as f32)
+        .collect();
+    let (tempo_bpm, evenness) = timing(&intervals);
+
+    Evaluation {
+        expected,
+        wrong_notes,
+        missing_notes,
+        tempo_bpm,
+        evenness,
+    }
+}
+
+fn timing(intervals: &[f32]) -> (Option<f32>, Option<f32>) {
+    if intervals.is_empty() {
+        return (None, None);
+    }
+
+    let mean = intervals.iter().sum::<f32>() / intervals.len() as f32;
+    if mean <= 0.0 {
+        return (None, None);
+    }
+
+    let variance = intervals
+        .iter()
+        .map(|interval| (interval - mean).powi(2))
+        .sum::<f32>()
+        / intervals.len() as f32;
+    let coefficient_of_variation = variance.sqrt() / mean;
+
+    (
+        Some(60_000.0 / mean),
+        Some((1.0 - coefficient_of_variation).clamp(0.0, 1.0)),
+    )
+}
+
+/// Splits a stream of notes into runs wherever no note was played for more than `max_gap_ms`.
+pub fn split_runs(events: &[NoteEvent], max_gap_ms: u64) -> Vec<&[NoteEvent]> {
+    let mut runs: Vec<&[NoteEvent]> = Vec::new();
+    let mut start = 0;
+
+    for index in 1..events.len() {
+        let gap = events[index]
+            .timestamp_ms
+            .saturating_sub(events[index - 1].timestamp_ms);
+        if gap > max_gap_ms {
+            runs.push(&events[start..index]);
+            start = index;
+        }
+    }
+    if start < events.len() {
+        runs.push(&events[start..]);
+    }
+
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const C4: u8 = 60;
+    const C_MAJOR: [u8; 8] = [60, 62, 64, 65, 67, 69, 71, 72];
+
+    fn exercise(
+        root: usize,
+        kind: ScaleKind,
+        direction: RunDirection,
+        octaves: u8,
+    ) -> ScaleExercise {
+        ScaleExercise {
+            root,
+            kind,
+            direction,
+            octaves,
+        }
+    }
+
+    // Notes played every `step_ms` milliseconds.
+    fn played(notes: &[u8], step_ms: u64) -> Vec<NoteEvent> {
+        notes
+            .iter()
+            .enumerate()
+            .map(|(index, note)| NoteEvent {
+                timestamp_ms: index as u64 * step_ms,
+                note: *note,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn grades_correct_runs_in_every_direction() {
+        let up_and_down: Vec<u8> = C_MAJOR
+            .iter()
+            .chain(C_MAJOR.iter().rev().skip(1))
+            .copied()
+            .collect();
+        let descending: Vec<u8> = C_MAJOR.iter().rev().copied().collect();
+        [
+            (RunDirection::Ascending, C_MAJOR.to_vec()),
+            (RunDirection::Descending, descending),
+            (RunDirection::UpAndDown, up_and_down),
+        ]
+        .into_iter()
+        .for_each(|(direction, notes)| {
+            let exercise = exercise(0, ScaleKind::Major, direction, 1);
+            assert_eq!(exercise.note_count(), notes.len());
+            let evaluation = evaluate(&exercise, &played(&notes, 250));
+            assert!(evaluation.is_correct(), "{direction:?}: {evaluation:?}");
+            assert_eq!(evaluation.expected, notes);
+            assert_eq!(evaluation.tempo_bpm, Some(240.0));
+            assert_eq!(evaluation.evenness, Some(1.0));
+        });
+    }
+
+    #[test]
+    fn follows_the_kind_and_root_of_the_scale() {
+        // A harmonic minor, from A3.
+        let notes = [57, 59, 60, 62, 64, 65, 68, 69];
+        let exercise = exercise(9, ScaleKind::HarmonicMinor, RunDirection::Ascending, 1);
+        assert!(evaluate(&exercise, &played(&notes, 200)).is_correct());
+
+        // The natural minor has a G instead of the G#.
+        let exercise = ScaleExercise {
+            kind: ScaleKind::NaturalMinor,
+            ..exercise
+        };
+        assert_eq!(
+            evaluate(&exercise, &played(&notes, 200)).wrong_notes,
+            vec![WrongNote {
+                index: 6,
+                expected: Some(67),
+                played: 68
+            }]
+        );
+    }
+
+    #[test]
+    fn spans_several_octaves() {
+        let exercise = exercise(2, ScaleKind::Major, RunDirection::Ascending, 2);
+        let expected = exercise.expected_notes(50);
+        assert_eq!(exercise.note_count(), 15);
+        assert_eq!(
+            expected,
+            vec![50, 52, 54, 55, 57, 59, 61, 62, 64, 66, 67, 69, 71, 73, 74]
+        );
+        assert!(evaluate(&exercise, &played(&expected, 300)).is_correct());
+
+        // Descending runs start on the top root and end two octaves down.
+        let exercise = ScaleExercise {
+            direction: RunDirection::Descending,
+            ..exercise
+        };
+        let descending = exercise.expected_notes(74);
+        assert_eq!(
+            descending,
+            expected.iter().rev().copied().collect::<Vec<u8>>()
+        );
+
+        let exercise = ScaleExercise {
+            direction: RunDirection::UpAndDown,
+            octaves: 3,
+            ..exercise
+        };
+        let notes = exercise.expected_notes(38);
+        assert_eq!((notes.len(), exercise.note_count()), (43, 43));
+        assert_eq!((notes[0], notes[21], notes[42]), (38, 74, 38));
+    }
+
+    #[test]
+    fn treats_zero_octaves_as_one() {
+        let exercise = exercise(0, ScaleKind::Major, RunDirection::Ascending, 0);
+        assert_eq!(exercise.note_count(), 8);
+        assert_eq!(exercise.expected_notes(C4), C_MAJOR.to_vec());
+    }
+
+    #[test]
+    fn starts_on_the_closest_root() {
+        let exercise = exercise(0, ScaleKind::Major, RunDirection::Ascending, 1);
+        assert_eq!(exercise.expected_notes(72)[0], 72);
+        // A B below the C is a wrong first note, not the start of a run from the C below.
+        assert_eq!(exercise.expected_notes(71)[0], 72);
+        assert_eq!(exercise.expected_notes(65)[0], 60);
+        assert_eq!(exercise.expected_notes(67)[0], 72);
+    }
+
+    #[test]
+    fn clamps_notes_to_the_midi_range() {
+        let exercise = exercise(0, ScaleKind::Major, RunDirection::Ascending, 2);
+        let notes = exercise.expected_notes(120);
+        assert_eq!(notes.first(), Some(&120));
+        assert_eq!(notes.last(), Some(&127));
+    }
+
+    #[test]
+    fn reports_wrong_notes() {
+        let exercise = exercise(0, ScaleKind::Major, RunDirection::Ascending, 1);
+        let mut notes = C_MAJOR;
+        notes[2] = 63;
+        notes[5] = 70;
+        let evaluation = evaluate(&exercise, &played(&notes, 250));
+        assert!(!evaluation.is_correct());
+        assert_eq!(evaluation.missing_notes, 0);
+        assert_eq!(
+            evaluation.wrong_notes,
+            vec![
+                WrongNote {
+                    index: 2,
+                    expected: Some(64),
+                    played: 63
+                },
+                WrongNote {
+                    index: 5,
+                    expected: Some(69),
+                    played: 70
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_missing_and_extra_notes() {
+        let exercise = exercise(0, ScaleKind::Major, RunDirection::Ascending, 1);
+        let evaluation = evaluate(&exercise, &played(&C_MAJOR[..5], 250));
+        assert!(evaluation.wrong_notes.is_empty());
+        assert_eq!(evaluation.missing_notes, 3);
+        assert!(!evaluation.is_correct());
+
+        let mut notes = C_MAJOR.to_vec();
+        notes.push(74);
+        let evaluation = evaluate(&exercise, &played(&notes, 250));
+        assert_eq!(
+            evaluation.wrong_notes,
+            vec![WrongNote {
+                index: 8,
+                expected: None,
+                played: 74
+            }]
+        );
+
+        let evaluation = evaluate(&exercise, &[]);
+        assert_eq!(evaluation.missing_notes, 8);
+        assert_eq!((evaluation.tempo_bpm, evaluation.evenness), (None, None));
+    }
+
+    #[test]
+    fn counts_a_retry_without_a_pause_against_the_run() {
+        let exercise = exercise(0, ScaleKind::Major, RunDirection::Ascending, 1);
+        // A slip on the third note, then the run played again from the root.
+        let notes: Vec<u8> = [60, 62, 63].into_iter().chain(C_MAJOR).collect();
+        let evaluation = evaluate(&exercise, &played(&notes, 250));
+        assert!(!evaluation.is_correct());
+        assert_eq!(evaluation.wrong_notes.len(), 9);
+    }
+
+    #[test]
+    fn grades_a_retry_after_a_pause_on_its_own() {
+        let exercise = exercise(0, ScaleKind::Major, RunDirection::Ascending, 1);
+        let mut events = played(&[60, 62, 63], 250);
+        events.extend(played(&C_MAJOR, 250).into_iter().map(|event| NoteEvent {
+            timestamp_ms: event.timestamp_ms + 5_000,
+            ..event
+        }));
+
+        let runs = split_runs(&events, 2_000);
+        assert_eq!(runs.len(), 2);
+        let first = evaluate(&exercise, runs[0]);
+        assert_eq!(first.wrong_notes.len(), 1);
+        assert_eq!(first.missing_notes, 5);
+        assert!(evaluate(&exercise, runs[1]).is_correct());
+    }
+
+    #[test]
+    fn measures_uneven_timing() {
+        let exercise = exercise(0, ScaleKind::Major, RunDirection::Ascending, 1);
+        let times = [0, 100, 500, 600, 1000, 1100, 1500, 1600];
+        let events: Vec<NoteEvent> = C_MAJOR
+            .iter()
+            .zip(times)
+            .map(|(note, timestamp_ms)| NoteEvent {
+                timestamp_ms,
+                note: *note,
+            })
+            .collect();
+        let evaluation = evaluate(&exercise, &events);
+        assert!(evaluation.is_correct());
+        let evenness = evaluation.evenness.unwrap();
+        assert!(evenness < 0.5, "{evenness}");
+        // All the notes at once give no tempo.
+        let evaluation = evaluate(&exercise, &played(&C_MAJOR, 0));
+        assert_eq!((evaluation.tempo_bpm, evaluation.evenness), (None, None));
+    }
+
+    #[test]
+    fn splits_runs_on_long_gaps() {
+        assert!(split_runs(&[], 100).is_empty());
+        let events = played(&[60, 62, 64], 100);
+        assert_eq!(split_runs(&events, 100).len(), 1);
+        assert_eq!(split_runs(&events, 99).len(), 3);
+    }
+}
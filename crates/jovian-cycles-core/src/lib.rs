@@ -0,0 +1,52 @@
+//! Practice engine behind jovian cycles.
+//!
+//! The crate holds everything needed to run a practice session without a user interface: the
+//! session state machine ([`transitions`]), the data it records ([`models`]), how the next key is
+//! picked ([`selection`]), which keys need practice most ([`weakness`]) and summaries of finished
+//! sessions ([`statistics`]), which can be corrected afterwards ([`edits`]). A [`Profile`] keeps
+//! the finished sessions, key weights and goals of one player, along with the [`assignment`]s
+//! given by a teacher. Sessions read the time through a [`Clock`], which lets [`replay`] run
+//! scripted sessions with fake timestamps, and [`undo`] takes session actions back.
+//! [`midi`] decodes MIDI input into session commands, [`scales`] checks played notes against the
+//! current key and [`evaluation`] grades whole scale runs. [`smf`] exports keys as MIDI files,
+//! [`synth`] renders them as audio and [`wav`] reads and writes audio files. [`pitch`] detects the
+//! notes played on acoustic instruments and drives the tuner. [`sync`] merges the history of a
+//! profile kept on several devices.
+//!
+//! ```
+//! use jovian_cycles_core::PracticeSessionState;
+//!
+//! let mut state = PracticeSessionState::default();
+//! state.request_new_key();
+//! state.pause();
+//! state.resume();
+//! state.finish();
+//!
+//! assert!(state.session_data.receipt.is_some());
+//! ```
+
+pub mod assignment;
+pub mod clock;
+pub mod edits;
+pub mod evaluation;
+pub mod midi;
+pub mod models;
+pub mod pitch;
+pub mod profile;
+pub mod replay;
+pub mod scales;
+pub mod selection;
+pub mod smf;
+pub mod statistics;
+pub mod sync;
+pub mod synth;
+pub mod transitions;
+pub mod undo;
+pub mod utils;
+pub mod wav;
+pub mod weakness;
+
+pub use clock::Clock;
+pub use models::{KeyData, KeysMap, PracticeSessionData, Receipt, TimeCode};
+pub use profile::Profile;
+pub use transitions::{PracticeSessionState, SessionCheckpoint, SessionCommand, SessionStates};
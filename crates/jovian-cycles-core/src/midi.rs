@@ -0,0 +1,377 @@
+use crate::transitions::SessionCommand;
+
+use serde::{Deserialize, Serialize};
+
+/*
+ * MIDI Input
+ *
+ * Decodes raw MIDI bytes into the few channel messages the practice engine cares about and maps
+ * them to session commands. Nothing here talks to a device, so any byte stream can be fed in.
+ */
+
+/// A channel message decoded from a MIDI byte stream. Channels are counted from zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MidiMessage {
+    NoteOn {
+        channel: u8,
+        note: u8,
+        velocity: u8,
+    },
+    NoteOff {
+        channel: u8,
+        note: u8,
+        velocity: u8,
+    },
+    ControlChange {
+        channel: u8,
+        controller: u8,
+        value: u8,
+    },
+}
+
+/// Incremental MIDI decoder.
+///
+/// Handles running status, skips system exclusive and system common messages and ignores
+/// real-time bytes wherever they appear. Messages other than [`MidiMessage`] are dropped.
+#[derive(Debug, Clone, Default)]
+pub struct MidiParser {
+    running_status: Option<u8>,
+    data: Vec<u8>,
+    in_sysex: bool,
+}
+
+impl MidiParser {
+    /// Feeds one byte, returning a message once its last data byte has arrived.
+    pub fn push(&mut self, byte: u8) -> Option<MidiMessage> {
+        // Real-time messages may be interleaved anywhere and carry no data.
+        if byte >= 0xF8 {
+            return None;
+        }
+
+        if byte >= 0x80 {
+            self.data.clear();
+            self.in_sysex = byte == 0xF0;
+            self.running_status = if byte < 0xF0 { Some(byte) } else { None };
+            return None;
+        }
+
+        if self.in_sysex {
+            return None;
+        }
+
+        let status = self.running_status?;
+        self.data.push(byte);
+        if self.data.len() < data_length(status) {
+            return None;
+        }
+
+        let message = decode(status, &self.data);
+        self.data.clear();
+        message
+    }
+
+    /// Feeds a slice of bytes, returning every message completed by it.
+    pub fn parse(&mut self, bytes: &[u8]) -> Vec<MidiMessage> {
+        bytes.iter().filter_map(|byte| self.push(*byte)).collect()
+    }
+}
+
+/// Decodes a complete byte stream with a fresh [`MidiParser`].
+pub fn parse(bytes: &[u8]) -> Vec<MidiMessage> {
+    MidiParser::default().parse(bytes)
+}
+
+fn data_length(status: u8) -> usize {
+    match status & 0xF0 {
+        0xC0 | 0xD0 => 1,
+        _ => 2,
+    }
+}
+
+fn decode(status: u8, data: &[u8]) -> Option<MidiMessage> {
+    let channel = status & 0x0F;
+
+    match (status & 0xF0, data) {
+        (0x90, [note, 0]) | (0x80, [note, _]) => Some(MidiMessage::NoteOff {
+            channel,
+            note: *note,
+            velocity: data[1],
+        }),
+        (0x90, [note, velocity]) => Some(MidiMessage::NoteOn {
+            channel,
+            note: *note,
+            velocity: *velocity,
+        }),
+        (0xB0, [controller, value]) => Some(MidiMessage::ControlChange {
+            channel,
+            controller: *controller,
+            value: *value,
+        }),
+        _ => None,
+    }
+}
+
+/*
+ * MIDI Bindings
+ */
+
+/// Controller number of the sustain pedal.
+pub const SUSTAIN_PEDAL: u8 = 64;
+/// Controller number of the sostenuto pedal.
+pub const SOSTENUTO_PEDAL: u8 = 66;
+/// Controller number of the soft pedal.
+pub const SOFT_PEDAL: u8 = 67;
+
+/// MIDI event that can trigger a session command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum MidiTrigger {
+    /// A note being pressed.
+    Note(u8),
+    /// A controller crossing from released (below 64) to pressed, such as a pedal going down.
+    Control(u8),
+}
+
+/// Which MIDI triggers run which session commands.
+///
+/// Nothing is bound by default: pedals are part of playing, so a sustain pedal pressed mid-run
+/// would otherwise skip to the next key. Bindings are learned from the player's device instead.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct MidiBindings {
+    pub bindings: Vec<(MidiTrigger, SessionCommand)>,
+}
+
+impl MidiBindings {
+    pub fn command(&self, trigger: MidiTrigger) -> Option<SessionCommand> {
+        self.bindings
+            .iter()
+            .find(|(bound, _)| *bound == trigger)
+            .map(|(_, command)| *command)
+    }
+
+    /// Binds `trigger` to `command`, replacing any previous binding of either.
+    pub fn bind(&mut self, trigger: MidiTrigger, command: SessionCommand) {
+        self.bindings
+            .retain(|(bound, bound_command)| *bound != trigger && *bound_command != command);
+        self.bindings.push((trigger, command));
+    }
+}
+
+/// Trigger corresponding to `message`, used to learn new bindings.
+pub fn trigger_for(message: &MidiMessage) -> Option<MidiTrigger> {
+    match *message {
+        MidiMessage::NoteOn { note, .. } => Some(MidiTrigger::Note(note)),
+        MidiMessage::ControlChange {
+            controller, value, ..
+        } if value >= 64 => Some(MidiTrigger::Control(controller)),
+        _ => None,
+    }
+}
+
+/// Turns decoded MIDI messages into session commands according to a set of [`MidiBindings`].
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct MidiController {
+    pub bindings: MidiBindings,
+    #[serde(skip, default = "released")]
+    pressed: [bool; 128],
+}
+
+fn released() -> [bool; 128] {
+    [false; 128]
+}
+
+impl MidiController {
+    pub fn new(bindings: MidiBindings) -> Self {
+        MidiController {
+            bindings,
+            pressed: released(),
+        }
+    }
+
+    /// Session command triggered by `message`, if any.
+    ///
+    /// Controllers only trigger when they are pressed, so holding a pedal down runs its command
+    /// once.
+    pub fn command_for(&mut self, message: &MidiMessage) -> Option<SessionCommand> {
+        match *message {
+            MidiMessage::NoteOn { note, .. } => self.bindings.command(MidiTrigger::Note(note)),
+            MidiMessage::ControlChange {
+                controller, value, ..
+            } => {
+                let index = (controller & 0x7F) as usize;
+                let was_pressed = self.pressed[index];
+                let is_pressed = value >= 64;
+                self.pressed[index] = is_pressed;
+
+                if is_pressed && !was_pressed {
+                    self.bindings.command(MidiTrigger::Control(controller))
+                } else {
+                    None
+                }
+            }
+            MidiMessage::NoteOff { .. } => None,
+        }
+    }
+}
+
+impl Default for MidiController {
+    fn default() -> Self {
+        MidiController::new(MidiBindings::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note_on(channel: u8, note: u8, velocity: u8) -> MidiMessage {
+        MidiMessage::NoteOn {
+            channel,
+            note,
+            velocity,
+        }
+    }
+
+    fn note_off(channel: u8, note: u8, velocity: u8) -> MidiMessage {
+        MidiMessage::NoteOff {
+            channel,
+            note,
+            velocity,
+        }
+    }
+
+    #[test]
+    fn decodes_channel_messages() {
+        assert_eq!(
+            parse(&[0x93, 60, 100, 0x83, 60, 40, 0xB1, 64, 127]),
+            vec![
+                note_on(3, 60, 100),
+                note_off(3, 60, 40),
+                MidiMessage::ControlChange {
+                    channel: 1,
+                    controller: 64,
+                    value: 127
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn follows_running_status() {
+        assert_eq!(
+            parse(&[0x90, 60, 100, 64, 90, 67, 80]),
+            vec![note_on(0, 60, 100), note_on(0, 64, 90), note_on(0, 67, 80)]
+        );
+        // Program changes carry one data byte, so running status repeats them byte by byte.
+        assert_eq!(
+            parse(&[0xC0, 1, 2, 3, 0x90, 60, 1]),
+            vec![note_on(0, 60, 1)]
+        );
+    }
+
+    #[test]
+    fn reads_velocity_zero_as_note_off() {
+        assert_eq!(
+            parse(&[0x95, 60, 100, 60, 0]),
+            vec![note_on(5, 60, 100), note_off(5, 60, 0)]
+        );
+    }
+
+    #[test]
+    fn ignores_interleaved_realtime_bytes() {
+        assert_eq!(
+            parse(&[0xF8, 0x90, 0xF8, 60, 0xFE, 100, 0xF8, 62, 0xFE, 0xFA, 0]),
+            vec![note_on(0, 60, 100), note_off(0, 62, 0)]
+        );
+    }
+
+    #[test]
+    fn skips_system_exclusive() {
+        assert_eq!(
+            parse(&[0x90, 60, 100, 0xF0, 0x7E, 60, 100, 0x09, 0xF7, 61, 100, 0x90, 62, 100]),
+            // Data bytes right after the sysex have no running status to belong to.
+            vec![note_on(0, 60, 100), note_on(0, 62, 100)]
+        );
+        // Real-time bytes inside a sysex do not end it.
+        assert_eq!(
+            parse(&[0xF0, 1, 0xF8, 2, 3, 0xF7, 0x90, 60, 100]),
+            vec![note_on(0, 60, 100)]
+        );
+        // A status byte ends a sysex missing its 0xF7.
+        assert_eq!(
+            parse(&[0xF0, 1, 2, 0x90, 60, 100]),
+            vec![note_on(0, 60, 100)]
+        );
+    }
+
+    #[test]
+    fn skips_system_common_messages() {
+        // Song position pointer, then data bytes that no status applies to.
+        assert_eq!(
+            parse(&[0x90, 60, 100, 0xF2, 1, 2, 64, 0, 0x80, 60, 0]),
+            vec![note_on(0, 60, 100), note_off(0, 60, 0)]
+        );
+    }
+
+    #[test]
+    fn completes_messages_split_across_callbacks() {
+        let mut parser = MidiParser::default();
+        assert_eq!(parser.parse(&[0x90]), vec![]);
+        assert_eq!(parser.parse(&[60]), vec![]);
+        assert_eq!(parser.parse(&[100, 62]), vec![note_on(0, 60, 100)]);
+        assert_eq!(parser.parse(&[0xF8]), vec![]);
+        assert_eq!(parser.parse(&[0]), vec![note_off(0, 62, 0)]);
+        assert_eq!(parser.parse(&[0xB0, 64]), vec![]);
+        assert_eq!(
+            parser.parse(&[127]),
+            vec![MidiMessage::ControlChange {
+                channel: 0,
+                controller: 64,
+                value: 127
+            }]
+        );
+    }
+
+    #[test]
+    fn drops_data_without_status() {
+        assert_eq!(parse(&[60, 100, 0x90, 60, 100]), vec![note_on(0, 60, 100)]);
+    }
+
+    #[test]
+    fn pedals_are_left_unbound_by_default() {
+        let mut controller = MidiController::default();
+        [SUSTAIN_PEDAL, SOSTENUTO_PEDAL, SOFT_PEDAL]
+            .iter()
+            .for_each(|controller_number| {
+                let pedal = MidiMessage::ControlChange {
+                    channel: 0,
+                    controller: *controller_number,
+                    value: 127,
+                };
+                assert_eq!(controller.command_for(&pedal), None);
+            });
+    }
+
+    #[test]
+    fn pedals_trigger_once_per_press() {
+        let mut bindings = MidiBindings::default();
+        bindings.bind(MidiTrigger::Control(SUSTAIN_PEDAL), SessionCommand::NewKey);
+        let mut controller = MidiController::new(bindings);
+        let pedal = |value| MidiMessage::ControlChange {
+            channel: 0,
+            controller: SUSTAIN_PEDAL,
+            value,
+        };
+        assert_eq!(
+            controller.command_for(&pedal(127)),
+            Some(SessionCommand::NewKey)
+        );
+        assert_eq!(controller.command_for(&pedal(100)), None);
+        assert_eq!(controller.command_for(&pedal(0)), None);
+        assert_eq!(
+            controller.command_for(&pedal(64)),
+            Some(SessionCommand::NewKey)
+        );
+    }
+}
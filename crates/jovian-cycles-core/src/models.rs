@@ -0,0 +1,620 @@
+use crate::clock::Clock;
+use crate::edits::ReceiptEdit;
+use crate::selection::{
+    choose_in_order, choose_pinned, choose_uniform, choose_weighted, probabilities, Constraints,
+    Strategy,
+};
+use crate::weakness::KeyWeakness;
+
+use color_eyre::eyre::{eyre, Result};
+use rand::SeedableRng;
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+
+/*
+ * Generic Data Types
+ */
+/// Name of a state together with the unix timestamp (in seconds) at which it was entered.
+pub type TimeCode = (String, i64);
+
+/// Practice data for one of the twelve keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyData {
+    /// Index of the key, counted in semitones from C.
+    pub nid: usize,
+    /// Number of times the key has been worked on.
+    pub repetitions: i32,
+    /// Relative likelihood of the key being selected next.
+    pub weight: i32,
+    /// Seconds spent working on the key, pauses excluded.
+    #[serde(default)]
+    pub working_seconds: i64,
+    /// Whether the key is selected in every session, before any other key comes up twice.
+    #[serde(default)]
+    pub pinned: bool,
+    /// Last self-rating of the key in the session, from 1 to 5.
+    #[serde(default)]
+    pub rating: Option<u8>,
+    /// Number of times the key was skipped instead of practiced.
+    #[serde(default)]
+    pub skips: i32,
+}
+
+/// A selected key the player skipped instead of practicing it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SkipEvent {
+    /// Index of the skipped key.
+    pub key: usize,
+    /// Unix timestamp (in seconds) of the skip.
+    pub skipped_at: i64,
+    /// Why the key was skipped, if the player said so.
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// Practice data for every key, indexed by [`KeyData::nid`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeysMap(pub Vec<KeyData>);
+
+/// Summary of a practice session, handed out when the session finishes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Receipt {
+    /// Practice data of every key at the end of the session.
+    pub key_data_archive: Option<KeysMap>,
+    /// States the session went through, with the time each was entered, oldest first.
+    pub time_stamp_archive: Option<Vec<TimeCode>>,
+    /// Seed the keys were selected with, if the session was seeded.
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// Keys in the order they were selected.
+    #[serde(default)]
+    pub key_sequence: Vec<usize>,
+    /// Title of the assignment the session was practiced for.
+    #[serde(default)]
+    pub assignment: Option<String>,
+    /// Identifies the session across devices, see [`crate::sync`].
+    #[serde(default)]
+    pub session_id: String,
+    /// Length in seconds of every rest, in the order they were taken.
+    #[serde(default)]
+    pub rests: Vec<i64>,
+    /// Corrections made after the session finished, oldest first.
+    #[serde(default)]
+    pub edits: Vec<ReceiptEdit>,
+    /// Whether the session was practiced without the app and logged by hand afterwards.
+    #[serde(default)]
+    pub manual: bool,
+    /// Every skip, in the order they happened.
+    #[serde(default)]
+    pub skips: Vec<SkipEvent>,
+}
+
+/// Everything recorded while a practice session is running.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PracticeSessionData {
+    /// Aggregate practice session time data.
+    pub practice_session_history: Option<Vec<TimeCode>>,
+    /// Aggregate data about keys, repetitions, and probability.
+    pub all_keys_map: KeysMap,
+    /// Data associated with current key.
+    pub current_key_data: Option<KeyData>,
+    /// Keys in the order they were selected.
+    #[serde(default)]
+    pub key_history: Vec<usize>,
+    /// State name and start timestamp.
+    start_timestamp: Option<TimeCode>,
+    /// Receipt of practice session given when process finishes.
+    pub receipt: Option<Receipt>,
+    /// Source of the recorded timestamps.
+    #[serde(skip)]
+    pub clock: Clock,
+    /// How the next key is selected.
+    #[serde(default)]
+    pub strategy: Strategy,
+    /// Order followed by the in order strategy.
+    #[serde(default)]
+    pub key_order: Vec<usize>,
+    /// Weakness of every key, used by the weakest keys strategy.
+    #[serde(default)]
+    pub weakness: Vec<KeyWeakness>,
+    /// Which keys may follow the keys selected so far.
+    #[serde(default)]
+    pub constraints: Constraints,
+    /// Assignment being practiced, if any.
+    #[serde(default)]
+    pub assignment: Option<String>,
+    /// Identifies the session in its receipt.
+    #[serde(default = "new_session_id")]
+    pub session_id: String,
+    /// Length of every finished rest.
+    #[serde(default)]
+    pub rests: Vec<i64>,
+    /// Every skip of the session.
+    #[serde(default)]
+    pub skips: Vec<SkipEvent>,
+    /// Weight a key gains in selection every time it is skipped.
+    #[serde(default)]
+    pub skip_bonus: i32,
+    /// Seed of the key selection, if the session is deterministic.
+    seed: Option<u64>,
+    /// Random number generator used to select keys, saved with its position.
+    #[serde(default = "ChaCha12Rng::from_entropy", with = "rng_state")]
+    rng: ChaCha12Rng,
+}
+
+impl Default for PracticeSessionData {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PracticeSessionData {
+    /// Creates session data with every key at zero repetitions and equal weight.
+    pub fn new() -> Self {
+        let mut keys_map_vec = Vec::new();
+        for id in 0..12 {
+            let keys_map_data = KeyData {
+                nid: id,
+                repetitions: 0,
+                weight: 100,
+                working_seconds: 0,
+                pinned: false,
+                rating: None,
+                skips: 0,
+            };
+            keys_map_vec.push(keys_map_data);
+        }
+
+        PracticeSessionData {
+            practice_session_history: None,
+            all_keys_map: KeysMap(keys_map_vec),
+            current_key_data: None,
+            key_history: Vec::new(),
+            start_timestamp: None,
+            receipt: None,
+            clock: Clock::System,
+            strategy: Strategy::default(),
+            key_order: Vec::new(),
+            weakness: Vec::new(),
+            constraints: Constraints::default(),
+            assignment: None,
+            session_id: new_session_id(),
+            rests: Vec::new(),
+            skips: Vec::new(),
+            skip_bonus: 0,
+            seed: None,
+            rng: ChaCha12Rng::from_entropy(),
+        }
+    }
+
+    /// Clears the session while keeping the receipt of the last finished session, the key weights
+    /// and pins, and how keys are selected and constrained.
+    ///
+    /// A seeded session starts its key sequence over.
+    pub fn reset(self) -> Self {
+        let mut keys_map_vec = Vec::new();
+        for key in self.all_keys_map.0.iter() {
+            let keys_map_data = KeyData {
+                nid: key.nid,
+                repetitions: 0,
+                weight: key.weight,
+                working_seconds: 0,
+                pinned: key.pinned,
+                rating: None,
+                skips: 0,
+            };
+            keys_map_vec.push(keys_map_data);
+        }
+
+        PracticeSessionData {
+            practice_session_history: None,
+            all_keys_map: KeysMap(keys_map_vec),
+            current_key_data: None,
+            key_history: Vec::new(),
+            start_timestamp: None,
+            receipt: self.receipt.clone(),
+            clock: self.clock,
+            strategy: self.strategy,
+            key_order: self.key_order.clone(),
+            weakness: self.weakness.clone(),
+            constraints: self.constraints,
+            assignment: self.assignment.clone(),
+            session_id: new_session_id(),
+            rests: Vec::new(),
+            skips: Vec::new(),
+            skip_bonus: self.skip_bonus,
+            seed: self.seed,
+            rng: rng_from_seed(self.seed),
+        }
+    }
+
+    /// Seed of the key selection, if one has been set.
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    /// Restarts the key selection from `seed`, or from a random state when `seed` is `None`.
+    ///
+    /// Sessions with the same seed and the same actions select the same keys in the same order.
+    pub fn set_seed(&mut self, seed: Option<u64>) {
+        self.seed = seed;
+        self.rng = rng_from_seed(seed);
+    }
+
+    /// Selects the next key according to [`Self::strategy`] and the weights in
+    /// [`Self::all_keys_map`], once every pinned key has been selected, and within
+    /// [`Self::constraints`].
+    ///
+    /// Whatever the strategy, weights and seed, every selected key satisfies the constraints
+    /// whenever some key with a positive weight does.
+    ///
+    /// ```
+    /// use jovian_cycles_core::PracticeSessionData;
+    ///
+    /// let mut data = PracticeSessionData::new();
+    /// data.get_new_key();
+    /// assert_eq!(data.current_key_data.map(|key| key.nid), data.key_history.last().copied());
+    /// ```
+    pub fn get_new_key(&mut self) {
+        let keys = self.candidate_keys();
+        let choice = match (choose_pinned(&keys, &self.key_history), self.strategy) {
+            (Some(pinned), _) => Some(pinned),
+            (None, Strategy::Weighted | Strategy::Weakest) => choose_weighted(&keys, &mut self.rng),
+            (None, Strategy::Uniform) => choose_uniform(&keys, &mut self.rng),
+            (None, Strategy::InOrder) => {
+                choose_in_order(&keys, &self.key_order, self.key_history.last().copied())
+            }
+        };
+        if let Some(num) = choice {
+            self.current_key_data = Some(self.all_keys_map.0[num]);
+            self.key_history.push(num);
+        }
+    }
+
+    // Keys as the strategy weighs them, without the keys the constraints rule out.
+    fn candidate_keys(&self) -> Vec<KeyData> {
+        let keys = match self.strategy {
+            Strategy::Weakest => self.weakest_keys(),
+            _ => self.skipped_keys(),
+        };
+        self.constraints.apply(&keys, &self.key_history)
+    }
+
+    /// The next `count` keys the session will select, as long as it only requests new keys.
+    ///
+    /// The session itself is left untouched.
+    pub fn plan_keys(&self, count: usize) -> Vec<usize> {
+        let mut data = self.clone();
+        let start = data.key_history.len();
+        (0..count).for_each(|_| data.get_new_key());
+        data.key_history.split_off(start)
+    }
+
+    /// Probability of each key being the next one selected, indexed by key id.
+    ///
+    /// ```
+    /// use jovian_cycles_core::PracticeSessionData;
+    ///
+    /// let mut data = PracticeSessionData::new();
+    /// data.all_keys_map.0.iter_mut().for_each(|key| key.weight = 0);
+    /// data.all_keys_map.0[0].weight = 300;
+    /// data.all_keys_map.0[7].weight = 100;
+    /// assert_eq!(data.next_key_probabilities()[0], 0.75);
+    ///
+    /// data.all_keys_map.0[2].weight = 100;
+    /// data.all_keys_map.0[2].pinned = true;
+    /// assert_eq!(data.next_key_probabilities()[2], 1.0);
+    /// assert_eq!(data.next_key_probabilities()[0], 0.0);
+    /// ```
+    pub fn next_key_probabilities(&self) -> Vec<f32> {
+        let keys = &self.candidate_keys();
+        let certain = |choice: Option<usize>| {
+            (0..keys.len())
+                .map(|key| if Some(key) == choice { 1.0 } else { 0.0 })
+                .collect()
+        };
+        if let Some(pinned) = choose_pinned(keys, &self.key_history) {
+            return certain(Some(pinned));
+        }
+        match self.strategy {
+            Strategy::Weighted | Strategy::Weakest => probabilities(keys),
+            Strategy::Uniform => {
+                let candidates = keys.iter().filter(|key| key.weight > 0).count();
+                keys.iter()
+                    .map(|key| match key.weight > 0 {
+                        true => 1.0 / candidates as f32,
+                        false => 0.0,
+                    })
+                    .collect()
+            }
+            Strategy::InOrder => certain(choose_in_order(
+                keys,
+                &self.key_order,
+                self.key_history.last().copied(),
+            )),
+        }
+    }
+
+    /// The keys with [`Self::skip_bonus`] added to their weights for every time they were
+    /// skipped in the session, as the key selection weighs them.
+    ///
+    /// The weights of the keys themselves are left alone, so the bonus lasts for the session.
+    pub fn skipped_keys(&self) -> Vec<KeyData> {
+        self.all_keys_map
+            .0
+            .iter()
+            .map(|key| KeyData {
+                weight: key.weight + key.skips * self.skip_bonus,
+                ..*key
+            })
+            .collect()
+    }
+
+    /// The keys with their weights, skip bonus included, multiplied by [`KeyWeakness::factor`],
+    /// as the weakest keys strategy picks them.
+    pub fn weakest_keys(&self) -> Vec<KeyData> {
+        self.skipped_keys()
+            .iter()
+            .map(|key| {
+                let factor = self.weakness.get(key.nid).map_or(1.0, KeyWeakness::factor);
+                KeyData {
+                    weight: (key.weight as f32 * factor).round() as i32,
+                    ..*key
+                }
+            })
+            .collect()
+    }
+
+    /// Rates the current key from 1 to 5, replacing an earlier rating in the session.
+    pub fn rate_current_key(&mut self, rating: u8) {
+        if let Some(data) = &mut self.current_key_data {
+            data.rating = Some(rating.clamp(1, 5));
+            self.all_keys_map.0[data.nid].rating = data.rating;
+        }
+    }
+
+    /// Records that the current key was skipped, so that [`Self::skip_bonus`] is added to its
+    /// weight when selecting keys (see [`Self::skipped_keys`]).
+    ///
+    /// ```
+    /// use jovian_cycles_core::PracticeSessionState;
+    ///
+    /// let mut state = PracticeSessionState::default();
+    /// state.session_data.skip_bonus = 50;
+    /// state.request_new_key();
+    /// let skipped = state.session_data.current_key_data.unwrap().nid;
+    /// state.skip_key();
+    /// state.session_data.give_skip_reason("Too hard");
+    ///
+    /// let key = state.session_data.all_keys_map.0[skipped];
+    /// assert_eq!((key.skips, key.weight), (1, 100));
+    /// assert_eq!(state.session_data.skipped_keys()[skipped].weight, 150);
+    /// assert_eq!(state.session_data.skips[0].reason.as_deref(), Some("Too hard"));
+    /// ```
+    pub fn record_skip(&mut self) {
+        if let Some(data) = &mut self.current_key_data {
+            let key = &mut self.all_keys_map.0[data.nid];
+            key.skips += 1;
+            data.skips = key.skips;
+            self.skips.push(SkipEvent {
+                key: data.nid,
+                skipped_at: self.clock.now(),
+                reason: None,
+            });
+        }
+    }
+
+    /// Gives the reason of the last skip of the session.
+    pub fn give_skip_reason(&mut self, reason: &str) {
+        let reason = reason.trim();
+        if let Some(skip) = self.skips.last_mut() {
+            skip.reason = (!reason.is_empty()).then(|| reason.to_string());
+        }
+    }
+
+    /// Adds a repetition to the current key.
+    pub fn increment_key_repetition(mut self) -> Result<Self> {
+        match self.current_key_data {
+            Some(ref mut data) => {
+                data.repetitions += 1;
+
+                // Update all keys map
+                self.all_keys_map.0[data.nid].repetitions = data.repetitions;
+
+                Ok(self)
+            }
+            None => {
+                let msg = eyre!("Unable to increment repetition count for the current key as a current key has not been set. Will attempt to set the state back to RequestingNewKey to try again.");
+                Err(msg)
+            }
+        }
+    }
+
+    /// Removes a repetition from the current key.
+    pub fn decrement_key_repetition(mut self) -> Result<Self> {
+        match self.current_key_data {
+            Some(ref mut data) => {
+                data.repetitions -= 1;
+
+                // Update all keys map
+                self.all_keys_map.0[data.nid].repetitions = data.repetitions;
+
+                Ok(self)
+            }
+            None => {
+                let msg = eyre!("Unable to decrement repetition count for the current key as a current key has not been set. Will attempt to set the state back to RequestingNewKey to try again.");
+                Err(msg)
+            }
+        }
+    }
+
+    /// Marks the start of `state_name` at the current time of [`Self::clock`].
+    ///
+    /// The state that ends is accounted for first: work goes to the current key and rests are
+    /// added to [`Self::rests`].
+    pub fn set_timestamp(&mut self, state_name: String) {
+        let timestamp: i64 = self.clock.now();
+
+        self.set_timestamp_at(state_name, timestamp);
+    }
+
+    /// Marks the start of `state_name` at `timestamp`, which must not come before the start of
+    /// the current state.
+    pub fn set_timestamp_at(&mut self, state_name: String, timestamp: i64) {
+        self.account_for_current_state(timestamp);
+        self.start_timestamp = Some((state_name, timestamp));
+    }
+
+    /// Turns the rest in progress back into work on the current key, as if it never started.
+    ///
+    /// Returns `false` and changes nothing unless the current state is a rest that followed work.
+    pub fn cancel_rest(&mut self) -> bool {
+        let Some(("Resting", started)) = self
+            .start_timestamp
+            .as_ref()
+            .map(|(state_name, started)| (state_name.as_str(), *started))
+        else {
+            return false;
+        };
+        let Some(history) = &mut self.practice_session_history else {
+            return false;
+        };
+        match history.as_slice() {
+            [.., (previous, _), (last, timestamp)]
+                if previous == "Working" && last == "Resting" && *timestamp == started => {}
+            _ => return false,
+        }
+
+        // Work up to the rest was already counted, the rest of it is counted when it ends.
+        history.pop();
+        self.start_timestamp = Some(("Working".to_string(), started));
+        true
+    }
+
+    fn account_for_current_state(&mut self, now: i64) {
+        let Some((state_name, started)) = &self.start_timestamp else {
+            return;
+        };
+        let seconds = (now - started).max(0);
+
+        match state_name.as_str() {
+            "Working" => {
+                if let Some(ref mut data) = self.current_key_data {
+                    data.working_seconds += seconds;
+                    self.all_keys_map.0[data.nid] = *data;
+                }
+            }
+            "Resting" => self.rests.push(seconds),
+            _ => {}
+        }
+    }
+
+    /// Seconds since the current state started, zero before the session starts.
+    pub fn elapsed_seconds(&self) -> i64 {
+        match &self.start_timestamp {
+            Some((_, started)) => (self.clock.now() - started).max(0),
+            None => 0,
+        }
+    }
+
+    /// Seconds worked on `key`, including the running stretch of work on the current key.
+    pub fn working_seconds(&self, key: usize) -> i64 {
+        let running = match (&self.start_timestamp, self.current_key_data) {
+            (Some((state_name, _)), Some(data)) if state_name == "Working" && data.nid == key => {
+                self.elapsed_seconds()
+            }
+            _ => 0,
+        };
+        self.all_keys_map.0[key].working_seconds + running
+    }
+
+    /// State name and start time of the current state, if one has been marked.
+    pub fn start_timestamp(&self) -> Option<&TimeCode> {
+        self.start_timestamp.as_ref()
+    }
+
+    /// Appends the start of the current state to the practice session history.
+    pub fn push_timestamp(&mut self) {
+        let current_timestamp;
+        match self.start_timestamp.clone() {
+            None => {}
+            Some(time_code) => {
+                current_timestamp = time_code;
+                match &mut self.practice_session_history {
+                    None => {
+                        self.practice_session_history = Some(vec![current_timestamp]);
+                    }
+                    Some(history) => {
+                        history.push(current_timestamp);
+                    }
+                };
+            }
+        };
+    }
+
+    /// Builds a receipt from the keys and history recorded so far.
+    pub fn construct_receipt(&self) -> Receipt {
+        Receipt {
+            key_data_archive: Some(self.all_keys_map.clone()),
+            time_stamp_archive: self.practice_session_history.clone(),
+            seed: self.seed,
+            key_sequence: self.key_history.clone(),
+            assignment: self.assignment.clone(),
+            session_id: self.session_id.clone(),
+            rests: self.rests.clone(),
+            edits: Vec::new(),
+            manual: false,
+            skips: self.skips.clone(),
+        }
+    }
+}
+
+// Drawn from the thread's generator rather than `rng`, so seeded sessions still select the same
+// keys.
+pub(crate) fn new_session_id() -> String {
+    format!("{:016x}", rand::random::<u64>())
+}
+
+// The generator behind `rand::rngs::StdRng`, whose position in its stream can be saved.
+fn rng_from_seed(seed: Option<u64>) -> ChaCha12Rng {
+    match seed {
+        Some(seed) => ChaCha12Rng::seed_from_u64(seed),
+        None => ChaCha12Rng::from_entropy(),
+    }
+}
+
+// Saves the generator with its key and position, so a session picked up again from a checkpoint
+// goes on selecting the keys it would have selected without the interruption.
+mod rng_state {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha12Rng;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    struct RngState {
+        seed: [u8; 32],
+        stream: u64,
+        // The 68 bit word position, high half first.
+        word_pos: [u64; 2],
+    }
+
+    pub fn serialize<S: Serializer>(rng: &ChaCha12Rng, serializer: S) -> Result<S::Ok, S::Error> {
+        let word_pos = rng.get_word_pos();
+        RngState {
+            seed: rng.get_seed(),
+            stream: rng.get_stream(),
+            word_pos: [(word_pos >> 64) as u64, word_pos as u64],
+        }
+        .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<ChaCha12Rng, D::Error> {
+        let state = RngState::deserialize(deserializer)?;
+        let mut rng = ChaCha12Rng::from_seed(state.seed);
+        rng.set_stream(state.stream);
+        rng.set_word_pos(((state.word_pos[0] as u128) << 64) | state.word_pos[1] as u128);
+        Ok(rng)
+    }
+}
@@ -0,0 +1,300 @@
+/*
+ * Pitch Detection
+ *
+ * Estimates the fundamental frequency of a mono buffer of samples with the YIN algorithm
+ * (de Cheveigné and Kawahara, 2002). Detection only depends on the samples, so it can be checked
+ * against generated buffers without a microphone.
+ */
+
+/// Lowest frequency that can be detected, a little below the low E of a bass guitar.
+pub const MIN_FREQUENCY: f32 = 40.0;
+/// Highest frequency that can be detected, a little above the top C of a piano.
+pub const MAX_FREQUENCY: f32 = 4200.0;
+
+// Buffers quieter than this are treated as silence.
+const SILENCE_RMS: f32 = 0.01;
+// Dips of the normalized difference below this value are taken as the period.
+const YIN_THRESHOLD: f32 = 0.15;
+
+/// A detected pitch.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PitchEstimate {
+    pub frequency: f32,
+    /// How periodic the buffer is, from 0 (noise) to 1 (a pure tone).
+    pub clarity: f32,
+}
+
+/// Estimates the pitch of `samples`, or `None` for silence and unpitched sounds.
+///
+/// The buffer must hold at least two periods of the lowest pitch to be detected; 2048 samples
+/// at 44.1 kHz go down to about 43 Hz.
+///
+/// ```
+/// use jovian_cycles_core::pitch::detect_pitch;
+/// use jovian_cycles_core::synth::{Synth, SynthNote, Waveform};
+///
+/// let synth = Synth { waveform: Waveform::Sawtooth, ..Default::default() };
+/// let note = SynthNote { start: 0.0, duration: 0.2, note: 57, velocity: 1.0 };
+/// let samples = synth.render(&[note]);
+///
+/// let estimate = detect_pitch(&samples[2000..4048], synth.sample_rate).unwrap();
+/// assert!((estimate.frequency - 220.0).abs() < 1.0);
+/// ```
+pub fn detect_pitch(samples: &[f32], sample_rate: u32) -> Option<PitchEstimate> {
+    if samples.is_empty() {
+        return None;
+    }
+    let rms =
+        (samples.iter().map(|sample| sample * sample).sum::<f32>() / samples.len() as f32).sqrt();
+    if rms < SILENCE_RMS {
+        return None;
+    }
+
+    let sample_rate = sample_rate as f32;
+    let min_lag = ((sample_rate / MAX_FREQUENCY).floor() as usize).max(2);
+    let max_lag = ((sample_rate / MIN_FREQUENCY).ceil() as usize).min(samples.len() / 2);
+    if max_lag <= min_lag + 1 {
+        return None;
+    }
+    let window = samples.len() - max_lag;
+
+    // Cumulative mean normalized difference of the buffer with itself, delayed by each lag.
+    let mut normalized = vec![1.0; max_lag + 1];
+    let mut running_sum = 0.0;
+    for lag in 1..=max_lag {
+        let difference: f32 = (0..window)
+            .map(|index| {
+                let delta = samples[index] - samples[index + lag];
+                delta * delta
+            })
+            .sum();
+        running_sum += difference;
+        normalized[lag] = if running_sum > 0.0 {
+            difference * lag as f32 / running_sum
+        } else {
+            1.0
+        };
+    }
+
+    // The first dip below the threshold, followed down to its bottom.
+    let mut lag = (min_lag..max_lag).find(|lag| normalized[*lag] < YIN_THRESHOLD)?;
+    while lag + 1 < max_lag && normalized[lag + 1] < normalized[lag] {
+        lag += 1;
+    }
+
+    // Parabolic interpolation between the neighbouring lags.
+    let (before, at, after) = (normalized[lag - 1], normalized[lag], normalized[lag + 1]);
+    let curvature = before - 2.0 * at + after;
+    let refined_lag = if curvature.abs() > f32::EPSILON {
+        lag as f32 + 0.5 * (before - after) / curvature
+    } else {
+        lag as f32
+    };
+
+    Some(PitchEstimate {
+        frequency: sample_rate / refined_lag,
+        clarity: (1.0 - at).clamp(0.0, 1.0),
+    })
+}
+
+/// MIDI note number of `frequency`, with a fractional part for pitches between notes.
+pub fn frequency_to_midi(frequency: f32, a4_frequency: f32) -> f32 {
+    69.0 + 12.0 * (frequency / a4_frequency).log2()
+}
+
+/// MIDI note closest to `frequency`, or `None` outside the MIDI range.
+pub fn nearest_midi_note(frequency: f32, a4_frequency: f32) -> Option<u8> {
+    if frequency <= 0.0 || a4_frequency <= 0.0 {
+        return None;
+    }
+    let note = frequency_to_midi(frequency, a4_frequency).round();
+    if (0.0..=127.0).contains(&note) {
+        Some(note as u8)
+    } else {
+        None
+    }
+}
+
+/*
+ * Tuner
+ */
+
+/// Nearest note to a detected pitch and how far the pitch is from it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TunerReading {
+    pub frequency: f32,
+    pub note: u8,
+    /// Deviation from the note in cents, between -50 (flat) and 50 (sharp).
+    pub cents: f32,
+}
+
+/// Reading of a tuner whose A4 is tuned to `a4_frequency`.
+///
+/// ```
+/// use jovian_cycles_core::pitch::{detect_pitch, tune};
+/// use jovian_cycles_core::synth::{Synth, SynthNote};
+/// use jovian_cycles_core::wav::{read_wav, write_wav};
+///
+/// // An A4 played on an instrument tuned to 442 Hz.
+/// let synth = Synth { a4_frequency: 442.0, ..Default::default() };
+/// let note = SynthNote { start: 0.0, duration: 0.2, note: 69, velocity: 1.0 };
+/// let wav = write_wav(&synth.render(&[note]), synth.sample_rate);
+///
+/// let (samples, sample_rate) = read_wav(&wav).unwrap();
+/// let estimate = detect_pitch(&samples[2000..4048], sample_rate).unwrap();
+///
+/// let reading = tune(estimate.frequency, 440.0).unwrap();
+/// assert_eq!(reading.note, 69);
+/// assert!((reading.cents - 7.85).abs() < 1.0);
+///
+/// let reading = tune(estimate.frequency, 442.0).unwrap();
+/// assert!(reading.cents.abs() < 1.0);
+/// ```
+pub fn tune(frequency: f32, a4_frequency: f32) -> Option<TunerReading> {
+    let note = nearest_midi_note(frequency, a4_frequency)?;
+    Some(TunerReading {
+        frequency,
+        note,
+        cents: 100.0 * (frequency_to_midi(frequency, a4_frequency) - note as f32),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::f32::consts::TAU;
+
+    const SAMPLE_RATE: u32 = 44_100;
+
+    fn sine(frequency: f32, length: usize) -> Vec<f32> {
+        (0..length)
+            .map(|index| 0.5 * (TAU * frequency * index as f32 / SAMPLE_RATE as f32).sin())
+            .collect()
+    }
+
+    // Deviation of `detected` from `expected`, in cents.
+    fn cents_off(detected: f32, expected: f32) -> f32 {
+        1200.0 * (detected / expected).log2()
+    }
+
+    // White noise from a fixed linear congruential generator, between -0.5 and 0.5.
+    fn noise(length: usize) -> Vec<f32> {
+        let mut state: u32 = 12345;
+        (0..length)
+            .map(|_| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (state >> 8) as f32 / (1 << 24) as f32 - 0.5
+            })
+            .collect()
+    }
+
+    #[test]
+    fn detects_sine_waves_across_the_range() {
+        [
+            41.2, 55.0, 82.41, 110.0, 196.0, 261.63, 440.0, 880.0, 1760.0, 3520.0, 4186.0,
+        ]
+        .into_iter()
+        .for_each(|frequency| {
+            // Low pitches need two periods of the lowest detectable one.
+            let length = if frequency < 50.0 { 4096 } else { 2048 };
+            let estimate = detect_pitch(&sine(frequency, length), SAMPLE_RATE)
+                .unwrap_or_else(|| panic!("{frequency} Hz not detected"));
+            let off = cents_off(estimate.frequency, frequency);
+            assert!(off.abs() < 5.0, "{frequency} Hz detected {off} cents off");
+            assert!(estimate.clarity > 0.9, "{frequency} Hz: {estimate:?}");
+        });
+    }
+
+    #[test]
+    fn ignores_pitches_out_of_range() {
+        assert_eq!(detect_pitch(&sine(30.0, 4096), SAMPLE_RATE), None);
+        // Too short a buffer for the lowest pitches.
+        assert_eq!(detect_pitch(&sine(440.0, 4), SAMPLE_RATE), None);
+        assert_eq!(detect_pitch(&[], SAMPLE_RATE), None);
+    }
+
+    #[test]
+    fn returns_none_for_silence() {
+        assert_eq!(detect_pitch(&vec![0.0; 2048], SAMPLE_RATE), None);
+        let quiet: Vec<f32> = sine(440.0, 2048)
+            .iter()
+            .map(|sample| sample * 0.01)
+            .collect();
+        assert_eq!(detect_pitch(&quiet, SAMPLE_RATE), None);
+        assert_eq!(detect_pitch(&vec![0.5; 2048], SAMPLE_RATE), None);
+    }
+
+    #[test]
+    fn returns_none_for_noise() {
+        let samples = noise(2048);
+        assert_eq!(detect_pitch(&samples, SAMPLE_RATE), None);
+        assert_eq!(detect_pitch(&samples[..1024], 22_050), None);
+    }
+
+    #[test]
+    fn avoids_octave_errors() {
+        // A weak fundamental under strong second and third harmonics is still heard as 110 Hz.
+        let fundamental = sine(110.0, 2048);
+        let second = sine(220.0, 2048);
+        let third = sine(330.0, 2048);
+        let samples: Vec<f32> = (0..2048)
+            .map(|index| 0.3 * fundamental[index] + second[index] + 0.8 * third[index])
+            .collect();
+        let estimate = detect_pitch(&samples, SAMPLE_RATE).unwrap();
+        assert!(
+            cents_off(estimate.frequency, 110.0).abs() < 5.0,
+            "{estimate:?}"
+        );
+
+        // A pure tone is never taken for its lower octave.
+        [98.0, 196.0, 392.0, 784.0]
+            .into_iter()
+            .for_each(|frequency| {
+                let estimate = detect_pitch(&sine(frequency, 2048), SAMPLE_RATE).unwrap();
+                let octaves = (estimate.frequency / frequency).log2().round();
+                assert_eq!(octaves, 0.0, "{frequency} Hz: {estimate:?}");
+            });
+    }
+
+    #[test]
+    fn converts_frequencies_to_notes() {
+        assert_eq!(frequency_to_midi(440.0, 440.0), 69.0);
+        assert!((frequency_to_midi(261.6256, 440.0) - 60.0).abs() < 1e-4);
+        assert_eq!(nearest_midi_note(440.0, 440.0), Some(69));
+        assert_eq!(nearest_midi_note(8.18, 440.0), Some(0));
+        assert_eq!(nearest_midi_note(12_543.85, 440.0), Some(127));
+        assert_eq!(nearest_midi_note(14_000.0, 440.0), None);
+        assert_eq!(nearest_midi_note(0.0, 440.0), None);
+        assert_eq!(nearest_midi_note(440.0, 0.0), None);
+    }
+
+    #[test]
+    fn measures_cents_from_the_nearest_note() {
+        let semitone = |cents: f32| 440.0 * 2f32.powf(cents / 1200.0);
+
+        let reading = tune(semitone(25.0), 440.0).unwrap();
+        assert_eq!(reading.note, 69);
+        assert!((reading.cents - 25.0).abs() < 0.01);
+
+        let reading = tune(semitone(-30.0), 440.0).unwrap();
+        assert_eq!(reading.note, 69);
+        assert!((reading.cents + 30.0).abs() < 0.01);
+
+        // Past half a semitone the next note is nearer.
+        let reading = tune(semitone(70.0), 440.0).unwrap();
+        assert_eq!(reading.note, 70);
+        assert!((reading.cents + 30.0).abs() < 0.01);
+
+        let reading = tune(semitone(-1200.0), 440.0).unwrap();
+        assert_eq!(reading.note, 57);
+        assert!(reading.cents.abs() < 0.01);
+
+        // The same pitch against another reference.
+        let reading = tune(440.0, 442.0).unwrap();
+        assert_eq!(reading.note, 69);
+        assert!((reading.cents + 7.85).abs() < 0.01);
+
+        assert!(tune(0.0, 440.0).is_none());
+    }
+}
@@ -0,0 +1,571 @@
+use crate::assignment::Assignment;
+use crate::edits::ReceiptChange;
+use crate::models::{new_session_id, KeyData, KeysMap, PracticeSessionData, Receipt};
+use crate::selection::{Constraints, Strategy};
+use crate::statistics::SessionStatistics;
+use crate::sync::session_id;
+use crate::weakness::{weakness, KeyWeakness};
+
+use chrono::{DateTime, Datelike, Local, NaiveDate};
+use color_eyre::eyre::{eyre, Result};
+use serde::{Deserialize, Serialize};
+
+/*
+ * Profiles
+ *
+ * A profile belongs to one player and keeps what should outlive a single session: the receipts
+ * of finished sessions, the selection weight of every key, the goals the player works towards and
+ * the assignments given by a teacher.
+ */
+
+/// Most repetitions of a key a session logged by hand can have.
+pub const MAX_LOGGED_REPETITIONS: u32 = 999;
+
+/// Practice targets of a profile.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Goals {
+    /// Minutes of work to practice every day.
+    pub daily_minutes: u32,
+    /// Sessions to finish every week, counted from Monday.
+    pub weekly_sessions: u32,
+    /// Whether practice logged by hand counts towards the goals.
+    pub count_manual: bool,
+}
+
+impl Default for Goals {
+    fn default() -> Self {
+        Goals {
+            daily_minutes: 20,
+            weekly_sessions: 5,
+            count_manual: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub name: String,
+    /// Identifies the profile on a sync server, see [`crate::sync`]. Devices sharing the history
+    /// of the profile use the same id.
+    pub id: String,
+    /// Receipts of finished sessions, oldest first, including withdrawn ones (see
+    /// [`Self::sessions`]).
+    pub history: Vec<Receipt>,
+    /// Selection weight of every key, indexed by key id.
+    pub weights: Vec<i32>,
+    /// Keys selected in every session, see [`KeyData::pinned`].
+    pub pinned: Vec<usize>,
+    /// How keys are selected in the player's own practice.
+    pub strategy: Strategy,
+    /// Weight added to a key every time it is skipped, so avoided keys come back more often.
+    pub skip_bonus: i32,
+    /// Limits on which keys may follow each other.
+    pub constraints: Constraints,
+    pub goals: Goals,
+    pub assignments: Vec<Assignment>,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Profile::new("Default")
+    }
+}
+
+impl Profile {
+    /// Creates a profile without history, with every key equally likely.
+    pub fn new(name: &str) -> Self {
+        Profile {
+            name: name.to_string(),
+            id: new_profile_id(),
+            history: Vec::new(),
+            weights: vec![100; 12],
+            pinned: Vec::new(),
+            strategy: Strategy::default(),
+            skip_bonus: 0,
+            constraints: Constraints::default(),
+            goals: Goals::default(),
+            assignments: Vec::new(),
+        }
+    }
+
+    /// Adds the receipt of a finished session to the history.
+    ///
+    /// A session withdrawn earlier, because its end was undone and then redone, is brought back
+    /// as of `recorded_at` (a Unix timestamp in seconds).
+    pub fn record(&mut self, receipt: Receipt, recorded_at: i64) {
+        let id = session_id(&receipt);
+        match self
+            .history
+            .iter_mut()
+            .find(|recorded| session_id(recorded) == id)
+        {
+            Some(recorded) => {
+                if recorded.is_withdrawn() {
+                    recorded.edit(ReceiptChange::Withdrawn { withdrawn: false }, recorded_at);
+                }
+            }
+            None => self.history.push(receipt),
+        }
+    }
+
+    /// Records practice done without the app as a session flagged as manual.
+    ///
+    /// The session ends at `finished_at` (a Unix timestamp in seconds), the `working_seconds` are
+    /// shared evenly between the keys with repetitions and every repetition of a key counts as
+    /// one selection. Unknown keys, more than [`MAX_LOGGED_REPETITIONS`] of a key, negative
+    /// durations and logs without any repetition are rejected.
+    ///
+    /// ```
+    /// use jovian_cycles_core::statistics::SessionStatistics;
+    /// use jovian_cycles_core::Profile;
+    ///
+    /// let mut profile = Profile::default();
+    /// let receipt = profile.log_practice(1_700_000_000, 1200, &[(0, 3), (7, 2)]).unwrap();
+    ///
+    /// assert!(receipt.manual);
+    /// assert_eq!(receipt.key_sequence, vec![0, 0, 0, 7, 7]);
+    /// let statistics = SessionStatistics::from_receipt(receipt);
+    /// assert_eq!(statistics.working_seconds, 1200);
+    /// assert_eq!(statistics.total_repetitions, 5);
+    /// ```
+    pub fn log_practice(
+        &mut self,
+        finished_at: i64,
+        working_seconds: i64,
+        repetitions: &[(usize, u32)],
+    ) -> Result<&Receipt> {
+        if working_seconds < 0 {
+            return Err(eyre!("Practice cannot last a negative time."));
+        }
+        if let Some((nid, _)) = repetitions
+            .iter()
+            .find(|(nid, _)| *nid >= self.weights.len())
+        {
+            return Err(eyre!("There is no key {}.", nid));
+        }
+        if let Some((nid, count)) = repetitions
+            .iter()
+            .find(|(_, count)| *count > MAX_LOGGED_REPETITIONS)
+        {
+            return Err(eyre!(
+                "{} repetitions of key {} are more than the {} that can be logged.",
+                count,
+                nid,
+                MAX_LOGGED_REPETITIONS
+            ));
+        }
+        let repetitions: Vec<(usize, u32)> = repetitions
+            .iter()
+            .copied()
+            .filter(|(_, count)| *count > 0)
+            .collect();
+        if repetitions.is_empty() {
+            return Err(eyre!("No repetitions were logged."));
+        }
+
+        let mut keys: Vec<KeyData> = self
+            .weights
+            .iter()
+            .enumerate()
+            .map(|(nid, weight)| KeyData {
+                nid,
+                repetitions: 0,
+                weight: *weight,
+                working_seconds: 0,
+                pinned: false,
+                rating: None,
+                skips: 0,
+            })
+            .collect();
+        let practiced = repetitions.len() as i64;
+        let mut key_sequence = Vec::new();
+        repetitions
+            .iter()
+            .enumerate()
+            .for_each(|(index, (nid, count))| {
+                let key = &mut keys[*nid];
+                key.repetitions = key.repetitions.saturating_add(*count as i32);
+                // The first keys take the seconds that do not share evenly.
+                key.working_seconds += working_seconds / practiced
+                    + i64::from((index as i64) < working_seconds % practiced);
+                key_sequence.extend(std::iter::repeat(*nid).take(*count as usize));
+            });
+
+        let receipt = Receipt {
+            key_data_archive: Some(KeysMap(keys)),
+            time_stamp_archive: Some(vec![
+                ("Working".to_string(), finished_at - working_seconds),
+                ("Finishing".to_string(), finished_at),
+            ]),
+            seed: None,
+            key_sequence,
+            assignment: None,
+            session_id: new_session_id(),
+            rests: Vec::new(),
+            edits: Vec::new(),
+            manual: true,
+            skips: Vec::new(),
+        };
+
+        // Past practice goes in between the sessions recorded around it.
+        let index = self
+            .history
+            .partition_point(|recorded| finished_at_of(recorded) <= Some(finished_at));
+        self.history.insert(index, receipt);
+        Ok(&self.history[index])
+    }
+
+    /// Takes the session `receipt` comes from back, as of `withdrawn_at` (a Unix timestamp in
+    /// seconds).
+    ///
+    /// The receipt stays in the history, marked as withdrawn, so syncing takes it back on every
+    /// other device too.
+    ///
+    /// ```
+    /// use jovian_cycles_core::{PracticeSessionState, Profile, SessionCommand};
+    ///
+    /// let mut profile = Profile::default();
+    /// let mut state = PracticeSessionState::default();
+    /// state.apply(SessionCommand::NewKey);
+    /// state.apply(SessionCommand::Finish);
+    /// let receipt = state.take_finished_receipts().remove(0);
+    /// profile.record(receipt.clone(), 100);
+    ///
+    /// profile.withdraw(&receipt, 200);
+    /// assert_eq!(profile.sessions().count(), 0);
+    /// assert!(profile.history[0].is_withdrawn());
+    ///
+    /// profile.record(receipt, 300);
+    /// assert_eq!(profile.sessions().count(), 1);
+    /// ```
+    pub fn withdraw(&mut self, receipt: &Receipt, withdrawn_at: i64) {
+        let id = session_id(receipt);
+        if let Some(recorded) = self
+            .history
+            .iter_mut()
+            .find(|recorded| session_id(recorded) == id)
+        {
+            recorded.edit(ReceiptChange::Withdrawn { withdrawn: true }, withdrawn_at);
+        }
+    }
+
+    /// Adds the assignments of a plan, replacing earlier assignments with the same title.
+    pub fn assign(&mut self, assignments: Vec<Assignment>) {
+        assignments.into_iter().for_each(|assignment| {
+            match self
+                .assignments
+                .iter_mut()
+                .find(|existing| existing.title == assignment.title)
+            {
+                Some(existing) => *existing = assignment,
+                None => self.assignments.push(assignment),
+            }
+        });
+    }
+
+    /// Starts practicing the assignment at `index` of [`Self::assignments`] in `data`.
+    ///
+    /// When leaving the player's own practice, its key settings are stored first, so
+    /// [`Self::stop_assignment`] brings back changes that were not saved yet.
+    ///
+    /// ```
+    /// use jovian_cycles_core::assignment::Assignment;
+    /// use jovian_cycles_core::{PracticeSessionData, Profile};
+    ///
+    /// let mut profile = Profile::default();
+    /// profile.assign(vec![Assignment {
+    ///     title: "Sharps".to_string(),
+    ///     teacher: String::new(),
+    ///     pool: vec!["G".to_string(), "D".to_string()],
+    ///     strategy: Default::default(),
+    ///     minutes: 10,
+    ///     sessions: 1,
+    ///     due: None,
+    ///     notes: String::new(),
+    /// }]);
+    ///
+    /// let mut data = PracticeSessionData::new();
+    /// data.all_keys_map.0[5].weight = 250;
+    /// data.all_keys_map.0[9].pinned = true;
+    /// let own = data.all_keys_map.clone();
+    ///
+    /// profile.start_assignment(0, &mut data).unwrap();
+    /// assert_eq!(data.all_keys_map.0[5].weight, 0);
+    /// assert!(!data.all_keys_map.0[9].pinned);
+    ///
+    /// profile.stop_assignment(&mut data);
+    /// assert_eq!(data.all_keys_map, own);
+    /// assert_eq!(data.assignment, None);
+    /// ```
+    pub fn start_assignment(&mut self, index: usize, data: &mut PracticeSessionData) -> Result<()> {
+        if index >= self.assignments.len() {
+            return Err(eyre!("There is no assignment {}.", index + 1));
+        }
+        if data.assignment.is_none() {
+            self.store_weights(data);
+        }
+        self.assignments[index].apply(data)
+    }
+
+    /// Goes back to the player's own practice, with the key settings of the profile.
+    pub fn stop_assignment(&self, data: &mut PracticeSessionData) {
+        data.assignment = None;
+        data.key_order.clear();
+        self.apply_weights(data);
+    }
+
+    /// Copies the weights, pins, selection strategy, constraints and skip bonus of the profile
+    /// onto `data`.
+    pub fn apply_weights(&self, data: &mut PracticeSessionData) {
+        data.strategy = self.strategy;
+        data.constraints = self.constraints;
+        data.skip_bonus = self.skip_bonus;
+        data.all_keys_map
+            .0
+            .iter_mut()
+            .zip(self.weights.iter())
+            .for_each(|(key, weight)| key.weight = *weight);
+        data.all_keys_map
+            .0
+            .iter_mut()
+            .for_each(|key| key.pinned = self.pinned.contains(&key.nid));
+    }
+
+    /// Remembers the weights and pins of the keys of `data`, how it selects and constrains keys
+    /// and its skip bonus.
+    pub fn store_weights(&mut self, data: &PracticeSessionData) {
+        self.strategy = data.strategy;
+        self.constraints = data.constraints;
+        self.skip_bonus = data.skip_bonus;
+        let keys = &data.all_keys_map.0;
+        self.weights = keys.iter().map(|key| key.weight).collect();
+        self.pinned = keys
+            .iter()
+            .filter(|key| key.pinned)
+            .map(|key| key.nid)
+            .collect();
+    }
+
+    /// Seconds worked in sessions that finished on `day`, in local time.
+    pub fn working_seconds_on(&self, day: NaiveDate) -> i64 {
+        self.counted_history()
+            .filter(|receipt| finished_on(receipt) == Some(day))
+            .map(|receipt| SessionStatistics::from_receipt(receipt).working_seconds)
+            .sum()
+    }
+
+    /// Number of sessions that finished in the week, starting on Monday, containing `day`.
+    pub fn sessions_in_week_of(&self, day: NaiveDate) -> usize {
+        let week = day.iso_week();
+        self.counted_history()
+            .filter_map(finished_on)
+            .filter(|finished| finished.iso_week() == week)
+            .count()
+    }
+
+    /// Number of days in a row, up to `today`, with at least one counted session.
+    ///
+    /// A streak that went on until yesterday still counts while today's practice is due.
+    ///
+    /// ```
+    /// use chrono::{Local, NaiveDate, TimeZone};
+    /// use jovian_cycles_core::Profile;
+    ///
+    /// let at_noon = |day: u32| {
+    ///     let date = NaiveDate::from_ymd_opt(2024, 3, day).unwrap();
+    ///     let noon = date.and_hms_opt(12, 0, 0).unwrap();
+    ///     Local.from_local_datetime(&noon).unwrap().timestamp()
+    /// };
+    /// let mut profile = Profile::default();
+    /// [1, 3, 4, 5].iter().for_each(|day| {
+    ///     profile.log_practice(at_noon(*day), 600, &[(0, 1)]).unwrap();
+    /// });
+    ///
+    /// let day = |day: u32| NaiveDate::from_ymd_opt(2024, 3, day).unwrap();
+    /// assert_eq!(profile.streak(day(5)), 3);
+    /// assert_eq!(profile.streak(day(6)), 3);
+    /// assert_eq!(profile.streak(day(7)), 0);
+    ///
+    /// // Practice logged by hand only counts when the goals count it.
+    /// profile.goals.count_manual = false;
+    /// assert_eq!(profile.streak(day(5)), 0);
+    /// ```
+    pub fn streak(&self, today: NaiveDate) -> usize {
+        let practiced: Vec<NaiveDate> = self.counted_history().filter_map(finished_on).collect();
+        let start = match today.pred_opt() {
+            Some(yesterday) if !practiced.contains(&today) => yesterday,
+            _ => today,
+        };
+        std::iter::successors(Some(start), NaiveDate::pred_opt)
+            .take_while(|day| practiced.contains(day))
+            .count()
+    }
+
+    /// Weakness of every key over the counted history, as of `now` (a Unix timestamp in seconds).
+    pub fn weakness(&self, now: i64) -> Vec<KeyWeakness> {
+        weakness(self.counted_history(), self.weights.len(), now)
+    }
+
+    /// Skips of every key over the counted history, indexed by key id.
+    pub fn skips_per_key(&self) -> Vec<i32> {
+        let mut skips = vec![0; self.weights.len()];
+        self.counted_history().for_each(|receipt| {
+            receipt
+                .corrected()
+                .key_data_archive
+                .iter()
+                .flat_map(|keys| keys.0.iter())
+                .for_each(|key| {
+                    if let Some(count) = skips.get_mut(key.nid) {
+                        *count += key.skips;
+                    }
+                });
+        });
+        skips
+    }
+
+    /// Reasons given for skips over the counted history, with how often each was given, most
+    /// frequent first.
+    pub fn skip_reasons(&self) -> Vec<(String, usize)> {
+        let mut reasons: Vec<(String, usize)> = Vec::new();
+        self.counted_history()
+            .map(Receipt::corrected)
+            .flat_map(|receipt| receipt.skips.into_iter())
+            .filter_map(|skip| skip.reason)
+            .for_each(
+                |reason| match reasons.iter_mut().find(|(given, _)| *given == reason) {
+                    Some((_, count)) => *count += 1,
+                    None => reasons.push((reason, 1)),
+                },
+            );
+        reasons.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        reasons
+    }
+
+    /// Receipts of the sessions in the history, leaving out withdrawn ones.
+    pub fn sessions(&self) -> impl Iterator<Item = &Receipt> {
+        self.history
+            .iter()
+            .filter(|receipt| !receipt.is_withdrawn())
+    }
+
+    /// Sessions that were not excluded from statistics, leaving out practice logged by hand
+    /// unless the goals count it.
+    pub fn counted_history(&self) -> impl Iterator<Item = &Receipt> {
+        self.sessions().filter(|receipt| {
+            !receipt.is_excluded() && (self.goals.count_manual || !receipt.manual)
+        })
+    }
+}
+
+// The last timestamp of the receipt marks the end of the session.
+fn finished_at_of(receipt: &Receipt) -> Option<i64> {
+    let (_, timestamp) = receipt.time_stamp_archive.as_ref()?.last()?;
+    Some(*timestamp)
+}
+
+// Local date the session ended on.
+// Long enough that nobody syncs into someone else's history by chance.
+fn new_profile_id() -> String {
+    format!("{:032x}", rand::random::<u128>())
+}
+
+fn finished_on(receipt: &Receipt) -> Option<NaiveDate> {
+    DateTime::from_timestamp(finished_at_of(receipt)?, 0)
+        .map(|time| time.with_timezone(&Local).date_naive())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn day(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 3, day).unwrap()
+    }
+
+    // Unix timestamp of `hour` o'clock on `day` in local time.
+    fn at(day: u32, hour: u32) -> i64 {
+        let time = self::day(day).and_hms_opt(hour, 0, 0).unwrap();
+        Local.from_local_datetime(&time).unwrap().timestamp()
+    }
+
+    #[test]
+    fn streaks_only_count_counted_sessions() {
+        let mut profile = Profile::default();
+        [1, 2, 3, 4].iter().for_each(|day| {
+            profile.log_practice(at(*day, 9), 600, &[(0, 1)]).unwrap();
+        });
+        // Played in the app on the 3rd and the 4th.
+        profile.history[2].manual = false;
+        profile.history[3].manual = false;
+        assert_eq!(profile.streak(day(4)), 4);
+
+        profile.goals.count_manual = false;
+        assert_eq!(profile.streak(day(4)), 2);
+
+        // Excluded and withdrawn sessions break it.
+        profile.history[2].edit(ReceiptChange::Excluded { excluded: true }, at(5, 9));
+        assert_eq!(profile.streak(day(4)), 1);
+        profile.history[2].edit(ReceiptChange::Excluded { excluded: false }, at(5, 9));
+        let undone = profile.history[3].clone();
+        profile.withdraw(&undone, at(5, 9));
+        assert_eq!(profile.streak(day(4)), 1);
+        assert_eq!(profile.streak(day(5)), 0);
+    }
+
+    #[test]
+    fn streaks_count_days_rather_than_sessions() {
+        let mut profile = Profile::default();
+        profile.log_practice(at(1, 23), 600, &[(0, 1)]).unwrap();
+        profile.log_practice(at(2, 8), 600, &[(0, 1)]).unwrap();
+        profile.log_practice(at(2, 20), 600, &[(0, 1)]).unwrap();
+        assert_eq!(profile.streak(day(2)), 2);
+        assert_eq!(profile.streak(day(1)), 1);
+        assert_eq!(Profile::default().streak(day(2)), 0);
+    }
+
+    #[test]
+    fn logging_shares_the_time_between_the_keys_practiced() {
+        let mut profile = Profile::default();
+        let receipt = profile
+            .log_practice(at(1, 9), 601, &[(0, 0), (7, 2), (3, 1)])
+            .unwrap();
+        assert_eq!(receipt.key_sequence, vec![7, 7, 3]);
+        let keys = &receipt.key_data_archive.as_ref().unwrap().0;
+        let logged = |nid: usize| (keys[nid].repetitions, keys[nid].working_seconds);
+        assert_eq!(logged(0), (0, 0));
+        assert_eq!(logged(7), (2, 301));
+        assert_eq!(logged(3), (1, 300));
+        assert_eq!(
+            SessionStatistics::from_receipt(receipt).working_seconds,
+            601
+        );
+    }
+
+    #[test]
+    fn logging_rejects_what_cannot_have_been_practiced() {
+        let mut profile = Profile::default();
+        [
+            (600, vec![(7, 2), (99, 5)]),
+            (600, vec![(7, MAX_LOGGED_REPETITIONS + 1)]),
+            (600, vec![(7, 0)]),
+            (600, vec![]),
+            (-1, vec![(7, 2)]),
+        ]
+        .iter()
+        .for_each(|(seconds, repetitions)| {
+            assert!(profile
+                .log_practice(at(1, 9), *seconds, repetitions)
+                .is_err());
+        });
+        assert!(profile.history.is_empty());
+
+        let receipt = profile
+            .log_practice(at(1, 9), 600, &[(7, MAX_LOGGED_REPETITIONS)])
+            .unwrap();
+        assert_eq!(receipt.key_sequence.len(), MAX_LOGGED_REPETITIONS as usize);
+    }
+}
@@ -0,0 +1,188 @@
+use crate::clock::Clock;
+use crate::models::Receipt;
+use crate::transitions::{PracticeSessionState, SessionCommand};
+
+use color_eyre::eyre::{eyre, Result, WrapErr};
+
+/*
+ * Session Replay
+ *
+ * A replay script is a text file with one step per line: a unix timestamp (in seconds) followed
+ * by a session command. An optional `seed <number>` line makes the selected keys reproducible.
+ * Blank lines and lines starting with `#` are ignored.
+ *
+ *     # a student's session
+ *     seed 42
+ *     0    new
+ *     95   skip
+ *     210  pause
+ *     300  resume
+ *     420  finish
+ */
+
+/// One line of a replay script.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScriptStep {
+    pub timestamp: i64,
+    pub command: SessionCommand,
+}
+
+/// A parsed replay script.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Script {
+    pub seed: Option<u64>,
+    pub steps: Vec<ScriptStep>,
+}
+
+/// Parses a replay script, rejecting unknown commands and timestamps that go back in time.
+pub fn parse_script(script: &str) -> Result<Script> {
+    let mut seed: Option<u64> = None;
+    let mut steps: Vec<ScriptStep> = Vec::new();
+
+    for (index, line) in script.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut fields = line.split_whitespace();
+        let (Some(timestamp), Some(command), None) = (fields.next(), fields.next(), fields.next())
+        else {
+            return Err(eyre!(
+                "Line {}: expected '<timestamp> <command>' but found '{}'.",
+                line_number,
+                line
+            ));
+        };
+
+        if timestamp == "seed" {
+            let value: u64 = command
+                .parse()
+                .wrap_err_with(|| format!("Line {}: invalid seed.", line_number))?;
+            seed = Some(value);
+            continue;
+        }
+
+        let timestamp: i64 = timestamp
+            .parse()
+            .wrap_err_with(|| format!("Line {}: invalid timestamp.", line_number))?;
+        let command: SessionCommand = command
+            .parse()
+            .wrap_err_with(|| format!("Line {}: invalid command.", line_number))?;
+
+        if let Some(previous) = steps.last() {
+            if timestamp < previous.timestamp {
+                return Err(eyre!(
+                    "Line {}: timestamp {} is earlier than the previous step.",
+                    line_number,
+                    timestamp
+                ));
+            }
+        }
+
+        steps.push(ScriptStep { timestamp, command });
+    }
+
+    Ok(Script { seed, steps })
+}
+
+/// Runs `steps` against `state`, moving its clock to each step's timestamp before applying it.
+pub fn replay_onto(state: &mut PracticeSessionState, steps: &[ScriptStep]) {
+    steps.iter().for_each(|step| {
+        state.session_data.clock.set(step.timestamp);
+        state.apply(step.command);
+    });
+}
+
+/// Runs `script` against a fresh session and returns its receipt.
+///
+/// A script that does not end with `finish` is finished at the time of its last step.
+pub fn replay(script: &Script) -> Result<Receipt> {
+    let mut state = PracticeSessionState::default();
+    state.session_data.clock = Clock::Manual(0);
+    state.session_data.set_seed(script.seed);
+
+    replay_onto(&mut state, &script.steps);
+    if script.steps.last().map(|step| step.command) != Some(SessionCommand::Finish) {
+        state.finish();
+    }
+
+    state
+        .session_data
+        .receipt
+        .ok_or_else(|| eyre!("The replayed session did not produce a receipt."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_of(script: &str) -> String {
+        format!("{:#}", parse_script(script).unwrap_err())
+    }
+
+    #[test]
+    fn parses_seed_comments_and_aliases() {
+        let script = parse_script("# comment\n\nseed 3\n0 n\n 10  skip \n10 t\n20 q\n").unwrap();
+        assert_eq!(script.seed, Some(3));
+        assert_eq!(
+            script.steps,
+            vec![
+                ScriptStep {
+                    timestamp: 0,
+                    command: SessionCommand::NewKey
+                },
+                ScriptStep {
+                    timestamp: 10,
+                    command: SessionCommand::Skip
+                },
+                ScriptStep {
+                    timestamp: 10,
+                    command: SessionCommand::TogglePause
+                },
+                ScriptStep {
+                    timestamp: 20,
+                    command: SessionCommand::Finish
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_commands() {
+        let error = error_of("0 new\n5 jump\n");
+        assert!(error.starts_with("Line 2: invalid command."), "{error}");
+        assert!(error.contains("Unknown session command 'jump'"), "{error}");
+    }
+
+    #[test]
+    fn rejects_bad_timestamps() {
+        assert!(error_of("soon new").starts_with("Line 1: invalid timestamp."));
+        assert!(error_of("1.5 new").starts_with("Line 1: invalid timestamp."));
+        assert!(error_of("seed -1").starts_with("Line 1: invalid seed."));
+    }
+
+    #[test]
+    fn rejects_timestamps_going_backwards() {
+        let error = error_of("0 new\n100 pause\n# rewound\n99 resume\n");
+        assert_eq!(
+            error,
+            "Line 4: timestamp 99 is earlier than the previous step."
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        assert!(error_of("0").starts_with("Line 1: expected '<timestamp> <command>'"));
+        assert!(error_of("0 new now").starts_with("Line 1: expected '<timestamp> <command>'"));
+    }
+
+    #[test]
+    fn finishes_unfinished_scripts() {
+        let receipt = replay(&parse_script("seed 1\n0 new\n30 new\n").unwrap()).unwrap();
+        let time_stamps = receipt.time_stamp_archive.unwrap();
+        assert_eq!(time_stamps.last(), Some(&("Finishing".to_string(), 30)));
+        assert_eq!(receipt.key_sequence.len(), 2);
+    }
+}
@@ -0,0 +1,191 @@
+use serde::{Deserialize, Serialize};
+
+/*
+ * Scales
+ *
+ * Keys are identified by their pitch class, counted in semitones from C like `KeyData::nid`.
+ * Notes are MIDI note numbers.
+ */
+
+/// Kind of scale practiced in each key.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ScaleKind {
+    #[default]
+    Major,
+    NaturalMinor,
+    HarmonicMinor,
+}
+
+impl ScaleKind {
+    pub const ALL: [ScaleKind; 3] = [
+        ScaleKind::Major,
+        ScaleKind::NaturalMinor,
+        ScaleKind::HarmonicMinor,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ScaleKind::Major => "Major",
+            ScaleKind::NaturalMinor => "Natural Minor",
+            ScaleKind::HarmonicMinor => "Harmonic Minor",
+        }
+    }
+
+    /// Semitones above the root of each degree of the scale.
+    pub fn intervals(&self) -> [u8; 7] {
+        match self {
+            ScaleKind::Major => [0, 2, 4, 5, 7, 9, 11],
+            ScaleKind::NaturalMinor => [0, 2, 3, 5, 7, 8, 10],
+            ScaleKind::HarmonicMinor => [0, 2, 3, 5, 7, 8, 11],
+        }
+    }
+}
+
+/// Pitch class (0 = C) of a MIDI note.
+pub fn pitch_class(note: u8) -> usize {
+    note as usize % 12
+}
+
+/// Pitch class of a key written as a letter followed by sharps or flats, e.g. `"Eb"` or `"F#"`.
+pub fn key_from_name(name: &str) -> Option<usize> {
+    let mut chars = name.trim().chars();
+    let natural: i32 = match chars.next()?.to_ascii_uppercase() {
+        'C' => 0,
+        'D' => 2,
+        'E' => 4,
+        'F' => 5,
+        'G' => 7,
+        'A' => 9,
+        'B' => 11,
+        _ => return None,
+    };
+
+    let mut offset = 0;
+    for accidental in chars {
+        offset += match accidental {
+            '#' | '♯' => 1,
+            'b' | '♭' => -1,
+            _ => return None,
+        };
+    }
+
+    Some((natural + offset).rem_euclid(12) as usize)
+}
+
+/// Pitch classes of the scale built on `root`, starting from the root.
+pub fn scale_pitch_classes(root: usize, kind: ScaleKind) -> Vec<usize> {
+    kind.intervals()
+        .iter()
+        .map(|interval| (root + *interval as usize) % 12)
+        .collect()
+}
+
+/// Whether `note` belongs to the scale built on `root`.
+pub fn is_in_key(root: usize, kind: ScaleKind, note: u8) -> bool {
+    scale_pitch_classes(root, kind).contains(&pitch_class(note))
+}
+
+/// Fraction of `notes` that belong to the scale built on `root`, or `None` without notes.
+pub fn in_key_ratio(root: usize, kind: ScaleKind, notes: &[u8]) -> Option<f32> {
+    if notes.is_empty() {
+        return None;
+    }
+
+    let in_key = notes
+        .iter()
+        .filter(|note| is_in_key(root, kind, **note))
+        .count();
+    Some(in_key as f32 / notes.len() as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_key_names_with_any_spelling() {
+        assert_eq!(key_from_name("C"), Some(0));
+        assert_eq!(key_from_name(" f# "), Some(6));
+        assert_eq!(key_from_name("Gb"), Some(6));
+        assert_eq!(key_from_name("G♭"), Some(6));
+        assert_eq!(key_from_name("F♯"), Some(6));
+        assert_eq!(key_from_name("Eb"), key_from_name("D#"));
+        assert_eq!(key_from_name("Bb"), Some(10));
+        // Spellings that cross the octave or use double accidentals.
+        assert_eq!(key_from_name("Cb"), Some(11));
+        assert_eq!(key_from_name("B#"), Some(0));
+        assert_eq!(key_from_name("E#"), Some(5));
+        assert_eq!(key_from_name("Fb"), Some(4));
+        assert_eq!(key_from_name("Ebb"), Some(2));
+        assert_eq!(key_from_name("C##"), Some(2));
+    }
+
+    #[test]
+    fn rejects_invalid_key_names() {
+        assert_eq!(key_from_name(""), None);
+        assert_eq!(key_from_name("   "), None);
+        assert_eq!(key_from_name("H"), None);
+        assert_eq!(key_from_name("#"), None);
+        assert_eq!(key_from_name("C major"), None);
+        assert_eq!(key_from_name("Cx"), None);
+        assert_eq!(key_from_name("Db7"), None);
+    }
+
+    #[test]
+    fn checks_notes_against_every_scale_kind() {
+        let c = key_from_name("C").unwrap();
+        // C4, E4, G4 and B4 are in C major; C#4 and Bb4 are not.
+        [60, 64, 67, 71, 72, 48].iter().for_each(|note| {
+            assert!(is_in_key(c, ScaleKind::Major, *note), "{note}");
+        });
+        [61, 70].iter().for_each(|note| {
+            assert!(!is_in_key(c, ScaleKind::Major, *note), "{note}");
+        });
+
+        // Eb and Ab belong to C minor, B only to the harmonic minor and Bb only to the natural.
+        assert!(is_in_key(c, ScaleKind::NaturalMinor, 63));
+        assert!(is_in_key(c, ScaleKind::NaturalMinor, 68));
+        assert!(is_in_key(c, ScaleKind::NaturalMinor, 70));
+        assert!(!is_in_key(c, ScaleKind::NaturalMinor, 71));
+        assert!(is_in_key(c, ScaleKind::HarmonicMinor, 71));
+        assert!(!is_in_key(c, ScaleKind::HarmonicMinor, 70));
+
+        // Enharmonic spellings name the same scale.
+        let f_sharp = key_from_name("F#").unwrap();
+        let g_flat = key_from_name("Gb").unwrap();
+        (0..128).for_each(|note| {
+            assert_eq!(
+                is_in_key(f_sharp, ScaleKind::Major, note),
+                is_in_key(g_flat, ScaleKind::Major, note)
+            );
+        });
+        // A#, the leading tone of B major, at both ends of the MIDI range.
+        let b = key_from_name("B").unwrap();
+        assert!(is_in_key(b, ScaleKind::Major, 10));
+        assert!(is_in_key(b, ScaleKind::Major, 118));
+        assert!(!is_in_key(b, ScaleKind::Major, 127));
+    }
+
+    #[test]
+    fn every_scale_has_seven_distinct_notes() {
+        ScaleKind::ALL.iter().for_each(|kind| {
+            (0..12).for_each(|root| {
+                let mut classes = scale_pitch_classes(root, *kind);
+                assert_eq!(classes[0], root);
+                classes.sort_unstable();
+                classes.dedup();
+                assert_eq!(classes.len(), 7, "{kind:?} on {root}");
+            });
+        });
+    }
+
+    #[test]
+    fn measures_the_share_of_notes_in_key() {
+        assert_eq!(in_key_ratio(0, ScaleKind::Major, &[]), None);
+        assert_eq!(
+            in_key_ratio(0, ScaleKind::Major, &[60, 62, 61, 63]),
+            Some(0.5)
+        );
+        assert_eq!(in_key_ratio(7, ScaleKind::Major, &[66, 67]), Some(1.0));
+    }
+}
@@ -0,0 +1,303 @@
+use crate::models::KeyData;
+
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/*
+ * Key Selection
+ */
+
+/// How the next key is picked among the keys with a positive weight.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Strategy {
+    /// With a probability proportional to the weight of each key.
+    #[default]
+    Weighted,
+    /// With the same probability for every key.
+    Uniform,
+    /// One after the other, following a fixed order.
+    InOrder,
+    /// Weighted, favoring the keys that need practice most, see [`crate::weakness`].
+    Weakest,
+}
+
+impl Strategy {
+    pub const ALL: [Strategy; 4] = [
+        Strategy::Weighted,
+        Strategy::Uniform,
+        Strategy::InOrder,
+        Strategy::Weakest,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Strategy::Weighted => "Weighted",
+            Strategy::Uniform => "Uniform",
+            Strategy::InOrder => "In order",
+            Strategy::Weakest => "Weakest keys",
+        }
+    }
+}
+
+/// Limits on which keys may follow the keys selected so far, applied on top of any [`Strategy`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Constraints {
+    /// Number of most recently selected keys that may not come up again, 1 to avoid immediate
+    /// repeats and 0 to allow them.
+    pub avoid_last: usize,
+    /// Smallest interval in semitones, up or down, between consecutive keys, from 0 to 6.
+    pub min_interval: usize,
+}
+
+impl Constraints {
+    /// Whether `key` may be selected after the keys of `history`.
+    pub fn allows(&self, key: usize, history: &[usize]) -> bool {
+        let recent = &history[history.len().saturating_sub(self.avoid_last)..];
+        let far_enough = history.last().map_or(true, |previous| {
+            let distance = key.abs_diff(*previous) % 12;
+            distance.min(12 - distance) >= self.min_interval
+        });
+        !recent.contains(&key) && far_enough
+    }
+
+    /// The keys with a weight of zero for every key the constraints do not allow after `history`.
+    ///
+    /// When the constraints leave no key with a positive weight, the keys are returned as they
+    /// are, so a session never runs out of keys.
+    pub fn apply(&self, keys: &[KeyData], history: &[usize]) -> Vec<KeyData> {
+        let constrained: Vec<KeyData> = keys
+            .iter()
+            .map(|key| match self.allows(key.nid, history) {
+                true => *key,
+                false => KeyData { weight: 0, ..*key },
+            })
+            .collect();
+        match constrained.iter().any(|key| key.weight > 0) {
+            true => constrained,
+            false => keys.to_vec(),
+        }
+    }
+}
+
+/// Picks the index of a key with a probability proportional to its weight.
+///
+/// Keys with a weight of zero or less are never picked. Returns `None` when no key can be picked.
+pub fn choose_weighted<R: Rng + ?Sized>(keys: &[KeyData], rng: &mut R) -> Option<usize> {
+    let weights = keys.iter().map(|key| key.weight.max(0) as u32);
+
+    match WeightedIndex::new(weights) {
+        Ok(distribution) => Some(distribution.sample(rng)),
+        Err(_) => None,
+    }
+}
+
+/// Picks the index of a key with a positive weight, every such key being equally likely.
+pub fn choose_uniform<R: Rng + ?Sized>(keys: &[KeyData], rng: &mut R) -> Option<usize> {
+    let candidates: Vec<usize> = (0..keys.len())
+        .filter(|index| keys[*index].weight > 0)
+        .collect();
+    candidates.choose(rng).copied()
+}
+
+/// Picks the key that follows `previous` in `order`, skipping keys without a positive weight.
+///
+/// An empty `order` goes through the keys from C upwards. Without a previous key, or when it is
+/// not part of `order`, the first key of `order` comes next.
+pub fn choose_in_order(
+    keys: &[KeyData],
+    order: &[usize],
+    previous: Option<usize>,
+) -> Option<usize> {
+    let default_order: Vec<usize> = (0..keys.len()).collect();
+    let order = if order.is_empty() {
+        &default_order
+    } else {
+        order
+    };
+
+    let start = previous
+        .and_then(|previous| order.iter().position(|key| *key == previous))
+        .map(|position| position + 1)
+        .unwrap_or(0);
+
+    (0..order.len())
+        .map(|offset| order[(start + offset) % order.len()])
+        .find(|key| keys.get(*key).map(|data| data.weight > 0).unwrap_or(false))
+}
+
+/// Picks the first pinned key with a positive weight, from C upwards, that is not part of
+/// `history` yet.
+///
+/// ```
+/// use jovian_cycles_core::selection::choose_pinned;
+/// use jovian_cycles_core::PracticeSessionData;
+///
+/// let mut data = PracticeSessionData::new();
+/// data.all_keys_map.0[4].pinned = true;
+/// data.all_keys_map.0[9].pinned = true;
+///
+/// assert_eq!(choose_pinned(&data.all_keys_map.0, &[]), Some(4));
+/// assert_eq!(choose_pinned(&data.all_keys_map.0, &[4, 2]), Some(9));
+/// assert_eq!(choose_pinned(&data.all_keys_map.0, &[9, 4]), None);
+/// ```
+pub fn choose_pinned(keys: &[KeyData], history: &[usize]) -> Option<usize> {
+    keys.iter()
+        .position(|key| key.pinned && key.weight > 0 && !history.contains(&key.nid))
+}
+
+/// Probability of each key being picked by [`choose_weighted`], in the same order as `keys`.
+pub fn probabilities(keys: &[KeyData]) -> Vec<f32> {
+    let total: i64 = keys.iter().map(|key| key.weight.max(0) as i64).sum();
+
+    keys.iter()
+        .map(|key| {
+            if total == 0 {
+                0.0
+            } else {
+                key.weight.max(0) as f32 / total as f32
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PracticeSessionData, Profile};
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    // Weights with `eligible` keys out of twelve left selectable, picked by `rng`.
+    fn weights(rng: &mut StdRng, eligible: usize) -> Vec<i32> {
+        let mut keys: Vec<usize> = (0..12).collect();
+        keys.shuffle(rng);
+        let mut weights = vec![0; 12];
+        keys[..eligible]
+            .iter()
+            .for_each(|key| weights[*key] = rng.gen_range(1..=500));
+        weights
+    }
+
+    // Selects keys for a while and checks every selection against the constraints.
+    fn check_session(data: &mut PracticeSessionData, selections: usize) {
+        for _ in 0..selections {
+            let keys = data.skipped_keys();
+            let history = data.key_history.clone();
+            let selectable = keys.iter().any(|key| key.weight > 0);
+            let possible = keys
+                .iter()
+                .any(|key| key.weight > 0 && data.constraints.allows(key.nid, &history));
+            let chances = data.next_key_probabilities();
+
+            data.get_new_key();
+
+            if !selectable {
+                assert_eq!(data.key_history, history, "{data:?}");
+                continue;
+            }
+            assert_eq!(data.key_history.len(), history.len() + 1, "{data:?}");
+            let selected = *data.key_history.last().unwrap();
+            assert!(keys[selected].weight > 0, "{data:?}");
+            assert!(chances[selected] > 0.0, "{data:?}");
+            let total: f32 = chances.iter().sum();
+            assert!((total - 1.0).abs() < 1e-4, "{chances:?}");
+            if possible {
+                assert!(data.constraints.allows(selected, &history), "{data:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn selections_keep_to_the_constraints_whenever_they_can() {
+        let mut profile = Profile::default();
+        profile
+            .log_practice(0, 600, &[(0, 10), (3, 1), (7, 4)])
+            .unwrap();
+        let weakness = profile.weakness(7 * 24 * 3600);
+
+        for seed in 0..8u64 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            for strategy in Strategy::ALL {
+                for avoid_last in 0..=13 {
+                    for min_interval in 0..=6 {
+                        // Down to fewer selectable keys than the constraints would need.
+                        for eligible in [12, 5, 2, 1, 0] {
+                            let mut data = PracticeSessionData::new();
+                            data.set_seed(Some(seed));
+                            data.strategy = strategy;
+                            data.constraints = Constraints {
+                                avoid_last,
+                                min_interval,
+                            };
+                            data.weakness = weakness.clone();
+                            let weights = weights(&mut rng, eligible);
+                            data.all_keys_map
+                                .0
+                                .iter_mut()
+                                .zip(weights)
+                                .for_each(|(key, weight)| key.weight = weight);
+                            check_session(&mut data, 30);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn pinned_and_skipped_keys_keep_to_the_constraints() {
+        for seed in 0..50u64 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            for strategy in Strategy::ALL {
+                let mut data = PracticeSessionData::new();
+                data.set_seed(Some(seed));
+                data.strategy = strategy;
+                data.skip_bonus = 100;
+                data.constraints = Constraints {
+                    avoid_last: rng.gen_range(0..=12),
+                    min_interval: rng.gen_range(0..=6),
+                };
+                data.all_keys_map.0.iter_mut().for_each(|key| {
+                    key.weight = rng.gen_range(0..=3) * 100;
+                    key.pinned = rng.gen_bool(0.2);
+                    key.skips = rng.gen_range(0..=2);
+                });
+                check_session(&mut data, 40);
+            }
+        }
+    }
+
+    #[test]
+    fn selections_stay_within_fewer_keys_than_the_constraints_avoid() {
+        let mut data = PracticeSessionData::new();
+        data.set_seed(Some(3));
+        data.all_keys_map
+            .0
+            .iter_mut()
+            .for_each(|key| key.weight = 0);
+        data.all_keys_map.0[2].weight = 100;
+        data.all_keys_map.0[9].weight = 100;
+        data.constraints = Constraints {
+            avoid_last: 5,
+            min_interval: 0,
+        };
+
+        // Both keys are always among the last five, so the constraints are left aside rather
+        // than running out of keys.
+        (0..20).for_each(|_| data.get_new_key());
+        assert_eq!(data.key_history.len(), 20);
+        assert!(data.key_history.iter().all(|key| *key == 2 || *key == 9));
+
+        // Avoiding only the last key, the two take turns.
+        data.constraints.avoid_last = 1;
+        (0..20).for_each(|_| data.get_new_key());
+        assert!(data.key_history[19..]
+            .windows(2)
+            .all(|pair| pair[0] != pair[1]));
+    }
+}
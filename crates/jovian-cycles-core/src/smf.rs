@@ -0,0 +1,455 @@
+use crate::evaluation::{RunDirection, ScaleExercise};
+use crate::scales::ScaleKind;
+
+/*
+ * Standard MIDI Files
+ *
+ * Writes format 1 Standard MIDI Files, so a sequence of keys can be loaded into a DAW.
+ */
+
+/// Ticks per quarter note used by [`render_scale_sequence`].
+pub const TICKS_PER_QUARTER: u16 = 480;
+
+/// Event of a [`SmfTrack`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmfEvent {
+    NoteOn { channel: u8, note: u8, velocity: u8 },
+    NoteOff { channel: u8, note: u8 },
+    Tempo { microseconds_per_quarter: u32 },
+}
+
+/// A named track whose events are placed at absolute tick positions.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SmfTrack {
+    pub name: String,
+    pub events: Vec<(u32, SmfEvent)>,
+}
+
+impl SmfTrack {
+    pub fn new(name: &str) -> Self {
+        SmfTrack {
+            name: name.to_string(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Adds a note starting at `start` and lasting `duration` ticks.
+    pub fn add_note(&mut self, start: u32, duration: u32, channel: u8, note: u8, velocity: u8) {
+        self.events.push((
+            start,
+            SmfEvent::NoteOn {
+                channel,
+                note,
+                velocity,
+            },
+        ));
+        self.events
+            .push((start + duration, SmfEvent::NoteOff { channel, note }));
+    }
+}
+
+/// Serializes `tracks` into the bytes of a format 1 Standard MIDI File.
+pub fn write_smf(tracks: &[SmfTrack], ticks_per_quarter: u16) -> Vec<u8> {
+    let mut bytes: Vec<u8> = Vec::new();
+
+    bytes.extend_from_slice(b"MThd");
+    bytes.extend_from_slice(&6u32.to_be_bytes());
+    bytes.extend_from_slice(&1u16.to_be_bytes());
+    bytes.extend_from_slice(&(tracks.len() as u16).to_be_bytes());
+    bytes.extend_from_slice(&ticks_per_quarter.to_be_bytes());
+
+    tracks.iter().for_each(|track| {
+        let chunk = track_chunk(track);
+        bytes.extend_from_slice(b"MTrk");
+        bytes.extend_from_slice(&(chunk.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&chunk);
+    });
+
+    bytes
+}
+
+fn track_chunk(track: &SmfTrack) -> Vec<u8> {
+    let mut chunk: Vec<u8> = Vec::new();
+
+    write_vlq(0, &mut chunk);
+    chunk.extend_from_slice(&[0xFF, 0x03]);
+    write_vlq(track.name.len() as u32, &mut chunk);
+    chunk.extend_from_slice(track.name.as_bytes());
+
+    // Notes ending on a tick are released before the notes starting on it.
+    let mut events = track.events.clone();
+    events.sort_by_key(|(tick, event)| (*tick, !matches!(event, SmfEvent::NoteOff { .. })));
+
+    let mut previous_tick = 0;
+    events.iter().for_each(|(tick, event)| {
+        write_vlq(tick - previous_tick, &mut chunk);
+        previous_tick = *tick;
+
+        match *event {
+            SmfEvent::NoteOn {
+                channel,
+                note,
+                velocity,
+            } => chunk.extend_from_slice(&[0x90 | (channel & 0x0F), note & 0x7F, velocity & 0x7F]),
+            SmfEvent::NoteOff { channel, note } => {
+                chunk.extend_from_slice(&[0x80 | (channel & 0x0F), note & 0x7F, 0])
+            }
+            SmfEvent::Tempo {
+                microseconds_per_quarter,
+            } => {
+                chunk.extend_from_slice(&[0xFF, 0x51, 0x03]);
+                chunk.extend_from_slice(&microseconds_per_quarter.to_be_bytes()[1..]);
+            }
+        }
+    });
+
+    write_vlq(0, &mut chunk);
+    chunk.extend_from_slice(&[0xFF, 0x2F, 0x00]);
+
+    chunk
+}
+
+/// Appends `value` as a MIDI variable-length quantity.
+pub fn write_vlq(value: u32, out: &mut Vec<u8>) {
+    let mut groups: Vec<u8> = vec![(value & 0x7F) as u8];
+    let mut rest = value >> 7;
+    while rest > 0 {
+        groups.push((rest & 0x7F) as u8 | 0x80);
+        rest >>= 7;
+    }
+    out.extend(groups.iter().rev());
+}
+
+/*
+ * Scale Sequences
+ */
+
+/// Scales of a sequence of keys, played one after the other.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScaleSequence {
+    /// Keys in the order they are played, counted in semitones from C.
+    pub keys: Vec<usize>,
+    pub kind: ScaleKind,
+    pub direction: RunDirection,
+    pub octaves: u8,
+    /// Quarter notes per minute. Every scale note lasts a quarter note.
+    pub bpm: f32,
+    /// Adds a second track holding the root of each scale two octaves below.
+    pub drone: bool,
+}
+
+/// Renders `sequence` as a Standard MIDI File, leaving a bar of rest between keys.
+pub fn render_scale_sequence(sequence: &ScaleSequence) -> Vec<u8> {
+    let quarter = TICKS_PER_QUARTER as u32;
+    let bar = 4 * quarter;
+
+    let mut tempo = SmfTrack::new("Tempo");
+    tempo.events.push((
+        0,
+        SmfEvent::Tempo {
+            microseconds_per_quarter: (60_000_000.0 / sequence.bpm.max(1.0)) as u32,
+        },
+    ));
+    let mut scales = SmfTrack::new("Scales");
+    let mut drone = SmfTrack::new("Drone");
+
+    let mut start = 0;
+    sequence.keys.iter().for_each(|key| {
+        let exercise = ScaleExercise {
+            root: key % 12,
+            kind: sequence.kind,
+            direction: sequence.direction,
+            octaves: sequence.octaves,
+        };
+        let middle_root = 60 + (key % 12) as u8;
+        let first_note = match sequence.direction {
+            RunDirection::Descending => middle_root + 12 * exercise.octaves.max(1),
+            _ => middle_root,
+        };
+        let notes = exercise.expected_notes(first_note);
+
+        notes.iter().enumerate().for_each(|(index, note)| {
+            scales.add_note(start + index as u32 * quarter, quarter, 0, *note, 96);
+        });
+        let length = notes.len() as u32 * quarter;
+        drone.add_note(start, length, 1, middle_root - 24, 64);
+
+        start += length.div_ceil(bar) * bar + bar;
+    });
+
+    let mut tracks = vec![tempo, scales];
+    if sequence.drone {
+        tracks.push(drone);
+    }
+
+    write_smf(&tracks, TICKS_PER_QUARTER)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // What a reader sees in a track: its name and events at absolute ticks.
+    #[derive(Debug, Default)]
+    struct ReadTrack {
+        name: String,
+        events: Vec<(u32, SmfEvent)>,
+        ended: bool,
+    }
+
+    fn read_u16(bytes: &[u8]) -> u16 {
+        u16::from_be_bytes([bytes[0], bytes[1]])
+    }
+
+    fn read_u32(bytes: &[u8]) -> u32 {
+        u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    }
+
+    fn read_vlq(bytes: &[u8], position: &mut usize) -> u32 {
+        let mut value = 0;
+        loop {
+            let byte = bytes[*position];
+            *position += 1;
+            value = (value << 7) | (byte & 0x7F) as u32;
+            if byte & 0x80 == 0 {
+                return value;
+            }
+        }
+    }
+
+    fn read_track(chunk: &[u8]) -> ReadTrack {
+        let mut track = ReadTrack::default();
+        let mut position = 0;
+        let mut tick = 0;
+        while position < chunk.len() {
+            assert!(!track.ended, "events after the end of the track");
+            tick += read_vlq(chunk, &mut position);
+            let status = chunk[position];
+            position += 1;
+            match status {
+                0xFF => {
+                    let kind = chunk[position];
+                    position += 1;
+                    let length = read_vlq(chunk, &mut position) as usize;
+                    let data = &chunk[position..position + length];
+                    position += length;
+                    match kind {
+                        0x03 => track.name = String::from_utf8(data.to_vec()).unwrap(),
+                        0x51 => track.events.push((
+                            tick,
+                            SmfEvent::Tempo {
+                                microseconds_per_quarter: read_u32(&[0, data[0], data[1], data[2]]),
+                            },
+                        )),
+                        0x2F => {
+                            assert!(data.is_empty());
+                            track.ended = true;
+                        }
+                        _ => panic!("unexpected meta event {kind:#x}"),
+                    }
+                }
+                _ => {
+                    let (note, velocity) = (chunk[position], chunk[position + 1]);
+                    position += 2;
+                    let channel = status & 0x0F;
+                    track.events.push((
+                        tick,
+                        match status & 0xF0 {
+                            0x90 => SmfEvent::NoteOn {
+                                channel,
+                                note,
+                                velocity,
+                            },
+                            0x80 => SmfEvent::NoteOff { channel, note },
+                            _ => panic!("unexpected status {status:#x}"),
+                        },
+                    ));
+                }
+            }
+        }
+        track
+    }
+
+    // Reads back the header fields and tracks of a file.
+    fn read_smf(bytes: &[u8]) -> (u16, u16, Vec<ReadTrack>) {
+        assert_eq!(&bytes[0..4], b"MThd");
+        assert_eq!(read_u32(&bytes[4..8]), 6);
+        let format = read_u16(&bytes[8..10]);
+        let track_count = read_u16(&bytes[10..12]);
+        let division = read_u16(&bytes[12..14]);
+
+        let mut tracks = Vec::new();
+        let mut position = 14;
+        while position < bytes.len() {
+            assert_eq!(&bytes[position..position + 4], b"MTrk");
+            let length = read_u32(&bytes[position + 4..position + 8]) as usize;
+            let chunk = &bytes[position + 8..position + 8 + length];
+            tracks.push(read_track(chunk));
+            position += 8 + length;
+        }
+        assert_eq!(position, bytes.len());
+        assert_eq!(tracks.len(), track_count as usize);
+        assert_eq!(format, 1);
+        (format, division, tracks)
+    }
+
+    // Notes of a track as (start, duration, channel, note, velocity), each on paired with the
+    // next off of the same note.
+    fn notes(track: &ReadTrack) -> Vec<(u32, u32, u8, u8, u8)> {
+        let mut open: Vec<(u32, u8, u8, u8)> = Vec::new();
+        let mut notes = Vec::new();
+        track.events.iter().for_each(|(tick, event)| match *event {
+            SmfEvent::NoteOn {
+                channel,
+                note,
+                velocity,
+            } => open.push((*tick, channel, note, velocity)),
+            SmfEvent::NoteOff { channel, note } => {
+                let index = open
+                    .iter()
+                    .position(|(_, open_channel, open_note, _)| {
+                        (*open_channel, *open_note) == (channel, note)
+                    })
+                    .expect("note off without a note on");
+                let (start, channel, note, velocity) = open.remove(index);
+                notes.push((start, tick - start, channel, note, velocity));
+            }
+            SmfEvent::Tempo { .. } => {}
+        });
+        assert!(open.is_empty(), "notes left on: {open:?}");
+        notes.sort();
+        notes
+    }
+
+    fn sequence(drone: bool) -> ScaleSequence {
+        ScaleSequence {
+            keys: vec![0, 7],
+            kind: ScaleKind::Major,
+            direction: RunDirection::Ascending,
+            octaves: 1,
+            bpm: 120.0,
+            drone,
+        }
+    }
+
+    #[test]
+    fn writes_variable_length_quantities() {
+        [
+            (0, vec![0x00]),
+            (0x7F, vec![0x7F]),
+            (0x80, vec![0x81, 0x00]),
+            (0x2000, vec![0xC0, 0x00]),
+            (0x3FFF, vec![0xFF, 0x7F]),
+            (0x0FFF_FFFF, vec![0xFF, 0xFF, 0xFF, 0x7F]),
+        ]
+        .into_iter()
+        .for_each(|(value, expected)| {
+            let mut bytes = Vec::new();
+            write_vlq(value, &mut bytes);
+            assert_eq!(bytes, expected, "{value:#x}");
+            assert_eq!(read_vlq(&bytes, &mut 0), value);
+        });
+    }
+
+    #[test]
+    fn reads_back_a_scale_sequence() {
+        let (_, division, tracks) = read_smf(&render_scale_sequence(&sequence(false)));
+        assert_eq!(division, TICKS_PER_QUARTER);
+        assert_eq!(tracks.len(), 2);
+        assert!(tracks.iter().all(|track| track.ended));
+
+        assert_eq!(tracks[0].name, "Tempo");
+        assert_eq!(
+            tracks[0].events,
+            vec![(
+                0,
+                SmfEvent::Tempo {
+                    microseconds_per_quarter: 500_000
+                }
+            )]
+        );
+
+        assert_eq!(tracks[1].name, "Scales");
+        let quarter = TICKS_PER_QUARTER as u32;
+        let scales = notes(&tracks[1]);
+        assert_eq!(scales.len(), 16);
+        assert!(scales.iter().all(|(_, duration, channel, _, velocity)| (
+            *duration, *channel, *velocity
+        ) == (quarter, 0, 96)));
+
+        // C major from middle C, then a bar of rest after the two bars it fills, then G major.
+        let c_major: Vec<(u32, u8)> = scales[..8]
+            .iter()
+            .map(|(start, _, _, note, _)| (*start, *note))
+            .collect();
+        assert_eq!(
+            c_major,
+            [60, 62, 64, 65, 67, 69, 71, 72]
+                .into_iter()
+                .enumerate()
+                .map(|(index, note)| (index as u32 * quarter, note))
+                .collect::<Vec<(u32, u8)>>()
+        );
+        assert_eq!((scales[8].0, scales[8].3), (12 * quarter, 67));
+        assert_eq!((scales[15].0, scales[15].3), (19 * quarter, 79));
+    }
+
+    #[test]
+    fn releases_notes_before_the_next_starts() {
+        let (_, _, tracks) = read_smf(&render_scale_sequence(&sequence(false)));
+        let events = &tracks[1].events;
+        let quarter = TICKS_PER_QUARTER as u32;
+        assert!(matches!(events[0], (0, SmfEvent::NoteOn { note: 60, .. })));
+        assert!(matches!(
+            events[1],
+            (tick, SmfEvent::NoteOff { note: 60, .. }) if tick == quarter
+        ));
+        assert!(matches!(
+            events[2],
+            (tick, SmfEvent::NoteOn { note: 62, .. }) if tick == quarter
+        ));
+        assert!(events.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+    }
+
+    #[test]
+    fn adds_the_drone_track() {
+        let (_, _, tracks) = read_smf(&render_scale_sequence(&sequence(true)));
+        assert_eq!(tracks.len(), 3);
+        assert_eq!(tracks[2].name, "Drone");
+        assert!(tracks[2].ended);
+        let quarter = TICKS_PER_QUARTER as u32;
+        assert_eq!(
+            notes(&tracks[2]),
+            vec![
+                (0, 8 * quarter, 1, 36, 64),
+                (12 * quarter, 8 * quarter, 1, 43, 64)
+            ]
+        );
+    }
+
+    #[test]
+    fn descends_from_the_top_root() {
+        let sequence = ScaleSequence {
+            keys: vec![2],
+            direction: RunDirection::Descending,
+            octaves: 2,
+            ..sequence(false)
+        };
+        let (_, _, tracks) = read_smf(&render_scale_sequence(&sequence));
+        let scales = notes(&tracks[1]);
+        assert_eq!(scales.len(), 15);
+        assert_eq!(scales.first().map(|note| note.3), Some(86));
+        assert_eq!(scales.last().map(|note| note.3), Some(62));
+    }
+
+    #[test]
+    fn writes_an_empty_sequence() {
+        let sequence = ScaleSequence {
+            keys: Vec::new(),
+            ..sequence(true)
+        };
+        let (_, _, tracks) = read_smf(&render_scale_sequence(&sequence));
+        assert_eq!(tracks.len(), 3);
+        assert!(tracks[1].events.is_empty() && tracks[2].events.is_empty());
+    }
+}
@@ -0,0 +1,85 @@
+use crate::models::{Receipt, TimeCode};
+
+/*
+ * Session Statistics
+ */
+
+/// Totals computed from the receipt of a finished practice session.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SessionStatistics {
+    /// Repetitions summed over every key.
+    pub total_repetitions: i32,
+    /// Number of keys with at least one repetition.
+    pub keys_practiced: usize,
+    /// Seconds spent in the `Working` state.
+    pub working_seconds: i64,
+    /// Seconds spent in the `Resting` state.
+    pub resting_seconds: i64,
+    /// Skips summed over every key.
+    pub skips: i32,
+}
+
+impl SessionStatistics {
+    /// Totals of the receipt with its corrections applied.
+    ///
+    /// Working time comes from the timeline, adjusted by the corrections made to the working time
+    /// of single keys.
+    pub fn from_receipt(receipt: &Receipt) -> Self {
+        let mut statistics = SessionStatistics::default();
+        let corrected = receipt.corrected();
+
+        if let Some(keys) = &corrected.key_data_archive {
+            statistics.total_repetitions = keys.0.iter().map(|key| key.repetitions).sum();
+            statistics.keys_practiced = keys.0.iter().filter(|key| key.repetitions > 0).count();
+            statistics.skips = keys.0.iter().map(|key| key.skips).sum();
+        }
+
+        let recorded = |receipt: &Receipt| -> i64 {
+            receipt
+                .key_data_archive
+                .iter()
+                .flat_map(|keys| keys.0.iter())
+                .map(|key| key.working_seconds)
+                .sum()
+        };
+        statistics.working_seconds += recorded(&corrected) - recorded(receipt);
+
+        if let Some(time_stamps) = &receipt.time_stamp_archive {
+            state_durations(time_stamps)
+                .iter()
+                .for_each(|(state_name, seconds)| match state_name.as_str() {
+                    "Working" => statistics.working_seconds += seconds,
+                    "Resting" => statistics.resting_seconds += seconds,
+                    _ => {}
+                });
+        }
+
+        statistics
+    }
+}
+
+/// Pairs each state in the history with the number of seconds until the next state started.
+///
+/// The last state has no end and is left out.
+pub fn state_durations(time_stamps: &[TimeCode]) -> Vec<(String, i64)> {
+    time_stamps
+        .windows(2)
+        .map(|pair| (pair[0].0.clone(), pair[1].1 - pair[0].1))
+        .collect()
+}
+
+/// Formats a number of seconds as `m:ss`, or `h:mm:ss` from an hour on.
+///
+/// ```
+/// use jovian_cycles_core::statistics::format_duration;
+///
+/// assert_eq!(format_duration(75), "1:15");
+/// assert_eq!(format_duration(3725), "1:02:05");
+/// ```
+pub fn format_duration(seconds: i64) -> String {
+    let seconds = seconds.max(0);
+    match seconds / 3600 {
+        0 => format!("{}:{:02}", seconds / 60, seconds % 60),
+        hours => format!("{}:{:02}:{:02}", hours, seconds / 60 % 60, seconds % 60),
+    }
+}
@@ -0,0 +1,329 @@
+use crate::models::Receipt;
+
+use color_eyre::Result;
+
+/*
+ * Sync
+ *
+ * Copies of the app on different devices share their history through a sync server. A client
+ * sends every receipt it has for a profile and gets back every receipt the server has for it,
+ * both sides keeping the union. The recorded data of a receipt never changes once a session
+ * finishes, only corrections are appended to it, so a receipt is identified by its session id
+ * alone, corrections are merged the same way, and merging the same receipts twice, or in any
+ * order, gives the same history.
+ *
+ * The protocol is plain HTTP with JSON bodies:
+ *
+ *     GET  /profiles/<id>/receipts    every receipt stored for the profile
+ *     POST /profiles/<id>/receipts    merges the receipts in the body, answers with every
+ *                                     receipt stored for the profile
+ *
+ * Profiles are told apart by their generated id ([`crate::Profile::id`]) rather than their name,
+ * so two students who both kept the default name never share a history. Devices share the
+ * history of a profile by using the same id.
+ */
+
+/// Path of the receipts of the profile with id `profile` on a sync server.
+///
+/// Ids are typed in by hand to share a profile between devices, so any text is encoded.
+///
+/// ```
+/// use jovian_cycles_core::sync::receipts_path;
+///
+/// assert_eq!(receipts_path("3f2a9c"), "/profiles/3f2a9c/receipts");
+/// assert_eq!(receipts_path("Ms. Ozerova's class"), "/profiles/Ms.%20Ozerova%27s%20class/receipts");
+/// ```
+pub fn receipts_path(profile: &str) -> String {
+    format!("/profiles/{}/receipts", encode_component(profile))
+}
+
+/// Address of the receipts of the profile with id `profile` on the sync server at `server_url`.
+pub fn receipts_url(server_url: &str, profile: &str) -> String {
+    format!(
+        "{}{}",
+        server_url.trim().trim_end_matches('/'),
+        receipts_path(profile)
+    )
+}
+
+/// Id of the profile named by a path built with [`receipts_path`], if `path` is one.
+pub fn profile_from_path(path: &str) -> Option<String> {
+    let name = path.strip_prefix("/profiles/")?.strip_suffix("/receipts")?;
+    if name.is_empty() || name.contains('/') {
+        return None;
+    }
+    decode_component(name)
+}
+
+/// Identifier of the session a receipt comes from.
+///
+/// Receipts written before sessions had identifiers are identified by their timestamps.
+pub fn session_id(receipt: &Receipt) -> String {
+    if !receipt.session_id.is_empty() {
+        return receipt.session_id.clone();
+    }
+    let time_stamps = receipt.time_stamp_archive.as_deref().unwrap_or_default();
+    let first = time_stamps.first().map(|(_, timestamp)| *timestamp);
+    let last = time_stamps.last().map(|(_, timestamp)| *timestamp);
+    format!("{}-{}", first.unwrap_or_default(), last.unwrap_or_default())
+}
+
+/// Adds the receipts of `incoming` missing from `history`, and the corrections missing from the
+/// receipts both have, and returns how many receipts were added or corrected.
+///
+/// The history ends up ordered by the time sessions finished, so every device shows the same
+/// history once they have synced.
+pub fn merge(history: &mut Vec<Receipt>, incoming: Vec<Receipt>) -> usize {
+    let mut known: Vec<String> = history.iter().map(session_id).collect();
+    let before = history.len();
+    let mut corrected = 0;
+
+    incoming.into_iter().for_each(|receipt| {
+        let id = session_id(&receipt);
+        match known.iter().position(|known| *known == id) {
+            Some(index) => {
+                if merge_edits(&mut history[index], receipt) {
+                    corrected += 1;
+                }
+            }
+            None => {
+                known.push(id);
+                history.push(receipt);
+            }
+        }
+    });
+
+    let added = history.len() - before;
+    if added > 0 {
+        history.sort_by_cached_key(|receipt| (finished_at(receipt), session_id(receipt)));
+    }
+    added + corrected
+}
+
+/// Serializes receipts for a request or a response body.
+pub fn to_json(receipts: &[Receipt]) -> Result<String> {
+    Ok(serde_json::to_string(receipts)?)
+}
+
+/// Reads receipts from a request or a response body.
+pub fn from_json(text: &str) -> Result<Vec<Receipt>> {
+    Ok(serde_json::from_str(text)?)
+}
+
+// Corrections are only ever appended, so the audit trails of two copies of a receipt are merged
+// like the histories: keeping the union, ordered by the time corrections were made.
+fn merge_edits(receipt: &mut Receipt, incoming: Receipt) -> bool {
+    let before = receipt.edits.len();
+    incoming.edits.into_iter().for_each(|edit| {
+        if !receipt.edits.contains(&edit) {
+            receipt.edits.push(edit);
+        }
+    });
+    if receipt.edits.len() == before {
+        return false;
+    }
+    receipt.edits.sort_by_key(|edit| edit.edited_at);
+    true
+}
+
+fn finished_at(receipt: &Receipt) -> Option<i64> {
+    let (_, timestamp) = receipt.time_stamp_archive.as_ref()?.last()?;
+    Some(*timestamp)
+}
+
+// Percent-encodes everything but unreserved characters.
+fn encode_component(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+fn decode_component(text: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut rest = text.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edits::ReceiptChange;
+    use crate::Profile;
+
+    // Receipts of sessions finishing at each of `finished`, in that order.
+    fn receipts(finished: &[i64]) -> Vec<Receipt> {
+        let mut profile = Profile::default();
+        finished
+            .iter()
+            .map(|finished_at| {
+                profile
+                    .log_practice(*finished_at, 600, &[(0, 1)])
+                    .unwrap()
+                    .clone()
+            })
+            .collect()
+    }
+
+    fn ids(history: &[Receipt]) -> Vec<String> {
+        history.iter().map(session_id).collect()
+    }
+
+    #[test]
+    fn merges_missing_receipts_in_the_order_they_finished() {
+        let all = receipts(&[3000, 1000, 2000, 4000]);
+        let mut history = vec![all[0].clone(), all[3].clone()];
+        assert_eq!(merge(&mut history, vec![all[2].clone(), all[1].clone()]), 2);
+        assert_eq!(
+            history.iter().map(finished_at).collect::<Vec<_>>(),
+            vec![Some(1000), Some(2000), Some(3000), Some(4000)]
+        );
+    }
+
+    #[test]
+    fn merging_twice_changes_nothing() {
+        let all = receipts(&[1000, 2000, 3000]);
+        let mut history = all[..1].to_vec();
+        assert_eq!(merge(&mut history, all.clone()), 2);
+        let merged = history.clone();
+        assert_eq!(merge(&mut history, all.clone()), 0);
+        assert_eq!(merge(&mut history, merged.clone()), 0);
+        assert_eq!(history, merged);
+    }
+
+    #[test]
+    fn merging_in_either_order_gives_the_same_history() {
+        let all = receipts(&[1000, 2000, 3000, 4000, 2000]);
+        let mut first = vec![all[1].clone(), all[2].clone()];
+        first[0].edit(
+            ReceiptChange::Repetitions {
+                key: 0,
+                repetitions: 4,
+            },
+            5000,
+        );
+        let mut second = vec![
+            all[0].clone(),
+            all[1].clone(),
+            all[3].clone(),
+            all[4].clone(),
+        ];
+        second[1].edit(ReceiptChange::Excluded { excluded: true }, 6000);
+
+        let mut one_way = first.clone();
+        merge(&mut one_way, second.clone());
+        let mut other_way = second.clone();
+        merge(&mut other_way, first.clone());
+        assert_eq!(one_way, other_way);
+        assert_eq!(one_way.len(), 5);
+
+        // Sessions finishing at the same time are ordered by their ids.
+        let tied: Vec<&Receipt> = one_way
+            .iter()
+            .filter(|receipt| finished_at(receipt) == Some(2000))
+            .collect();
+        assert_eq!(tied.len(), 2);
+        assert!(session_id(tied[0]) < session_id(tied[1]));
+    }
+
+    #[test]
+    fn combines_corrections_by_the_time_they_were_made() {
+        let mut receipt = receipts(&[1000]).remove(0);
+        receipt.edit(
+            ReceiptChange::Repetitions {
+                key: 0,
+                repetitions: 5,
+            },
+            100,
+        );
+        let mut ours = receipt.clone();
+        ours.edit(
+            ReceiptChange::Repetitions {
+                key: 0,
+                repetitions: 2,
+            },
+            300,
+        );
+        let mut theirs = receipt.clone();
+        theirs.edit(
+            ReceiptChange::Repetitions {
+                key: 0,
+                repetitions: 3,
+            },
+            200,
+        );
+
+        let mut history = vec![ours];
+        assert_eq!(merge(&mut history, vec![theirs.clone()]), 1);
+        let edited_at: Vec<i64> = history[0].edits.iter().map(|edit| edit.edited_at).collect();
+        assert_eq!(edited_at, vec![100, 200, 300]);
+        // The latest correction wins.
+        let corrected = history[0].corrected();
+        assert_eq!(corrected.key_data_archive.unwrap().0[0].repetitions, 2);
+
+        assert_eq!(merge(&mut history, vec![theirs]), 0);
+    }
+
+    #[test]
+    fn keeps_corrections_made_within_the_same_second() {
+        let receipt = receipts(&[1000]).remove(0);
+        let mut ours = receipt.clone();
+        ours.edit(ReceiptChange::Withdrawn { withdrawn: true }, 100);
+        ours.edit(ReceiptChange::Withdrawn { withdrawn: false }, 100);
+        ours.edit(ReceiptChange::Withdrawn { withdrawn: true }, 100);
+        assert!(ours.is_withdrawn());
+
+        let mut history = vec![receipt];
+        assert_eq!(merge(&mut history, vec![ours.clone()]), 1);
+        assert_eq!(history, vec![ours]);
+        assert!(history[0].is_withdrawn());
+    }
+
+    #[test]
+    fn identifies_receipts_without_session_ids_by_their_timestamps() {
+        let mut old = receipts(&[1000]).remove(0);
+        old.session_id = String::new();
+        assert_eq!(session_id(&old), "400-1000");
+
+        let mut history = vec![old.clone()];
+        assert_eq!(merge(&mut history, vec![old]), 0);
+        assert_eq!(ids(&history), vec!["400-1000".to_string()]);
+    }
+
+    #[test]
+    fn reads_back_the_json_it_writes() {
+        let mut history = receipts(&[1000, 2000]);
+        history[1].edit(ReceiptChange::RemoveKey { key: 0 }, 3000);
+        assert_eq!(from_json(&to_json(&history).unwrap()).unwrap(), history);
+        assert!(from_json("{").is_err());
+    }
+
+    #[test]
+    fn encodes_profile_ids_in_paths() {
+        ["Default", "Ms. Ozerova's class", "a/b", "100% ☺"]
+            .into_iter()
+            .for_each(|name| {
+                let path = receipts_path(name);
+                assert_eq!(path.matches('/').count(), 3, "{path}");
+                assert_eq!(profile_from_path(&path).as_deref(), Some(name));
+            });
+        assert_eq!(profile_from_path("/profiles//receipts"), None);
+        assert_eq!(profile_from_path("/profiles/a/b/receipts"), None);
+        assert_eq!(profile_from_path("/profiles/%ZZ/receipts"), None);
+        assert_eq!(profile_from_path("/other"), None);
+    }
+}
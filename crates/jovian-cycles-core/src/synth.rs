@@ -0,0 +1,419 @@
+use crate::evaluation::{RunDirection, ScaleExercise};
+use crate::scales::ScaleKind;
+
+use serde::{Deserialize, Serialize};
+use std::f32::consts::TAU;
+
+/*
+ * Synthesizer
+ *
+ * Renders notes into a mono buffer of samples between -1 and 1. Rendering only depends on its
+ * inputs, so the same notes always produce the same samples.
+ */
+
+/// Frequency in hertz of a MIDI note, given the frequency of A4 (MIDI note 69).
+pub fn midi_to_frequency(note: u8, a4_frequency: f32) -> f32 {
+    a4_frequency * 2f32.powf((note as f32 - 69.0) / 12.0)
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Waveform {
+    #[default]
+    Sine,
+    Triangle,
+    Square,
+    Sawtooth,
+}
+
+impl Waveform {
+    pub const ALL: [Waveform; 4] = [
+        Waveform::Sine,
+        Waveform::Triangle,
+        Waveform::Square,
+        Waveform::Sawtooth,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Waveform::Sine => "Sine",
+            Waveform::Triangle => "Triangle",
+            Waveform::Square => "Square",
+            Waveform::Sawtooth => "Sawtooth",
+        }
+    }
+
+    /// Value of the waveform at `phase`, measured in cycles.
+    pub fn sample(&self, phase: f32) -> f32 {
+        let phase = phase.fract();
+        match self {
+            Waveform::Sine => (TAU * phase).sin(),
+            Waveform::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+            Waveform::Square => {
+                if phase < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Waveform::Sawtooth => 2.0 * phase - 1.0,
+        }
+    }
+}
+
+/// Attack, decay, sustain and release envelope. Times are in seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct Envelope {
+    pub attack: f32,
+    pub decay: f32,
+    /// Level held after the decay, between 0 and 1.
+    pub sustain: f32,
+    pub release: f32,
+}
+
+impl Default for Envelope {
+    fn default() -> Self {
+        Envelope {
+            attack: 0.01,
+            decay: 0.1,
+            sustain: 0.7,
+            release: 0.15,
+        }
+    }
+}
+
+impl Envelope {
+    /// Level `time` seconds after a note starts, for a note held for `held` seconds.
+    pub fn amplitude(&self, time: f32, held: f32) -> f32 {
+        if time < 0.0 {
+            return 0.0;
+        }
+        if time >= held {
+            let released = time - held;
+            if released >= self.release {
+                return 0.0;
+            }
+            return self.level(held) * (1.0 - released / self.release.max(f32::EPSILON));
+        }
+        self.level(time)
+    }
+
+    fn level(&self, time: f32) -> f32 {
+        if time < self.attack {
+            time / self.attack.max(f32::EPSILON)
+        } else if time < self.attack + self.decay {
+            let decayed = (time - self.attack) / self.decay.max(f32::EPSILON);
+            1.0 - (1.0 - self.sustain) * decayed
+        } else {
+            self.sustain
+        }
+    }
+}
+
+/// A note to render. Times are in seconds and velocity is between 0 and 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SynthNote {
+    pub start: f32,
+    pub duration: f32,
+    pub note: u8,
+    pub velocity: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Synth {
+    pub sample_rate: u32,
+    pub waveform: Waveform,
+    pub envelope: Envelope,
+    /// Overall volume applied to every note.
+    pub gain: f32,
+    pub a4_frequency: f32,
+}
+
+impl Default for Synth {
+    fn default() -> Self {
+        Synth {
+            sample_rate: 44_100,
+            waveform: Waveform::default(),
+            envelope: Envelope::default(),
+            gain: 0.3,
+            a4_frequency: 440.0,
+        }
+    }
+}
+
+impl Synth {
+    /// Renders `notes` into a buffer long enough for the last release to finish.
+    pub fn render(&self, notes: &[SynthNote]) -> Vec<f32> {
+        let sample_rate = self.sample_rate as f32;
+        let end = notes
+            .iter()
+            .map(|note| note.start + note.duration + self.envelope.release)
+            .fold(0.0, f32::max);
+        let mut samples = vec![0.0; (end * sample_rate).ceil() as usize];
+
+        notes.iter().for_each(|note| {
+            let frequency = midi_to_frequency(note.note, self.a4_frequency);
+            let first = (note.start * sample_rate).round().max(0.0) as usize;
+            let length = ((note.duration + self.envelope.release) * sample_rate).ceil() as usize;
+
+            samples
+                .iter_mut()
+                .skip(first)
+                .take(length)
+                .enumerate()
+                .for_each(|(index, sample)| {
+                    let time = index as f32 / sample_rate;
+                    let amplitude = self.envelope.amplitude(time, note.duration);
+                    *sample += self.gain
+                        * note.velocity
+                        * amplitude
+                        * self.waveform.sample(frequency * time);
+                });
+        });
+
+        samples
+            .iter_mut()
+            .for_each(|sample| *sample = sample.clamp(-1.0, 1.0));
+        samples
+    }
+}
+
+/*
+ * Play-Along Patterns
+ */
+
+/// What to play for a key.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Pattern {
+    #[default]
+    Scale,
+    /// Root, third and fifth of every octave, then the top root.
+    Arpeggio,
+}
+
+impl Pattern {
+    pub const ALL: [Pattern; 2] = [Pattern::Scale, Pattern::Arpeggio];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Pattern::Scale => "Scale",
+            Pattern::Arpeggio => "Arpeggio",
+        }
+    }
+}
+
+/// Notes of `pattern` in the key of `root`, starting around middle C, one per beat at `bpm`.
+pub fn pattern_notes(
+    pattern: Pattern,
+    root: usize,
+    kind: ScaleKind,
+    direction: RunDirection,
+    octaves: u8,
+    bpm: f32,
+) -> Vec<SynthNote> {
+    let exercise = ScaleExercise {
+        root: root % 12,
+        kind,
+        direction,
+        octaves,
+    };
+    let middle_root = 60 + (root % 12) as u8;
+    let first_note = match direction {
+        RunDirection::Descending => middle_root + 12 * octaves.max(1),
+        _ => middle_root,
+    };
+    let scale = exercise.expected_notes(first_note);
+
+    // The arpeggio keeps the notes that are the root, third or fifth of the scale.
+    let notes: Vec<u8> = match pattern {
+        Pattern::Scale => scale,
+        Pattern::Arpeggio => scale
+            .iter()
+            .filter(|note| {
+                let degree = (**note as i32 - middle_root as i32).rem_euclid(12) as u8;
+                let intervals = kind.intervals();
+                degree == intervals[0] || degree == intervals[2] || degree == intervals[4]
+            })
+            .copied()
+            .collect(),
+    };
+
+    let beat = 60.0 / bpm.max(1.0);
+    notes
+        .iter()
+        .enumerate()
+        .map(|(index, note)| SynthNote {
+            start: index as f32 * beat,
+            duration: beat * 0.9,
+            note: *note,
+            velocity: 0.8,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENVELOPE: Envelope = Envelope {
+        attack: 0.1,
+        decay: 0.2,
+        sustain: 0.5,
+        release: 0.4,
+    };
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-5
+    }
+
+    #[test]
+    fn envelope_rises_to_its_peak_during_the_attack() {
+        assert_eq!(ENVELOPE.amplitude(-0.01, 1.0), 0.0);
+        assert_eq!(ENVELOPE.amplitude(0.0, 1.0), 0.0);
+        assert!(close(ENVELOPE.amplitude(0.05, 1.0), 0.5));
+        assert!(close(ENVELOPE.amplitude(0.1, 1.0), 1.0));
+    }
+
+    #[test]
+    fn envelope_decays_to_the_sustain_level() {
+        assert!(close(ENVELOPE.amplitude(0.2, 1.0), 0.75));
+        assert!(close(ENVELOPE.amplitude(0.3, 1.0), 0.5));
+        assert!(close(ENVELOPE.amplitude(0.9, 1.0), 0.5));
+    }
+
+    #[test]
+    fn envelope_releases_to_silence() {
+        assert!(close(ENVELOPE.amplitude(1.0, 1.0), 0.5));
+        assert!(close(ENVELOPE.amplitude(1.2, 1.0), 0.25));
+        assert!(ENVELOPE.amplitude(1.399, 1.0) < 0.01);
+        assert!(close(ENVELOPE.amplitude(1.4, 1.0), 0.0));
+        assert_eq!(ENVELOPE.amplitude(10.0, 1.0), 0.0);
+    }
+
+    #[test]
+    fn envelope_releases_from_where_a_short_note_stopped() {
+        // Released halfway through the attack and during the decay.
+        assert!(close(ENVELOPE.amplitude(0.05, 0.05), 0.5));
+        assert!(close(ENVELOPE.amplitude(0.25, 0.05), 0.25));
+        assert!(close(ENVELOPE.amplitude(0.2, 0.2), 0.75));
+        assert!(close(ENVELOPE.amplitude(0.4, 0.2), 0.375));
+        assert!(close(ENVELOPE.amplitude(0.45, 0.05), 0.0));
+    }
+
+    #[test]
+    fn envelope_handles_instant_stages() {
+        let envelope = Envelope {
+            attack: 0.0,
+            decay: 0.0,
+            sustain: 0.8,
+            release: 0.0,
+        };
+        assert!(close(envelope.amplitude(0.0, 1.0), 0.8));
+        assert!(close(envelope.amplitude(0.5, 1.0), 0.8));
+        assert_eq!(envelope.amplitude(1.0, 1.0), 0.0);
+    }
+
+    #[test]
+    fn renders_until_the_last_release_ends() {
+        let synth = Synth {
+            sample_rate: 1000,
+            envelope: ENVELOPE,
+            ..Synth::default()
+        };
+        let note = SynthNote {
+            start: 0.5,
+            duration: 1.0,
+            note: 69,
+            velocity: 1.0,
+        };
+        let samples = synth.render(&[note]);
+        assert_eq!(samples.len(), 1900);
+        assert!(samples[..500].iter().all(|sample| *sample == 0.0));
+        assert!(samples.iter().all(|sample| sample.abs() <= synth.gain));
+        assert!(samples[1000..1500].iter().any(|sample| sample.abs() > 0.1));
+        assert_eq!(synth.render(&[note]), samples);
+        assert!(synth.render(&[]).is_empty());
+    }
+
+    #[test]
+    fn converts_notes_to_frequencies() {
+        assert!(close(midi_to_frequency(69, 440.0), 440.0));
+        assert!(close(midi_to_frequency(81, 440.0), 880.0));
+        assert!((midi_to_frequency(60, 440.0) - 261.6256).abs() < 1e-3);
+        assert!(close(midi_to_frequency(69, 432.0), 432.0));
+    }
+
+    fn pitches(notes: &[SynthNote]) -> Vec<u8> {
+        notes.iter().map(|note| note.note).collect()
+    }
+
+    #[test]
+    fn plays_scales_in_every_direction() {
+        let scale = |direction, octaves| {
+            pattern_notes(
+                Pattern::Scale,
+                2,
+                ScaleKind::Major,
+                direction,
+                octaves,
+                120.0,
+            )
+        };
+        let d_major = vec![62, 64, 66, 67, 69, 71, 73, 74];
+        assert_eq!(pitches(&scale(RunDirection::Ascending, 1)), d_major);
+        assert_eq!(
+            pitches(&scale(RunDirection::Descending, 1)),
+            d_major.iter().rev().copied().collect::<Vec<u8>>()
+        );
+        let up_and_down = pitches(&scale(RunDirection::UpAndDown, 1));
+        assert_eq!(up_and_down.len(), 15);
+        assert_eq!(
+            (up_and_down[0], up_and_down[7], up_and_down[14]),
+            (62, 74, 62)
+        );
+
+        let two_octaves = pitches(&scale(RunDirection::Descending, 2));
+        assert_eq!(two_octaves.len(), 15);
+        assert_eq!((two_octaves[0], two_octaves[14]), (86, 62));
+    }
+
+    #[test]
+    fn plays_arpeggios_in_every_direction() {
+        let arpeggio = |direction, octaves| {
+            pitches(&pattern_notes(
+                Pattern::Arpeggio,
+                9,
+                ScaleKind::NaturalMinor,
+                direction,
+                octaves,
+                120.0,
+            ))
+        };
+        assert_eq!(arpeggio(RunDirection::Ascending, 1), vec![69, 72, 76, 81]);
+        assert_eq!(arpeggio(RunDirection::Descending, 1), vec![81, 76, 72, 69]);
+        assert_eq!(
+            arpeggio(RunDirection::UpAndDown, 1),
+            vec![69, 72, 76, 81, 76, 72, 69]
+        );
+        assert_eq!(
+            arpeggio(RunDirection::Ascending, 2),
+            vec![69, 72, 76, 81, 84, 88, 93]
+        );
+    }
+
+    #[test]
+    fn plays_one_note_per_beat() {
+        let notes = pattern_notes(
+            Pattern::Scale,
+            12,
+            ScaleKind::Major,
+            RunDirection::Ascending,
+            1,
+            120.0,
+        );
+        assert_eq!(notes[0].note, 60);
+        notes.iter().enumerate().for_each(|(index, note)| {
+            assert!(close(note.start, index as f32 * 0.5));
+            assert!(close(note.duration, 0.45));
+        });
+    }
+}
@@ -0,0 +1,775 @@
+use crate::clock::Clock;
+use crate::models::{KeysMap, PracticeSessionData, Receipt};
+use crate::sync::session_id;
+use crate::undo::{SessionSnapshot, UndoEntry, UndoStack};
+use crate::utils::match_states;
+
+use color_eyre::eyre::{eyre, Report};
+use log::{debug, error};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/*
+ * Type State
+ */
+
+/// States of the practice session state machine.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum SessionStates {
+    /// No session is running.
+    Waiting,
+    /// A new key is being selected.
+    RequestingNewKey,
+    /// The current key is being dropped for a new one.
+    SkippingKey,
+    /// The player is practicing the current key.
+    Working,
+    /// The session is paused.
+    Resting,
+    /// The session is ending and its receipt being written.
+    Finishing,
+}
+
+/// Session actions that can be requested by a front-end or a replay script.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum SessionCommand {
+    /// Selects a new key, see [`PracticeSessionState::request_new_key`].
+    NewKey,
+    /// Drops the current key for a new one, see [`PracticeSessionState::skip_key`].
+    Skip,
+    /// Starts resting, see [`PracticeSessionState::pause`].
+    Pause,
+    /// Goes back to working, see [`PracticeSessionState::resume`].
+    Resume,
+    /// Pauses while working and resumes otherwise.
+    TogglePause,
+    /// Ends the session, see [`PracticeSessionState::finish`].
+    Finish,
+    /// Counts a repetition of the current key, see
+    /// [`PracticeSessionState::complete_repetition`].
+    CompleteRepetition,
+    /// Starts resting as of the given Unix timestamp (in seconds), see
+    /// [`PracticeSessionState::pause_since`].
+    PauseSince(i64),
+}
+
+impl SessionCommand {
+    /// Name of the command as shown to the player.
+    pub fn label(&self) -> &'static str {
+        match self {
+            SessionCommand::NewKey => "Request new key",
+            SessionCommand::Skip => "Skip key",
+            SessionCommand::Pause => "Pause",
+            SessionCommand::Resume => "Resume",
+            SessionCommand::TogglePause => "Pause or resume",
+            SessionCommand::Finish => "End session",
+            SessionCommand::CompleteRepetition => "Count repetition",
+            SessionCommand::PauseSince(_) => "Pause while away",
+        }
+    }
+}
+
+impl FromStr for SessionCommand {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "n" | "new" => Ok(SessionCommand::NewKey),
+            "s" | "skip" => Ok(SessionCommand::Skip),
+            "p" | "pause" => Ok(SessionCommand::Pause),
+            "r" | "resume" => Ok(SessionCommand::Resume),
+            "t" | "toggle" => Ok(SessionCommand::TogglePause),
+            "q" | "finish" => Ok(SessionCommand::Finish),
+            "c" | "repetition" => Ok(SessionCommand::CompleteRepetition),
+            _ => Err(eyre!("Unknown session command '{}'.", s)),
+        }
+    }
+}
+
+/// A practice session: the current state of the state machine and the data recorded so far.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct PracticeSessionState {
+    /// Names of the twelve keys, from C upwards.
+    #[serde(skip)]
+    pub note_name_list: Vec<String>,
+    /// Current state of the state machine.
+    #[serde(skip)]
+    pub session_state: SessionStates,
+    /// Everything recorded in the session so far.
+    #[serde(skip)]
+    pub session_data: PracticeSessionData,
+    /// Receipts of sessions finished since [`Self::take_finished_receipts`] was last called.
+    #[serde(skip)]
+    pub finished_receipts: Vec<Receipt>,
+    /// Receipts handed out earlier whose session was taken back with [`Self::undo`], since
+    /// [`Self::take_withdrawn_receipts`] was last called.
+    #[serde(skip)]
+    pub withdrawn_receipts: Vec<Receipt>,
+    /// Actions run with [`Self::apply`], for [`Self::undo`] and [`Self::redo`].
+    #[serde(skip)]
+    pub undo_stack: UndoStack,
+}
+
+/// A running session saved so it can be picked up again after the app was closed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionCheckpoint {
+    /// State the session was in.
+    pub session_state: SessionStates,
+    /// Everything the session had recorded.
+    pub session_data: PracticeSessionData,
+    /// Unix timestamp (in seconds) at which the checkpoint was taken.
+    pub saved_at: i64,
+}
+
+/*
+* Transition States
+*
+* New Key -> Working
+* New Key -> Resting
+* Working -> Resting
+*
+* All -> Finishing
+*/
+
+impl Default for PracticeSessionState {
+    fn default() -> Self {
+        PracticeSessionState {
+            note_name_list: vec![
+                "C".to_owned(),
+                "C#".to_owned(),
+                "D".to_owned(),
+                "Eb".to_owned(),
+                "E".to_owned(),
+                "F".to_owned(),
+                "F#".to_owned(),
+                "G".to_owned(),
+                "Ab".to_owned(),
+                "A".to_owned(),
+                "Bb".to_owned(),
+                "B".to_owned(),
+            ],
+            session_state: SessionStates::Waiting,
+            session_data: PracticeSessionData::new(),
+            finished_receipts: Vec::new(),
+            withdrawn_receipts: Vec::new(),
+            undo_stack: UndoStack::default(),
+        }
+    }
+}
+
+#[allow(clippy::wrong_self_convention)]
+impl PracticeSessionState {
+    /// Enters [`SessionStates::RequestingNewKey`], recording when it started.
+    pub fn to_requesting_new_key(&mut self) {
+        self.session_state = SessionStates::RequestingNewKey;
+        self.session_data
+            .set_timestamp("Requesting New Key".to_string());
+        self.session_data.push_timestamp();
+    }
+
+    /// Selects the next key, see [`PracticeSessionData::get_new_key`].
+    pub fn requesting_new_key(&mut self) {
+        self.session_data.get_new_key();
+    }
+
+    /// Enters [`SessionStates::SkippingKey`], recording when it started.
+    pub fn to_skipping_key(&mut self) {
+        self.session_state = SessionStates::SkippingKey;
+        self.session_data.set_timestamp("Skipping Key".to_string());
+        self.session_data.push_timestamp();
+    }
+
+    /// Takes back the repetition of the skipped key and records the skip.
+    pub fn skipping_key(&mut self) {
+        // A skipped key was selected but never practiced, so it does not count as a repetition.
+        self.decrement_key();
+        self.session_data.record_skip();
+    }
+
+    /// Enters [`SessionStates::Waiting`], once a session has ended.
+    pub fn to_waiting(&mut self) {
+        self.session_state = SessionStates::Waiting;
+    }
+
+    /// Enters [`SessionStates::Working`], recording when it started.
+    pub fn to_working(&mut self) {
+        self.session_state = SessionStates::Working;
+        self.session_data.set_timestamp("Working".to_string());
+        self.session_data.push_timestamp();
+    }
+
+    // (Working) State functions
+    fn increment_key(&mut self) {
+        match self.session_data.clone().increment_key_repetition() {
+            Ok(data) => {
+                self.session_data = data;
+            }
+            Err(e) => {
+                error!("{:#?}", e);
+                self.session_state = SessionStates::RequestingNewKey;
+            }
+        };
+    }
+
+    /// Removes a repetition from the current key, going back to requesting a key if there is
+    /// none.
+    pub fn decrement_key(&mut self) {
+        match self.session_data.clone().decrement_key_repetition() {
+            Ok(data) => {
+                self.session_data = data;
+            }
+            Err(e) => {
+                error!("{:#?}", e);
+                self.session_state = SessionStates::RequestingNewKey;
+            }
+        };
+    }
+
+    /// Counts a repetition of a newly selected key and brings the receipt up to date.
+    pub fn working(&mut self) {
+        match self.session_data.clone().practice_session_history {
+            None => {}
+            Some(history) => {
+                let length = history.len();
+                let previous_state_time_code =
+                    length.checked_sub(2).and_then(|index| history.get(index));
+
+                debug!("{:#?}", length);
+                debug!("{:#?}", previous_state_time_code);
+
+                if previous_state_time_code.is_some_and(|(name, _)| name == "Requesting New Key") {
+                    self.increment_key();
+                };
+            }
+        };
+
+        self.session_data.receipt = Some(self.session_data.construct_receipt());
+    }
+
+    /// Enters [`SessionStates::Resting`], recording when it started.
+    pub fn to_resting(&mut self) {
+        self.session_state = SessionStates::Resting;
+        self.session_data.set_timestamp("Resting".to_string());
+        self.session_data.push_timestamp();
+    }
+
+    /// Brings the receipt up to date as the rest starts.
+    pub fn resting(&mut self) {
+        // Work stopped when the rest started, so the receipt is up to date until it ends.
+        self.session_data.receipt = Some(self.session_data.construct_receipt());
+    }
+
+    /// Enters [`SessionStates::Finishing`], recording when it started.
+    pub fn to_finishing(&mut self) {
+        self.session_state = SessionStates::Finishing;
+        self.session_data.set_timestamp("Finishing".to_string());
+        self.session_data.push_timestamp();
+    }
+
+    /// Writes the receipt of the session and hands it out with the finished receipts.
+    pub fn finishing(&mut self) {
+        let receipt = self.session_data.construct_receipt();
+        self.finished_receipts.push(receipt.clone());
+        self.session_data.receipt = Some(receipt);
+    }
+}
+
+/*
+ * Session Actions
+ *
+ * Each action chains the transition and state functions the same way for every front-end, so
+ * the session can be driven without an egui context.
+ */
+impl PracticeSessionState {
+    /// Whether [`Self::skip_key`] does anything in the current state.
+    pub fn can_skip(&self) -> bool {
+        matches!(
+            self.session_state,
+            SessionStates::Working | SessionStates::Resting
+        )
+    }
+
+    /// Whether [`Self::pause`] does anything in the current state.
+    pub fn can_pause(&self) -> bool {
+        matches!(self.session_state, SessionStates::Working)
+    }
+
+    /// Whether [`Self::resume`] does anything in the current state.
+    pub fn can_resume(&self) -> bool {
+        matches!(
+            self.session_state,
+            SessionStates::RequestingNewKey | SessionStates::Resting
+        )
+    }
+
+    /// Selects a new key and starts working on it.
+    pub fn request_new_key(&mut self) {
+        self.to_requesting_new_key();
+        match_states(self);
+
+        self.to_working();
+        match_states(self);
+    }
+
+    /// Drops the current key and starts working on a new one.
+    pub fn skip_key(&mut self) {
+        if !self.can_skip() {
+            debug!("Skipping is not currently possible in this state.");
+            return;
+        }
+
+        self.to_skipping_key();
+        match_states(self);
+
+        self.request_new_key();
+    }
+
+    /// Stops working and starts resting.
+    pub fn pause(&mut self) {
+        if !self.can_pause() {
+            debug!("Pausing is not currently possible in this state.");
+            return;
+        }
+
+        self.to_resting();
+        match_states(self);
+    }
+
+    /// Stops working and starts resting as of `timestamp`, e.g. once it turns out the player left
+    /// some time ago.
+    ///
+    /// The rest starts no earlier than the current stretch of work and no later than now.
+    pub fn pause_since(&mut self, timestamp: i64) {
+        if !self.can_pause() {
+            debug!("Pausing is not currently possible in this state.");
+            return;
+        }
+
+        let data = &mut self.session_data;
+        let working_since = data
+            .start_timestamp()
+            .map_or(timestamp, |(_, started)| *started);
+        let timestamp = timestamp.max(working_since).min(data.clock.now());
+
+        self.session_state = SessionStates::Resting;
+        self.session_data
+            .set_timestamp_at("Resting".to_string(), timestamp);
+        self.session_data.push_timestamp();
+        match_states(self);
+    }
+
+    /// Takes back the current pause, counting the time since it started as work.
+    ///
+    /// Does nothing unless the session is resting after working on a key.
+    pub fn cancel_pause(&mut self) {
+        if !matches!(self.session_state, SessionStates::Resting) || !self.session_data.cancel_rest()
+        {
+            debug!("There is no pause to take back.");
+            return;
+        }
+
+        self.session_state = SessionStates::Working;
+        self.session_data.receipt = Some(self.session_data.construct_receipt());
+    }
+
+    /// Goes back to working on the current key.
+    pub fn resume(&mut self) {
+        if !self.can_resume() {
+            debug!("Resuming is not currently possible in this state.");
+            return;
+        }
+
+        self.to_working();
+        match_states(self);
+    }
+
+    /// Ends the session, leaving its receipt in [`PracticeSessionData::receipt`].
+    pub fn finish(&mut self) {
+        self.to_finishing();
+        match_states(self);
+
+        self.to_waiting();
+        match_states(self);
+    }
+
+    /// Counts a repetition of the current key, e.g. after a correctly played run.
+    ///
+    /// Does nothing unless the session is working on a key.
+    pub fn complete_repetition(&mut self) {
+        if !matches!(self.session_state, SessionStates::Working) {
+            debug!("Repetitions can only be completed while working.");
+            return;
+        }
+
+        self.increment_key();
+        self.session_data.receipt = Some(self.session_data.construct_receipt());
+    }
+
+    /// Snapshot of the running session, `None` while no session is running.
+    pub fn checkpoint(&self) -> Option<SessionCheckpoint> {
+        if matches!(self.session_state, SessionStates::Waiting) {
+            return None;
+        }
+
+        Some(SessionCheckpoint {
+            session_state: self.session_state.clone(),
+            session_data: self.session_data.clone(),
+            saved_at: self.session_data.clock.now(),
+        })
+    }
+
+    /// Picks the session of `checkpoint` up again.
+    ///
+    /// A session that was working is paused as of the checkpoint, so the time the app was closed
+    /// counts as rest rather than work. A seeded session goes on selecting the keys it would have
+    /// selected without the interruption.
+    pub fn resume_from(&mut self, checkpoint: SessionCheckpoint) {
+        let saved_at = checkpoint.saved_at;
+        self.restore(checkpoint.session_state, checkpoint.session_data);
+
+        self.pause_since(saved_at);
+    }
+
+    /// Ends the session of `checkpoint` as of the time it was taken, handing its receipt out with
+    /// the other finished sessions.
+    pub fn finish_from(&mut self, checkpoint: SessionCheckpoint) {
+        let clock = self.session_data.clock;
+        let saved_at = checkpoint.saved_at;
+        self.restore(checkpoint.session_state, checkpoint.session_data);
+
+        self.session_data.clock = Clock::Manual(saved_at);
+        self.finish();
+        self.session_data.clock = clock;
+    }
+
+    // Takes over `session_state` and `session_data`, keeping the clock of the session.
+    fn restore(&mut self, session_state: SessionStates, session_data: PracticeSessionData) {
+        let clock = self.session_data.clock;
+        self.session_state = session_state;
+        self.session_data = session_data;
+        self.session_data.clock = clock;
+    }
+
+    fn keep_key_settings(&mut self, keys: &KeysMap) {
+        self.session_data
+            .all_keys_map
+            .0
+            .iter_mut()
+            .zip(keys.0.iter())
+            .for_each(|(key, kept)| {
+                key.weight = kept.weight;
+                key.pinned = kept.pinned;
+            });
+    }
+
+    fn snapshot(&self) -> SessionSnapshot {
+        SessionSnapshot {
+            session_state: self.session_state.clone(),
+            session_data: self.session_data.clone(),
+        }
+    }
+
+    /// Hands out the receipts of the sessions finished since the last call.
+    pub fn take_finished_receipts(&mut self) -> Vec<Receipt> {
+        std::mem::take(&mut self.finished_receipts)
+    }
+
+    /// Hands out the receipts taken back by [`Self::undo`] since the last call.
+    pub fn take_withdrawn_receipts(&mut self) -> Vec<Receipt> {
+        std::mem::take(&mut self.withdrawn_receipts)
+    }
+
+    /// Runs the session action matching `command`, so that it can be undone.
+    ///
+    /// Actions that change nothing are not recorded.
+    pub fn apply(&mut self, command: SessionCommand) {
+        let before = self.snapshot();
+        let finished = self.finished_receipts.len();
+
+        self.run(command);
+
+        let after = self.snapshot();
+        if after != before {
+            self.undo_stack.record(UndoEntry {
+                command,
+                before,
+                after,
+                receipts: self.finished_receipts[finished..].to_vec(),
+            });
+        }
+    }
+
+    /// Takes back the last action run with [`Self::apply`], putting the session back exactly as
+    /// it was, and returns it. Weights and pins of keys are settings rather than part of the
+    /// action, and stay as they are.
+    ///
+    /// Receipts of a session the action finished are withdrawn.
+    ///
+    /// ```
+    /// use jovian_cycles_core::{PracticeSessionState, SessionCommand};
+    ///
+    /// let mut state = PracticeSessionState::default();
+    /// state.apply(SessionCommand::NewKey);
+    /// let (session_state, session_data) = (state.session_state.clone(), state.session_data.clone());
+    ///
+    /// state.apply(SessionCommand::Finish);
+    /// assert_eq!(state.take_finished_receipts().len(), 1);
+    ///
+    /// assert_eq!(state.undo(), Some(SessionCommand::Finish));
+    /// assert_eq!(state.session_state, session_state);
+    /// assert_eq!(state.session_data, session_data);
+    /// assert_eq!(state.take_withdrawn_receipts().len(), 1);
+    ///
+    /// assert_eq!(state.redo(), Some(SessionCommand::Finish));
+    /// assert_eq!(state.take_finished_receipts().len(), 1);
+    /// ```
+    pub fn undo(&mut self) -> Option<SessionCommand> {
+        let entry = self.undo_stack.undo()?;
+        let keys = self.session_data.all_keys_map.clone();
+        self.restore(entry.before.session_state, entry.before.session_data);
+        self.keep_key_settings(&keys);
+
+        entry.receipts.into_iter().for_each(|receipt| {
+            let id = session_id(&receipt);
+            match self
+                .finished_receipts
+                .iter()
+                .position(|finished| session_id(finished) == id)
+            {
+                Some(index) => {
+                    self.finished_receipts.remove(index);
+                }
+                None => self.withdrawn_receipts.push(receipt),
+            }
+        });
+
+        Some(entry.command)
+    }
+
+    /// Runs the last undone action again, leaving the session exactly as the action first left
+    /// it, apart from weights and pins of keys, and returns it.
+    ///
+    /// ```
+    /// use jovian_cycles_core::{PracticeSessionState, SessionCommand};
+    ///
+    /// let mut state = PracticeSessionState::default();
+    /// state.apply(SessionCommand::NewKey);
+    /// state.apply(SessionCommand::Skip);
+    /// let skipped = state.session_data.clone();
+    ///
+    /// // Resuming while working changes nothing and is not recorded.
+    /// state.apply(SessionCommand::Resume);
+    /// assert_eq!(state.undo(), Some(SessionCommand::Skip));
+    /// assert_eq!(state.undo(), Some(SessionCommand::NewKey));
+    /// assert_eq!(state.session_data.current_key_data, None);
+    /// assert_eq!(state.undo(), None);
+    ///
+    /// // The same keys come back rather than new random ones.
+    /// state.redo();
+    /// state.redo();
+    /// assert_eq!(state.session_data, skipped);
+    /// ```
+    pub fn redo(&mut self) -> Option<SessionCommand> {
+        let entry = self.undo_stack.redo()?;
+        let keys = self.session_data.all_keys_map.clone();
+        self.restore(entry.after.session_state, entry.after.session_data);
+        self.keep_key_settings(&keys);
+
+        entry.receipts.into_iter().for_each(|receipt| {
+            let id = session_id(&receipt);
+            match self
+                .withdrawn_receipts
+                .iter()
+                .position(|withdrawn| session_id(withdrawn) == id)
+            {
+                Some(index) => {
+                    self.withdrawn_receipts.remove(index);
+                }
+                None => self.finished_receipts.push(receipt),
+            }
+        });
+
+        Some(entry.command)
+    }
+
+    fn run(&mut self, command: SessionCommand) {
+        match command {
+            SessionCommand::NewKey => self.request_new_key(),
+            SessionCommand::Skip => self.skip_key(),
+            SessionCommand::Pause => self.pause(),
+            SessionCommand::Resume => self.resume(),
+            SessionCommand::TogglePause => {
+                if self.can_pause() {
+                    self.pause();
+                } else {
+                    self.resume();
+                }
+            }
+            SessionCommand::Finish => self.finish(),
+            SessionCommand::CompleteRepetition => self.complete_repetition(),
+            SessionCommand::PauseSince(timestamp) => self.pause_since(timestamp),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A seeded session on a manual clock.
+    fn seeded(seed: u64) -> PracticeSessionState {
+        let mut state = PracticeSessionState::default();
+        state.session_data.clock = Clock::Manual(0);
+        state.session_data.set_seed(Some(seed));
+        state
+    }
+
+    #[test]
+    fn resumed_sessions_keep_their_key_sequence() {
+        (0..20).for_each(|seed| {
+            let mut uninterrupted = seeded(seed);
+            (0..5).for_each(|_| uninterrupted.apply(SessionCommand::NewKey));
+
+            // Saved after three keys and picked up again by another copy of the app.
+            let mut saved = seeded(seed);
+            (0..3).for_each(|_| saved.apply(SessionCommand::NewKey));
+            let checkpoint = serde_json::to_string(&saved.checkpoint().unwrap()).unwrap();
+            let mut resumed = PracticeSessionState::default();
+            resumed.session_data.clock = Clock::Manual(0);
+            resumed.resume_from(serde_json::from_str(&checkpoint).unwrap());
+            assert_eq!(resumed.session_data.seed(), Some(seed));
+            resumed.apply(SessionCommand::Resume);
+            (0..2).for_each(|_| resumed.apply(SessionCommand::NewKey));
+
+            assert_eq!(
+                resumed.session_data.key_history,
+                uninterrupted.session_data.key_history
+            );
+        });
+    }
+
+    #[test]
+    fn working_without_an_earlier_state_counts_nothing() {
+        let mut state = seeded(7);
+        state.session_data.get_new_key();
+        state.to_working();
+        state.working();
+        let key = state.session_data.current_key_data.unwrap();
+        assert_eq!(key.repetitions, 0);
+        assert!(state.session_data.receipt.is_some());
+    }
+
+    // Applies `command` at `at` and checks that undoing it brings back the keys, the current key
+    // and the timestamps exactly as they were.
+    fn undoes_exactly(state: &mut PracticeSessionState, command: SessionCommand, at: i64) {
+        state.session_data.clock.set(at);
+        let session_state = state.session_state.clone();
+        let keys = state.session_data.all_keys_map.clone();
+        let current = state.session_data.current_key_data;
+        let timestamps = state.session_data.practice_session_history.clone();
+        let data = state.session_data.clone();
+
+        state.apply(command);
+        assert_ne!(state.session_data, data, "{command:?} did nothing");
+        assert_eq!(state.undo(), Some(command));
+
+        assert_eq!(state.session_state, session_state, "{command:?}");
+        assert_eq!(state.session_data.all_keys_map, keys, "{command:?}");
+        assert_eq!(state.session_data.current_key_data, current, "{command:?}");
+        assert_eq!(
+            state.session_data.practice_session_history, timestamps,
+            "{command:?}"
+        );
+
+        // Run it for real so the next command starts from there.
+        state.redo();
+    }
+
+    #[test]
+    fn undo_restores_the_state_before_every_command() {
+        let mut state = seeded(7);
+        undoes_exactly(&mut state, SessionCommand::NewKey, 10);
+        undoes_exactly(&mut state, SessionCommand::Skip, 20);
+        undoes_exactly(&mut state, SessionCommand::NewKey, 30);
+        undoes_exactly(&mut state, SessionCommand::CompleteRepetition, 35);
+        undoes_exactly(&mut state, SessionCommand::Pause, 40);
+        undoes_exactly(&mut state, SessionCommand::Resume, 50);
+        undoes_exactly(&mut state, SessionCommand::PauseSince(52), 55);
+        undoes_exactly(&mut state, SessionCommand::TogglePause, 60);
+        undoes_exactly(&mut state, SessionCommand::TogglePause, 70);
+        undoes_exactly(&mut state, SessionCommand::Finish, 80);
+
+        // Finishing during a rest too.
+        let mut state = seeded(7);
+        undoes_exactly(&mut state, SessionCommand::NewKey, 10);
+        undoes_exactly(&mut state, SessionCommand::Pause, 20);
+        undoes_exactly(&mut state, SessionCommand::Finish, 30);
+    }
+
+    #[test]
+    fn undoing_an_idle_pause_keeps_the_repetitions_before_it() {
+        let mut state = seeded(7);
+        state.apply(SessionCommand::NewKey);
+        state.session_data.clock.set(10);
+        state.apply(SessionCommand::CompleteRepetition);
+        state.apply(SessionCommand::CompleteRepetition);
+        state.session_data.clock.set(300);
+        state.apply(SessionCommand::PauseSince(20));
+        assert_eq!(state.session_state, SessionStates::Resting);
+
+        // Selecting the key counted its first repetition.
+        assert_eq!(state.undo(), Some(SessionCommand::PauseSince(20)));
+        assert_eq!(state.session_data.current_key_data.unwrap().repetitions, 3);
+        assert_eq!(state.undo(), Some(SessionCommand::CompleteRepetition));
+        assert_eq!(state.session_data.current_key_data.unwrap().repetitions, 2);
+    }
+
+    #[test]
+    fn undoing_a_skip_takes_its_bonus_back() {
+        let mut state = seeded(7);
+        state.session_data.skip_bonus = 50;
+        state.apply(SessionCommand::NewKey);
+        let skipped = state.session_data.current_key_data.unwrap().nid;
+        let weights = |state: &PracticeSessionState| {
+            let keys = &state.session_data.all_keys_map.0;
+            keys.iter().map(|key| key.weight).collect::<Vec<_>>()
+        };
+        let before = weights(&state);
+        let selected = state.session_data.skipped_keys();
+
+        state.apply(SessionCommand::Skip);
+        assert_eq!(weights(&state), before);
+        assert_eq!(
+            state.session_data.skipped_keys()[skipped].weight,
+            selected[skipped].weight + 50
+        );
+
+        state.undo();
+        assert_eq!(weights(&state), before);
+        assert_eq!(state.session_data.skipped_keys(), selected);
+        state.redo();
+        state.undo();
+        assert_eq!(state.session_data.skipped_keys(), selected);
+    }
+
+    #[test]
+    fn undoing_a_finish_withdraws_its_receipt() {
+        let mut state = seeded(7);
+        state.apply(SessionCommand::NewKey);
+        state.apply(SessionCommand::Finish);
+        let receipt = state.take_finished_receipts().remove(0);
+
+        state.undo();
+        assert_eq!(state.take_withdrawn_receipts(), vec![receipt.clone()]);
+        state.redo();
+        assert_eq!(state.take_finished_receipts(), vec![receipt]);
+
+        // Undone before anyone took the receipt, it is simply never handed out.
+        state.undo();
+        state.redo();
+        state.undo();
+        assert!(state.take_finished_receipts().is_empty());
+        assert_eq!(state.take_withdrawn_receipts().len(), 1);
+    }
+}
@@ -0,0 +1,130 @@
+use crate::models::{PracticeSessionData, Receipt};
+use crate::transitions::{SessionCommand, SessionStates};
+
+/*
+ * Undo
+ *
+ * Every session action run through `PracticeSessionState::apply` is recorded together with the
+ * session as it was before and after the action. Undoing puts the session from before back and
+ * redoing the session from after, so a key drawn at random comes back exactly as it was instead
+ * of being drawn again.
+ */
+
+// Actions kept for undoing, the oldest are forgotten first.
+const UNDO_LIMIT: usize = 100;
+
+/// State machine state and data of a session at one point in time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionSnapshot {
+    pub session_state: SessionStates,
+    pub session_data: PracticeSessionData,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct UndoEntry {
+    pub command: SessionCommand,
+    pub before: SessionSnapshot,
+    pub after: SessionSnapshot,
+    /// Receipts of the sessions the action finished.
+    pub receipts: Vec<Receipt>,
+}
+
+/// Session actions that can be undone, and the undone actions that can be redone.
+#[derive(Debug, Clone, Default)]
+pub struct UndoStack {
+    done: Vec<UndoEntry>,
+    undone: Vec<UndoEntry>,
+}
+
+impl UndoStack {
+    /// Action that [`crate::PracticeSessionState::undo`] takes back next.
+    pub fn next_undo(&self) -> Option<SessionCommand> {
+        self.done.last().map(|entry| entry.command)
+    }
+
+    /// Action that [`crate::PracticeSessionState::redo`] runs again next.
+    pub fn next_redo(&self) -> Option<SessionCommand> {
+        self.undone.last().map(|entry| entry.command)
+    }
+
+    /// Forgets every recorded action.
+    pub fn clear(&mut self) {
+        self.done.clear();
+        self.undone.clear();
+    }
+
+    // A new action makes the undone ones impossible to redo.
+    pub(crate) fn record(&mut self, entry: UndoEntry) {
+        self.undone.clear();
+        if self.done.len() == UNDO_LIMIT {
+            self.done.remove(0);
+        }
+        self.done.push(entry);
+    }
+
+    pub(crate) fn undo(&mut self) -> Option<UndoEntry> {
+        let entry = self.done.pop()?;
+        self.undone.push(entry.clone());
+        Some(entry)
+    }
+
+    pub(crate) fn redo(&mut self) -> Option<UndoEntry> {
+        let entry = self.undone.pop()?;
+        self.done.push(entry.clone());
+        Some(entry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(command: SessionCommand) -> UndoEntry {
+        let snapshot = SessionSnapshot {
+            session_state: SessionStates::Waiting,
+            session_data: PracticeSessionData::default(),
+        };
+        UndoEntry {
+            command,
+            before: snapshot.clone(),
+            after: snapshot,
+            receipts: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn forgets_the_oldest_action_past_the_limit() {
+        let mut stack = UndoStack::default();
+        stack.record(entry(SessionCommand::Skip));
+        (1..UNDO_LIMIT).for_each(|_| stack.record(entry(SessionCommand::NewKey)));
+        assert_eq!(stack.done.first().unwrap().command, SessionCommand::Skip);
+
+        stack.record(entry(SessionCommand::Finish));
+        assert_eq!(stack.done.len(), UNDO_LIMIT);
+        assert_eq!(stack.done.first().unwrap().command, SessionCommand::NewKey);
+        assert_eq!(stack.next_undo(), Some(SessionCommand::Finish));
+
+        while stack.undo().is_some() {}
+        assert_eq!(stack.undone.len(), UNDO_LIMIT);
+        assert!(stack
+            .undone
+            .iter()
+            .all(|entry| entry.command != SessionCommand::Skip));
+    }
+
+    #[test]
+    fn a_new_action_cannot_be_followed_by_redoing_old_ones() {
+        let mut stack = UndoStack::default();
+        stack.record(entry(SessionCommand::NewKey));
+        stack.record(entry(SessionCommand::Skip));
+        stack.undo();
+        assert_eq!(stack.next_redo(), Some(SessionCommand::Skip));
+
+        stack.record(entry(SessionCommand::Pause));
+        assert_eq!(stack.next_redo(), None);
+        assert!(stack.redo().is_none());
+        assert_eq!(stack.next_undo(), Some(SessionCommand::Pause));
+        stack.undo();
+        assert_eq!(stack.next_undo(), Some(SessionCommand::NewKey));
+    }
+}
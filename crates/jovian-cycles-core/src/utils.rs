@@ -2,6 +2,7 @@ use crate::transitions::{PracticeSessionState, SessionStates};
 
 use log::{debug, error, info};
 
+/// Runs the state function for the current state of the session.
 pub fn match_states(state: &mut PracticeSessionState) {
     match state.session_state {
         SessionStates::RequestingNewKey => {
@@ -16,11 +17,7 @@ pub fn match_states(state: &mut PracticeSessionState) {
             state.working();
         }
         SessionStates::Resting => {
-            // TODO: (ozerova) - Implement resting features.
-            //                   1) Stop working timer (drop RESTING timestamp)
-            //                   2) Wait for input to transition state to working, requesting
-            //                      new key, or finishing
-            //                   3) Transition state
+            state.resting();
         }
         SessionStates::Finishing => {
             state.finishing();
@@ -0,0 +1,99 @@
+use color_eyre::eyre::eyre;
+use color_eyre::Result;
+
+/*
+ * WAV Files
+ *
+ * Reads and writes uncompressed 16-bit PCM WAV files, enough to save rendered examples and to
+ * feed recordings to the pitch detector.
+ */
+
+/// Serializes mono `samples` between -1 and 1 into the bytes of a 16-bit PCM WAV file.
+pub fn write_wav(samples: &[f32], sample_rate: u32) -> Vec<u8> {
+    let data_length = 2 * samples.len() as u32;
+    let mut bytes: Vec<u8> = Vec::with_capacity(44 + data_length as usize);
+
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_length).to_le_bytes());
+    bytes.extend_from_slice(b"WAVE");
+
+    bytes.extend_from_slice(b"fmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&sample_rate.to_le_bytes());
+    bytes.extend_from_slice(&(2 * sample_rate).to_le_bytes());
+    bytes.extend_from_slice(&2u16.to_le_bytes());
+    bytes.extend_from_slice(&16u16.to_le_bytes());
+
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_length.to_le_bytes());
+    samples.iter().for_each(|sample| {
+        let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16;
+        bytes.extend_from_slice(&value.to_le_bytes());
+    });
+
+    bytes
+}
+
+/// Reads a 16-bit PCM WAV file into mono samples between -1 and 1 and its sample rate.
+///
+/// The channels of multichannel files are mixed down. Chunks other than `fmt ` and `data` are
+/// skipped.
+pub fn read_wav(bytes: &[u8]) -> Result<(Vec<f32>, u32)> {
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return Err(eyre!("Not a WAV file."));
+    }
+
+    let mut format: Option<(u16, u32)> = None;
+    let mut position = 12;
+    while position + 8 <= bytes.len() {
+        let id = &bytes[position..position + 4];
+        let length = u32::from_le_bytes(bytes[position + 4..position + 8].try_into()?) as usize;
+        let body = bytes
+            .get(position + 8..position + 8 + length)
+            .ok_or_else(|| eyre!("WAV chunk is truncated."))?;
+
+        match id {
+            b"fmt " => {
+                if body.len() < 16 {
+                    return Err(eyre!("WAV format chunk is too short."));
+                }
+                let encoding = u16::from_le_bytes([body[0], body[1]]);
+                let channels = u16::from_le_bytes([body[2], body[3]]);
+                let sample_rate = u32::from_le_bytes(body[4..8].try_into()?);
+                let bits = u16::from_le_bytes([body[14], body[15]]);
+                if encoding != 1 || bits != 16 {
+                    return Err(eyre!("Only 16-bit PCM WAV files are supported."));
+                }
+                if channels == 0 {
+                    return Err(eyre!("WAV file has no channels."));
+                }
+                format = Some((channels, sample_rate));
+            }
+            b"data" => {
+                let (channels, sample_rate) =
+                    format.ok_or_else(|| eyre!("WAV data comes before its format."))?;
+                let samples = body
+                    .chunks_exact(2 * channels as usize)
+                    .map(|frame| {
+                        frame
+                            .chunks_exact(2)
+                            .map(|sample| {
+                                i16::from_le_bytes([sample[0], sample[1]]) as f32 / i16::MAX as f32
+                            })
+                            .sum::<f32>()
+                            / channels as f32
+                    })
+                    .collect();
+                return Ok((samples, sample_rate));
+            }
+            _ => {}
+        }
+
+        // Chunks are padded to an even length.
+        position += 8 + length + length % 2;
+    }
+
+    Err(eyre!("WAV file has no data."))
+}
@@ -0,0 +1,321 @@
+use crate::models::Receipt;
+
+use serde::{Deserialize, Serialize};
+
+/*
+ * Weakest Keys
+ *
+ * Scores how much every key needs practice from the history of a profile. Four signs of weakness
+ * count equally, each scaled between 0 and 1:
+ *
+ *   - few repetitions, compared with the most practiced key,
+ *   - low self-ratings, unrated keys counting as average,
+ *   - a long time since the key was last practiced, two weeks or more counting fully,
+ *   - a high share of skips among the times the key was selected.
+ *
+ * The `Weakest` strategy multiplies the weight of every key by `1 + 4 * score`, so the weakest
+ * keys come up up to five times as often as keys with nothing to catch up on.
+ */
+
+// Days without practice after which a key counts as fully neglected.
+const NEGLECTED_DAYS: f32 = 14.0;
+
+/// Weakness of one key, with what it was computed from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeyWeakness {
+    /// Index of the key, counted in semitones from C.
+    pub key: usize,
+    /// Repetitions over the whole history.
+    pub repetitions: i32,
+    /// Repetitions of the most practiced key.
+    pub most_repetitions: i32,
+    /// Average self-rating, from 1 to 5, if the key was ever rated.
+    pub average_rating: Option<f32>,
+    /// Whole days since the last session the key was practiced in, if it ever was.
+    pub days_since_practice: Option<i64>,
+    /// Times the key was skipped instead of practiced.
+    pub skips: i32,
+    /// Overall weakness, from 0 for nothing to catch up on to 1 for the weakest possible key.
+    pub score: f32,
+}
+
+impl KeyWeakness {
+    /// Multiplier applied to the weight of the key by the `Weakest` strategy.
+    pub fn factor(&self) -> f32 {
+        1.0 + 4.0 * self.score
+    }
+
+    /// Signs of weakness of the key, strongest first.
+    pub fn reasons(&self) -> Vec<String> {
+        let mut reasons: Vec<(f32, String)> = vec![
+            (
+                self.repetition_part(),
+                format!(
+                    "{} repetition(s) in total, the most practiced key has {}",
+                    self.repetitions, self.most_repetitions
+                ),
+            ),
+            (
+                self.rating_part(),
+                match self.average_rating {
+                    Some(rating) => format!("Rated {rating:.1} of 5 on average"),
+                    None => "Never rated".to_string(),
+                },
+            ),
+            (
+                self.recency_part(),
+                match self.days_since_practice {
+                    Some(0) => "Practiced today".to_string(),
+                    Some(days) => format!("Last practiced {days} day(s) ago"),
+                    None => "Never practiced".to_string(),
+                },
+            ),
+            (
+                self.skip_part(),
+                format!(
+                    "Skipped {} of {} time(s) selected",
+                    self.skips,
+                    self.skips + self.repetitions
+                ),
+            ),
+        ];
+        reasons.sort_by(|a, b| b.0.total_cmp(&a.0));
+        reasons.into_iter().map(|(_, reason)| reason).collect()
+    }
+
+    fn repetition_part(&self) -> f32 {
+        match self.most_repetitions {
+            0 => 1.0,
+            most => 1.0 - self.repetitions as f32 / most as f32,
+        }
+    }
+
+    fn rating_part(&self) -> f32 {
+        self.average_rating
+            .map_or(0.5, |rating| ((5.0 - rating) / 4.0).clamp(0.0, 1.0))
+    }
+
+    fn recency_part(&self) -> f32 {
+        self.days_since_practice
+            .map_or(1.0, |days| (days as f32 / NEGLECTED_DAYS).min(1.0))
+    }
+
+    fn skip_part(&self) -> f32 {
+        match self.skips + self.repetitions {
+            0 => 0.0,
+            selected => self.skips as f32 / selected as f32,
+        }
+    }
+}
+
+/// Weakness of each of the `keys` keys over `history`, as of `now` (a Unix timestamp in seconds).
+///
+/// Corrections of the receipts are applied.
+///
+/// ```
+/// use jovian_cycles_core::weakness::weakness;
+/// use jovian_cycles_core::Profile;
+///
+/// let day = 24 * 60 * 60;
+/// let mut profile = Profile::default();
+/// profile.log_practice(0, 600, &[(0, 10), (7, 2)]).unwrap();
+///
+/// let scores = weakness(&profile.history, 12, 3 * day);
+/// assert_eq!(scores[0].days_since_practice, Some(3));
+/// assert!(scores[7].score > scores[0].score);
+/// assert_eq!(scores[5].days_since_practice, None);
+/// assert!(scores[5].score > scores[7].score);
+/// ```
+pub fn weakness<'a>(
+    history: impl IntoIterator<Item = &'a Receipt>,
+    keys: usize,
+    now: i64,
+) -> Vec<KeyWeakness> {
+    let mut repetitions = vec![0; keys];
+    let mut ratings = vec![Vec::new(); keys];
+    let mut last_practiced: Vec<Option<i64>> = vec![None; keys];
+    let mut skips = vec![0; keys];
+
+    history.into_iter().for_each(|receipt| {
+        let receipt = receipt.corrected();
+        let finished_at = receipt
+            .time_stamp_archive
+            .as_ref()
+            .and_then(|time_stamps| time_stamps.last())
+            .map(|(_, timestamp)| *timestamp);
+        // Receipts from before skips were recorded only tell them apart from repetitions.
+        let legacy = receipt.skips.is_empty()
+            && receipt
+                .key_data_archive
+                .iter()
+                .flat_map(|keys| keys.0.iter())
+                .all(|data| data.skips == 0);
+        receipt
+            .key_data_archive
+            .iter()
+            .flat_map(|keys| keys.0.iter())
+            .filter(|data| data.nid < keys)
+            .for_each(|data| {
+                repetitions[data.nid] += data.repetitions.max(0);
+                skips[data.nid] += match legacy {
+                    true => {
+                        let selected = receipt
+                            .key_sequence
+                            .iter()
+                            .filter(|key| **key == data.nid)
+                            .count() as i32;
+                        (selected - data.repetitions).max(0)
+                    }
+                    false => data.skips.max(0),
+                };
+                if let Some(rating) = data.rating {
+                    ratings[data.nid].push(rating as f32);
+                }
+                if data.repetitions > 0 {
+                    last_practiced[data.nid] = last_practiced[data.nid].max(finished_at);
+                }
+            });
+    });
+
+    let most_repetitions = repetitions.iter().copied().max().unwrap_or_default();
+    (0..keys)
+        .map(|key| {
+            let mut weakness = KeyWeakness {
+                key,
+                repetitions: repetitions[key],
+                most_repetitions,
+                average_rating: match ratings[key].len() {
+                    0 => None,
+                    rated => Some(ratings[key].iter().sum::<f32>() / rated as f32),
+                },
+                days_since_practice: last_practiced[key]
+                    .map(|practiced| (now - practiced).max(0) / (24 * 60 * 60)),
+                skips: skips[key],
+                score: 0.0,
+            };
+            weakness.score = (weakness.repetition_part()
+                + weakness.rating_part()
+                + weakness.recency_part()
+                + weakness.skip_part())
+                / 4.0;
+            weakness
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edits::ReceiptChange;
+    use crate::models::SkipEvent;
+    use crate::profile::Profile;
+
+    const DAY: i64 = 24 * 60 * 60;
+
+    // A session logged by hand at `finished_at` with the `repetitions` of each key.
+    fn logged(finished_at: i64, repetitions: &[(usize, u32)]) -> Receipt {
+        Profile::default()
+            .log_practice(finished_at, 600, repetitions)
+            .unwrap()
+            .clone()
+    }
+
+    fn key_data(receipt: &mut Receipt, key: usize) -> &mut crate::models::KeyData {
+        &mut receipt.key_data_archive.as_mut().unwrap().0[key]
+    }
+
+    #[test]
+    fn reads_skips_from_receipts_that_record_them() {
+        let mut receipt = logged(0, &[(0, 2), (7, 1)]);
+        // Selected twice more than practiced, but skipped only once.
+        receipt.key_sequence.extend([7, 7]);
+        key_data(&mut receipt, 7).skips = 1;
+        receipt.skips.push(SkipEvent {
+            key: 7,
+            skipped_at: 0,
+            reason: None,
+        });
+
+        let scores = weakness([&receipt], 12, 0);
+        assert_eq!(scores[7].skips, 1);
+        assert_eq!(scores[0].skips, 0);
+    }
+
+    #[test]
+    fn counts_skips_of_legacy_receipts_from_their_selections() {
+        let mut receipt = logged(0, &[(0, 2), (7, 1)]);
+        receipt.key_sequence.extend([7, 7]);
+
+        let scores = weakness([&receipt], 12, 0);
+        assert_eq!(scores[7].skips, 2);
+        assert_eq!(scores[0].skips, 0);
+        assert!(scores[7].skip_part() > scores[0].skip_part());
+    }
+
+    #[test]
+    fn scores_receipts_as_corrected() {
+        let mut receipt = logged(0, &[(0, 2), (7, 2)]);
+        receipt.edit(
+            ReceiptChange::Repetitions {
+                key: 0,
+                repetitions: 8,
+            },
+            DAY,
+        );
+        receipt.edit(ReceiptChange::RemoveKey { key: 7 }, DAY);
+
+        let scores = weakness([&receipt], 12, 0);
+        assert_eq!(scores[0].repetitions, 8);
+        assert_eq!(scores[0].most_repetitions, 8);
+        assert_eq!(scores[7].repetitions, 0);
+        assert_eq!(scores[7].days_since_practice, None);
+
+        // Withdrawn or excluded sessions are left out by the caller, not here.
+        receipt.edit(ReceiptChange::Excluded { excluded: true }, DAY);
+        assert_eq!(weakness([&receipt], 12, 0)[0].repetitions, 8);
+    }
+
+    #[test]
+    fn averages_ratings_and_counts_unrated_keys_as_average() {
+        let mut first = logged(0, &[(0, 1), (7, 1)]);
+        key_data(&mut first, 0).rating = Some(1);
+        key_data(&mut first, 7).rating = Some(5);
+        let mut second = logged(DAY, &[(0, 1), (7, 1)]);
+        key_data(&mut second, 0).rating = Some(2);
+
+        let scores = weakness([&first, &second], 12, DAY);
+        assert_eq!(scores[0].average_rating, Some(1.5));
+        assert_eq!(scores[0].rating_part(), 0.875);
+        assert_eq!(scores[7].average_rating, Some(5.0));
+        assert_eq!(scores[7].rating_part(), 0.0);
+        assert_eq!(scores[3].average_rating, None);
+        assert_eq!(scores[3].rating_part(), 0.5);
+        assert!(scores[0].score > scores[7].score);
+    }
+
+    #[test]
+    fn neglect_grows_with_the_days_since_the_last_practice() {
+        let receipts = [logged(0, &[(0, 1)]), logged(7 * DAY, &[(7, 1)])];
+
+        let scores = weakness(&receipts, 12, 14 * DAY);
+        assert_eq!(scores[7].days_since_practice, Some(7));
+        assert_eq!(scores[7].recency_part(), 0.5);
+        assert_eq!(scores[0].days_since_practice, Some(14));
+        assert_eq!(scores[0].recency_part(), 1.0);
+        assert_eq!(scores[3].recency_part(), 1.0);
+
+        let later = weakness(&receipts, 12, 60 * DAY);
+        assert_eq!(later[7].recency_part(), 1.0);
+        // Only the recency differs between the two keys.
+        assert!(scores[0].score > scores[7].score);
+        assert_eq!(later[0].score, later[7].score);
+    }
+
+    #[test]
+    fn the_weakest_key_is_five_times_as_likely() {
+        let scores = weakness(&[logged(0, &[(0, 1)])], 12, 0);
+        assert_eq!(scores[0].factor(), 1.0 + 4.0 * scores[0].score);
+        // Never practiced, rated, nor skipped.
+        assert_eq!(scores[3].score, (1.0 + 0.5 + 1.0) / 4.0);
+    }
+}
@@ -0,0 +1,63 @@
+use jovian_cycles_core::replay::{parse_script, replay};
+
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/*
+ * Golden tests of session replays: every `tests/fixtures/replay/<name>.script` is replayed and
+ * its receipt compared with `<name>.json` next to it. Session ids are random and left out.
+ *
+ * Run with `UPDATE_GOLDEN=1` to write the expected receipts from the current behavior.
+ */
+
+fn fixtures() -> Vec<PathBuf> {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/replay");
+    let mut scripts: Vec<PathBuf> = fs::read_dir(directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "script")
+        })
+        .collect();
+    scripts.sort();
+    scripts
+}
+
+// The receipt of the replayed script, as JSON.
+fn replayed(path: &Path) -> Value {
+    let script = parse_script(&fs::read_to_string(path).unwrap()).unwrap();
+    let mut receipt = serde_json::to_value(replay(&script).unwrap()).unwrap();
+    if let Some(fields) = receipt.as_object_mut() {
+        fields.remove("session_id");
+    }
+    receipt
+}
+
+#[test]
+fn replays_match_golden_receipts() {
+    let scripts = fixtures();
+    assert!(!scripts.is_empty());
+    scripts.iter().for_each(|path| {
+        let receipt = replayed(path);
+        let golden = path.with_extension("json");
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            fs::write(
+                &golden,
+                serde_json::to_string_pretty(&receipt).unwrap() + "\n",
+            )
+            .unwrap();
+        }
+        let expected: Value = serde_json::from_str(&fs::read_to_string(&golden).unwrap())
+            .unwrap_or_else(|e| panic!("{}: {e}", golden.display()));
+        assert_eq!(receipt, expected, "{}", path.display());
+    });
+}
+
+#[test]
+fn replays_are_deterministic() {
+    fixtures().iter().for_each(|path| {
+        assert_eq!(replayed(path), replayed(path), "{}", path.display());
+    });
+}
@@ -0,0 +1,109 @@
+use jovian_cycles_core::sync::{self, profile_from_path};
+use jovian_cycles_core::Receipt;
+
+use color_eyre::eyre::{Result, WrapErr};
+use std::collections::BTreeMap;
+use std::io::Read;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+/*
+ * Sync Server
+ *
+ * Keeps the receipts of every profile that syncs with it in a single JSON file and answers the
+ * requests described in `jovian_cycles_core::sync`.
+ */
+
+/// Receipts of every profile, by profile id.
+pub type Store = BTreeMap<String, Vec<Receipt>>;
+
+/// Reads the store saved at `path`, empty if nothing was saved yet.
+pub fn load(path: &str) -> Result<Store> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => serde_json::from_str(&text).wrap_err_with(|| format!("Unable to read {path}")),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Store::new()),
+        Err(e) => Err(e).wrap_err_with(|| format!("Unable to open {path}")),
+    }
+}
+
+// Writes the whole store next to the data file first, so a crash never leaves it half written.
+fn save(path: &str, store: &Store) -> Result<()> {
+    let temporary = format!("{path}.tmp");
+    std::fs::write(&temporary, serde_json::to_string(store)?)?;
+    std::fs::rename(&temporary, path)?;
+    Ok(())
+}
+
+/// Answers the requests reaching `server` until it is unblocked, saving `store` to `data_path`
+/// whenever it changes.
+pub fn serve(server: &Server, mut store: Store, data_path: &str) {
+    for request in server.incoming_requests() {
+        if let Err(e) = handle(request, &mut store, data_path) {
+            log::error!("{e:?}");
+        }
+    }
+}
+
+fn handle(mut request: Request, store: &mut Store, data_path: &str) -> Result<()> {
+    let path = request
+        .url()
+        .split('?')
+        .next()
+        .unwrap_or_default()
+        .to_string();
+
+    // Browsers ask before letting the web app send JSON to another origin.
+    if *request.method() == Method::Options {
+        return Ok(request.respond(with_headers(Response::from_string(""), "text/plain"))?);
+    }
+
+    let Some(profile) = profile_from_path(&path) else {
+        return Ok(request.respond(text(404, "Not found."))?);
+    };
+
+    let response = match request.method() {
+        Method::Get => receipts(store.get(&profile).map(Vec::as_slice).unwrap_or_default())?,
+        Method::Post => {
+            let mut body = String::new();
+            request.as_reader().read_to_string(&mut body)?;
+            match sync::from_json(&body) {
+                Ok(incoming) => {
+                    let history = store.entry(profile.clone()).or_default();
+                    let changed = sync::merge(history, incoming);
+                    let response = receipts(history)?;
+                    if changed > 0 {
+                        log::info!("Added or corrected {changed} receipt(s) of '{profile}'.");
+                        save(data_path, store)?;
+                    }
+                    response
+                }
+                Err(e) => text(400, &format!("Unable to read the receipts: {e}")),
+            }
+        }
+        _ => text(405, "Only GET and POST are supported."),
+    };
+
+    Ok(request.respond(response)?)
+}
+
+fn receipts(receipts: &[Receipt]) -> Result<Response<std::io::Cursor<Vec<u8>>>> {
+    Ok(with_headers(
+        Response::from_string(sync::to_json(receipts)?),
+        "application/json",
+    ))
+}
+
+fn text(status: u16, message: &str) -> Response<std::io::Cursor<Vec<u8>>> {
+    with_headers(Response::from_string(message), "text/plain").with_status_code(status)
+}
+
+fn with_headers<R: Read>(response: Response<R>, content_type: &str) -> Response<R> {
+    [
+        ("Content-Type", content_type),
+        ("Access-Control-Allow-Origin", "*"),
+        ("Access-Control-Allow-Methods", "GET, POST, OPTIONS"),
+        ("Access-Control-Allow-Headers", "Content-Type"),
+    ]
+    .into_iter()
+    .filter_map(|(name, value)| Header::from_bytes(name, value).ok())
+    .fold(response, |response, header| response.with_header(header))
+}
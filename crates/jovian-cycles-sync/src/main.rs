@@ -0,0 +1,37 @@
+use jovian_cycles_sync::{load, serve};
+
+use color_eyre::eyre::{eyre, Result};
+use tiny_http::Server;
+
+/*
+ * Sync Server
+ *
+ * Run it on any machine the devices can reach, e.g. for a quick try on one machine:
+ *
+ *     cargo run -p jovian-cycles-sync -- --address 127.0.0.1:7878 --data sync.json
+ *
+ * and set the sync server of the app to `http://127.0.0.1:7878`.
+ */
+
+fn main() -> Result<()> {
+    color_eyre::install()?;
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let option = |name: &str| {
+        args.iter()
+            .position(|arg| arg == name)
+            .and_then(|index| args.get(index + 1))
+            .cloned()
+    };
+    let address = option("--address").unwrap_or_else(|| "127.0.0.1:7878".to_string());
+    let data_path = option("--data").unwrap_or_else(|| "jovian-cycles-sync.json".to_string());
+
+    let store = load(&data_path)?;
+    let server = Server::http(&address).map_err(|e| eyre!("Unable to listen on {address}: {e}"))?;
+    log::info!("Syncing on http://{address}, storing receipts in {data_path}.");
+
+    serve(&server, store, &data_path);
+
+    Ok(())
+}
@@ -0,0 +1,193 @@
+use jovian_cycles_core::edits::ReceiptChange;
+use jovian_cycles_core::sync::{self, receipts_url};
+use jovian_cycles_core::{Profile, Receipt};
+use jovian_cycles_sync::{load, serve};
+
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use tiny_http::Server;
+
+/*
+ * Syncs devices through a server listening on a free port of localhost, with the same requests
+ * the app sends.
+ */
+
+struct TestServer {
+    url: String,
+    server: Arc<Server>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl TestServer {
+    fn start(data_path: &str) -> Self {
+        let server = Arc::new(Server::http("127.0.0.1:0").unwrap());
+        let url = format!("http://{}", server.server_addr().to_ip().unwrap());
+        let store = load(data_path).unwrap();
+        let thread = {
+            let server = server.clone();
+            let data_path = data_path.to_string();
+            std::thread::spawn(move || serve(&server, store, &data_path))
+        };
+        TestServer {
+            url,
+            server,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.server.unblock();
+        if let Some(thread) = self.thread.take() {
+            thread.join().unwrap();
+        }
+    }
+}
+
+// A data file of its own for every test, removed beforehand in case an earlier run left it.
+fn data_path(test: &str) -> String {
+    let path: PathBuf = std::env::temp_dir().join(format!(
+        "jovian-cycles-sync-{test}-{}.json",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&path);
+    path.to_string_lossy().into_owned()
+}
+
+fn fetch(request: ehttp::Request) -> Result<Vec<Receipt>, String> {
+    let response = ehttp::fetch_blocking(&request)?;
+    match response.text() {
+        Some(text) if response.ok => sync::from_json(text).map_err(|e| e.to_string()),
+        _ => Err(format!("{} {}", response.status, response.status_text)),
+    }
+}
+
+// Sends the whole history and merges back the answer, like the app does.
+fn sync_profile(server_url: &str, profile: &mut Profile) -> Result<usize, String> {
+    let body = sync::to_json(&profile.history).unwrap().into_bytes();
+    let url = receipts_url(server_url, &profile.id);
+    let receipts = fetch(ehttp::Request::post(url, body))?;
+    Ok(sync::merge(&mut profile.history, receipts))
+}
+
+fn pull(server_url: &str, profile: &str) -> Result<Vec<Receipt>, String> {
+    fetch(ehttp::Request::get(receipts_url(server_url, profile)))
+}
+
+#[test]
+fn devices_share_their_history() {
+    let data_path = data_path("share");
+    let server = TestServer::start(&data_path);
+
+    let mut laptop = Profile::new("Ms. Ozerova's class");
+    laptop.log_practice(1_000, 600, &[(0, 2)]).unwrap();
+    laptop.log_practice(3_000, 600, &[(7, 1)]).unwrap();
+    // The same profile on another device.
+    let mut phone = Profile {
+        id: laptop.id.clone(),
+        ..Profile::new("Ms. Ozerova's class")
+    };
+    phone.log_practice(2_000, 300, &[(5, 4)]).unwrap();
+
+    assert_eq!(sync_profile(&server.url, &mut laptop), Ok(0));
+    assert_eq!(sync_profile(&server.url, &mut phone), Ok(2));
+    assert_eq!(sync_profile(&server.url, &mut laptop), Ok(1));
+    assert_eq!(laptop.history, phone.history);
+    assert_eq!(laptop.history.len(), 3);
+
+    // Corrections travel too.
+    phone.history[0].edit(ReceiptChange::Excluded { excluded: true }, 4_000);
+    assert_eq!(sync_profile(&server.url, &mut phone), Ok(0));
+    assert_eq!(sync_profile(&server.url, &mut laptop), Ok(1));
+    assert!(laptop.history[0].is_excluded());
+    assert_eq!(laptop.history, phone.history);
+
+    // So does a session taken back by undoing its end, instead of the server handing it back.
+    let undone = laptop.history[2].clone();
+    laptop.withdraw(&undone, 5_000);
+    assert_eq!(sync_profile(&server.url, &mut laptop), Ok(0));
+    assert_eq!(sync_profile(&server.url, &mut phone), Ok(1));
+    assert_eq!(phone.sessions().count(), 2);
+    assert!(laptop.history[2].is_withdrawn());
+    assert_eq!(laptop.history, phone.history);
+
+    // Syncing again changes nothing, and other profiles are kept apart.
+    assert_eq!(sync_profile(&server.url, &mut laptop), Ok(0));
+    assert_eq!(pull(&server.url, "Someone else"), Ok(Vec::new()));
+    assert_eq!(pull(&server.url, &laptop.id), Ok(laptop.history.clone()));
+    let _ = std::fs::remove_file(&data_path);
+}
+
+#[test]
+fn profiles_with_the_same_name_keep_their_own_history() {
+    let data_path = data_path("same_name");
+    let server = TestServer::start(&data_path);
+
+    let mut anna = Profile::new("Default");
+    anna.log_practice(1_000, 600, &[(0, 2)]).unwrap();
+    let mut boris = Profile::new("Default");
+    boris.log_practice(2_000, 600, &[(7, 1)]).unwrap();
+    assert_ne!(anna.id, boris.id);
+
+    assert_eq!(sync_profile(&server.url, &mut anna), Ok(0));
+    assert_eq!(sync_profile(&server.url, &mut boris), Ok(0));
+    assert_eq!(sync_profile(&server.url, &mut anna), Ok(0));
+    assert_eq!(anna.history.len(), 1);
+    assert_eq!(boris.history.len(), 1);
+    assert_eq!(pull(&server.url, "Default"), Ok(Vec::new()));
+    let _ = std::fs::remove_file(&data_path);
+}
+
+#[test]
+fn history_outlives_the_server() {
+    let data_path = data_path("restart");
+    let mut profile = Profile::new("Default");
+    profile.log_practice(1_000, 600, &[(0, 2)]).unwrap();
+    {
+        let server = TestServer::start(&data_path);
+        assert_eq!(sync_profile(&server.url, &mut profile), Ok(0));
+    }
+
+    let server = TestServer::start(&data_path);
+    assert_eq!(pull(&server.url, &profile.id), Ok(profile.history.clone()));
+    let _ = std::fs::remove_file(&data_path);
+}
+
+#[test]
+fn history_stays_on_the_device_while_the_server_is_unreachable() {
+    // A port nothing listens on any more.
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let url = format!("http://127.0.0.1:{port}");
+
+    let mut profile = Profile::new("Default");
+    profile.log_practice(1_000, 600, &[(0, 2)]).unwrap();
+    let history = profile.history.clone();
+    assert!(sync_profile(&url, &mut profile).is_err());
+    assert_eq!(profile.history, history);
+
+    // The next sync that gets through catches the server up.
+    let data_path = data_path("unreachable");
+    let server = TestServer::start(&data_path);
+    assert_eq!(sync_profile(&server.url, &mut profile), Ok(0));
+    assert_eq!(pull(&server.url, &profile.id), Ok(history));
+    let _ = std::fs::remove_file(&data_path);
+}
+
+#[test]
+fn rejects_bad_requests() {
+    let data_path = data_path("bad");
+    let server = TestServer::start(&data_path);
+
+    let not_json = ehttp::Request::post(receipts_url(&server.url, "Default"), b"{".to_vec());
+    assert!(fetch(not_json).unwrap_err().starts_with("400"));
+    let elsewhere = ehttp::Request::get(format!("{}/profiles", server.url));
+    assert!(fetch(elsewhere).unwrap_err().starts_with("404"));
+    assert_eq!(pull(&server.url, "Default"), Ok(Vec::new()));
+}
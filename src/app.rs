@@ -1,11 +1,130 @@
-use crate::transitions::{PracticeSessionState, SessionStates};
-use crate::utils::match_states;
+use crate::export;
+use crate::practice_log::PracticeLogState;
+use crate::profiles::{self, Settings};
+use crate::report::ReportState;
+use crate::shortcuts::{first_pressed_key, KeyBindings, ShortcutAction};
+use crate::weakest;
+use jovian_cycles_core::selection::Strategy;
+use jovian_cycles_core::statistics::format_duration;
+use jovian_cycles_core::{
+    PracticeSessionState, Profile, SessionCheckpoint, SessionCommand, SessionStates,
+};
 
-use crate::models::{KeysMap, Receipt};
-use egui::{Align, Direction, Visuals};
-use log::{debug, error, info};
+use egui::{Align, Key, KeyboardShortcut, Modifiers};
+use log::debug;
+use serde::{Deserialize, Serialize};
 
-impl PracticeSessionState {
+const UNDO_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::Z);
+const REDO_SHORTCUT: KeyboardShortcut =
+    KeyboardShortcut::new(Modifiers::COMMAND.plus(Modifiers::SHIFT), Key::Z);
+
+// Profile changes requested from the profile menu.
+enum ProfileAction {
+    Switch(String),
+    Create,
+    Delete,
+    Export,
+    Import,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
+pub struct JovianCyclesApp {
+    #[serde(skip)]
+    pub theme: catppuccin_egui::Theme,
+    #[serde(skip)]
+    pub session: PracticeSessionState,
+    /// Names of every profile, in the order they were created.
+    pub profile_names: Vec<String>,
+    pub active_profile: String,
+    #[serde(skip)]
+    pub profile: Profile,
+    #[serde(skip)]
+    pub settings: Settings,
+    #[serde(skip)]
+    new_profile_name: String,
+    /// File profiles are exported to and imported from.
+    pub profile_path: String,
+    #[serde(skip)]
+    profile_status: Option<String>,
+    #[serde(skip)]
+    pub show_shortcuts: bool,
+    #[serde(skip)]
+    pub rebinding: Option<ShortcutAction>,
+    #[serde(skip)]
+    pub show_midi: bool,
+    #[serde(skip)]
+    pub show_mic: bool,
+    #[serde(skip)]
+    pub show_tuner: bool,
+    #[serde(skip)]
+    pub show_export: bool,
+    #[serde(skip)]
+    pub show_assignments: bool,
+    #[serde(skip)]
+    pub show_sync: bool,
+    #[serde(skip)]
+    pub show_log: bool,
+    #[serde(skip)]
+    pub show_weakest: bool,
+    #[serde(skip)]
+    rest_reminder_sent: bool,
+    #[serde(skip)]
+    last_activity: i64,
+    #[serde(skip)]
+    idle_pause: Option<i64>,
+    #[serde(skip)]
+    interrupted: Option<SessionCheckpoint>,
+    #[serde(skip)]
+    report: ReportState,
+    #[serde(skip)]
+    practice_log: PracticeLogState,
+    // Number of skips of the session the player was asked about.
+    #[serde(skip)]
+    skips_asked: usize,
+    #[serde(skip)]
+    skip_reason_text: String,
+    // History the weakness of keys was last scored on.
+    #[serde(skip)]
+    weakness_scored: Option<(usize, usize, bool, i64)>,
+}
+
+impl Default for JovianCyclesApp {
+    fn default() -> Self {
+        JovianCyclesApp {
+            theme: catppuccin_egui::LATTE,
+            session: PracticeSessionState::default(),
+            profile_names: vec!["Default".to_string()],
+            active_profile: "Default".to_string(),
+            profile: Profile::default(),
+            settings: Settings::default(),
+            new_profile_name: String::new(),
+            profile_path: "jovian-cycles-profile.json".to_string(),
+            profile_status: None,
+            show_shortcuts: false,
+            rebinding: None,
+            show_midi: false,
+            show_mic: false,
+            show_tuner: false,
+            show_export: false,
+            show_assignments: false,
+            show_sync: false,
+            show_log: false,
+            show_weakest: false,
+            rest_reminder_sent: false,
+            last_activity: 0,
+            idle_pause: None,
+            interrupted: None,
+            report: ReportState::default(),
+            practice_log: PracticeLogState::default(),
+            skips_asked: 0,
+            skip_reason_text: String::new(),
+            weakness_scored: None,
+        }
+    }
+}
+
+impl JovianCyclesApp {
     /// Called once before the first frame.
     pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
         // This is also where you can customize the look and feel of egui using
@@ -13,17 +132,716 @@ impl PracticeSessionState {
 
         // Load previous app state (if any).
         // Note that you must enable the `persistence` feature for this to work.
-        if let Some(storage) = cc.storage {
-            return eframe::get_value(storage, eframe::APP_KEY).unwrap_or_default();
+        let mut app: JovianCyclesApp = match cc.storage {
+            Some(storage) => eframe::get_value(storage, eframe::APP_KEY).unwrap_or_default(),
+            None => Default::default(),
+        };
+
+        let (profile, settings) = match cc.storage {
+            Some(storage) => match profiles::load(storage, &app.active_profile) {
+                Some(loaded) => loaded,
+                // Settings saved before there were profiles go to the first profile.
+                None => (
+                    Profile::new(&app.active_profile),
+                    eframe::get_value(storage, eframe::APP_KEY).unwrap_or_default(),
+                ),
+            },
+            None => (Profile::new(&app.active_profile), Settings::default()),
+        };
+        let interrupted = cc
+            .storage
+            .and_then(|storage| profiles::load_checkpoint(storage, &app.active_profile));
+        app.open_profile(profile, settings, interrupted);
+
+        app
+    }
+
+    /// Seeds the key selection, keeping the seed restored from storage when `seed` is `None`.
+    pub fn with_seed(mut self, seed: Option<u64>) -> Self {
+        if let Some(seed) = seed {
+            self.settings.seed_text = seed.to_string();
+        }
+        self.apply_seed();
+        self
+    }
+
+    // Starts a fresh session with the weights and settings of `profile`, offering to pick up the
+    // session that was interrupted the last time it was used.
+    fn open_profile(
+        &mut self,
+        profile: Profile,
+        settings: Settings,
+        interrupted: Option<SessionCheckpoint>,
+    ) {
+        self.active_profile = profile.name.clone();
+        if !self.profile_names.contains(&self.active_profile) {
+            self.profile_names.push(self.active_profile.clone());
+        }
+        self.profile = profile;
+        self.settings = settings;
+        self.rebinding = None;
+        self.interrupted = interrupted;
+
+        self.session = PracticeSessionState::default();
+        self.profile.apply_weights(&mut self.session.session_data);
+        self.apply_seed();
+        self.weakness_scored = None;
+    }
+
+    // Scores the keys again whenever the history changes, or at least every hour as the time since
+    // keys were practiced grows.
+    fn score_weakness(&mut self) {
+        let now = self.session.session_data.clock.now();
+        let history = &self.profile.history;
+        let scored = (
+            history.len(),
+            history.iter().map(|receipt| receipt.edits.len()).sum(),
+            self.profile.goals.count_manual,
+            now / 3600,
+        );
+        if self.weakness_scored != Some(scored) {
+            self.session.session_data.weakness = self.profile.weakness(now);
+            self.weakness_scored = Some(scored);
+        }
+    }
+
+    // Finished sessions go to the history of the active profile, and to the sync server. Undone
+    // ones leave it again.
+    fn record_finished_sessions(&mut self) {
+        let now = self.session.session_data.clock.now();
+        let withdrawn = self.session.take_withdrawn_receipts();
+        withdrawn
+            .iter()
+            .for_each(|receipt| self.profile.withdraw(receipt, now));
+
+        let receipts = self.session.take_finished_receipts();
+        if !receipts.is_empty() || !withdrawn.is_empty() {
+            self.settings.sync.sync_soon();
+        }
+        receipts
+            .into_iter()
+            .for_each(|receipt| self.profile.record(receipt, now));
+    }
+
+    // Shows how long the current rest lasts and reminds the player once it reaches the cap.
+    fn rest_timer(&mut self, ui: &mut egui::Ui) {
+        if !matches!(self.session.session_state, SessionStates::Resting) {
+            self.rest_reminder_sent = false;
+            return;
+        }
+
+        let elapsed = self.session.session_data.elapsed_seconds();
+        let cap = self.settings.rest_cap_minutes as i64 * 60;
+        if cap > 0 && elapsed >= cap {
+            ui.colored_label(
+                egui::Color32::RED,
+                format!(
+                    "Resting for {}, time to get back to work.",
+                    format_duration(elapsed)
+                ),
+            );
+            if !self.rest_reminder_sent {
+                self.rest_reminder_sent = true;
+                ui.ctx()
+                    .send_viewport_cmd(egui::ViewportCommand::RequestUserAttention(
+                        egui::UserAttentionType::Informational,
+                    ));
+            }
+        } else {
+            ui.label(format!("Resting for {}", format_duration(elapsed)));
+        }
+    }
+
+    // Pauses a session left working without input, playing or focus, as of the last activity.
+    // Frames stop while a browser tab is hidden, so the gap is noticed as soon as it is shown.
+    fn detect_idle(&mut self, ctx: &egui::Context, played: bool) {
+        let now = self.session.session_data.clock.now();
+        let working = matches!(self.session.session_state, SessionStates::Working);
+        let limit = self.settings.idle_minutes as i64 * 60;
+
+        if working && limit > 0 && now - self.last_activity >= limit {
+            self.session
+                .apply(SessionCommand::PauseSince(self.last_activity));
+            self.idle_pause = Some(now - self.last_activity);
+            return;
+        }
+
+        let used = ctx.input(|i| i.focused && !i.events.is_empty());
+        if !working || used || played {
+            self.last_activity = now;
+        }
+    }
+
+    fn idle_banner(&mut self, ctx: &egui::Context) {
+        let Some(idle_seconds) = self.idle_pause else {
+            return;
+        };
+        if !matches!(self.session.session_state, SessionStates::Resting) {
+            self.idle_pause = None;
+            return;
+        }
+
+        egui::TopBottomPanel::top("idle_banner").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(format!(
+                    "The session paused itself after {} without activity. Count that time as practice?",
+                    format_duration(idle_seconds)
+                ));
+                if ui
+                    .button("Keep")
+                    .on_hover_text("Count the time since the pause as work and carry on working.")
+                    .clicked()
+                {
+                    self.session.cancel_pause();
+                    self.idle_pause = None;
+                }
+                if ui
+                    .button("Discard")
+                    .on_hover_text("Count the time as rest and stay paused.")
+                    .clicked()
+                {
+                    self.idle_pause = None;
+                }
+            });
+        });
+    }
+
+    // Ends the interrupted session as of its last checkpoint.
+    fn finish_interrupted(&mut self) {
+        if let Some(checkpoint) = self.interrupted.take() {
+            let now = self.session.session_data.clock.now();
+            let mut interrupted = PracticeSessionState::default();
+            interrupted.session_data.clock = self.session.session_data.clock;
+            interrupted.finish_from(checkpoint);
+            interrupted
+                .take_finished_receipts()
+                .into_iter()
+                .for_each(|receipt| self.profile.record(receipt, now));
+            self.settings.sync.sync_soon();
+        }
+    }
+
+    fn interrupted_session_window(&mut self, ctx: &egui::Context) {
+        let Some(checkpoint) = &self.interrupted else {
+            return;
+        };
+        // Starting a new session settles the one that was interrupted.
+        if !matches!(self.session.session_state, SessionStates::Waiting) {
+            self.finish_interrupted();
+            return;
+        }
+
+        let mut data = checkpoint.session_data.clone();
+        data.clock.set(checkpoint.saved_at);
+        let worked: i64 = (0..data.all_keys_map.0.len())
+            .map(|key| data.working_seconds(key))
+            .sum();
+        let repetitions: i32 = data.all_keys_map.0.iter().map(|key| key.repetitions).sum();
+        let saved_at = chrono::DateTime::from_timestamp(checkpoint.saved_at, 0)
+            .map(|time| {
+                time.with_timezone(&chrono::Local)
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string()
+            })
+            .unwrap_or_default();
+
+        let mut resume = false;
+        let mut finish = false;
+        egui::Window::new("Interrupted Session")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "A session was still running when the app closed ({saved_at})."
+                ));
+                if let Some(key) = data.current_key_data {
+                    ui.label(format!(
+                        "Current key: {}",
+                        self.session.note_name_list[key.nid]
+                    ));
+                }
+                ui.label(format!(
+                    "{} repetition(s) over {} of work.",
+                    repetitions,
+                    format_duration(worked)
+                ));
+                ui.horizontal(|ui| {
+                    resume = ui
+                        .button("Resume")
+                        .on_hover_text("Carry on, paused. The time the app was closed is rest.")
+                        .clicked();
+                    finish = ui
+                        .button("Finish")
+                        .on_hover_text("Save the session as it was when the app closed.")
+                        .clicked();
+                });
+            });
+
+        if resume {
+            if let Some(checkpoint) = self.interrupted.take() {
+                self.session.resume_from(checkpoint);
+            }
+        }
+        if finish {
+            self.finish_interrupted();
+        }
+    }
+
+    // Asks why the last key was skipped, until the player answers or moves on.
+    fn skip_reason(&mut self, ui: &mut egui::Ui) {
+        let data = &mut self.session.session_data;
+        self.skips_asked = self.skips_asked.min(data.skips.len());
+        let Some(skip) = data.skips.last() else {
+            return;
+        };
+        if self.skips_asked == data.skips.len() || skip.reason.is_some() {
+            return;
+        }
+
+        let key = self.session.note_name_list[skip.key].clone();
+        let mut answer = None;
+        ui.horizontal_wrapped(|ui| {
+            ui.label(format!("Why did you skip {key}?"));
+            ["Too hard", "Not now", "Already know it"]
+                .into_iter()
+                .for_each(|reason| {
+                    if ui.small_button(reason).clicked() {
+                        answer = Some(reason.to_string());
+                    }
+                });
+            ui.add(
+                egui::TextEdit::singleline(&mut self.skip_reason_text)
+                    .hint_text("Other reason")
+                    .desired_width(100.0),
+            );
+            if ui.small_button("✔").clicked() {
+                answer = Some(std::mem::take(&mut self.skip_reason_text));
+            }
+            if ui.small_button("Dismiss").clicked() {
+                answer = Some(String::new());
+            }
+        });
+        if let Some(reason) = answer {
+            data.give_skip_reason(&reason);
+            self.skips_asked = data.skips.len();
+        }
+    }
+
+    // Self-rating of the current key, which the weakest keys strategy takes into account.
+    fn rating(&mut self, ui: &mut egui::Ui) {
+        let Some(current) = self.session.session_data.current_key_data else {
+            return;
+        };
+        ui.horizontal(|ui| {
+            ui.label("How did it go?");
+            (1..=5).for_each(|rating| {
+                let stars = if current.rating.is_some_and(|rated| rated >= rating) {
+                    "★"
+                } else {
+                    "☆"
+                };
+                if ui
+                    .selectable_label(current.rating == Some(rating), stars)
+                    .on_hover_text(format!("{rating} of 5"))
+                    .clicked()
+                {
+                    self.session.session_data.rate_current_key(rating);
+                }
+            });
+        });
+    }
+
+    // Excluding, pinning and weighting keys. An assignment decides which keys come up while it is
+    // practiced.
+    fn key_controls(&mut self, ui: &mut egui::Ui) {
+        let assigned = self.session.session_data.assignment.is_some();
+        egui::CollapsingHeader::new("Keys").show(ui, |ui| {
+            ui.add_enabled_ui(!assigned, |ui| {
+                let strategy = &mut self.session.session_data.strategy;
+                egui::ComboBox::from_label("Selection")
+                    .selected_text(strategy.label())
+                    .show_ui(ui, |ui| {
+                        Strategy::ALL.iter().for_each(|option| {
+                            ui.selectable_value(strategy, *option, option.label());
+                        });
+                    });
+                let constraints = &mut self.session.session_data.constraints;
+                ui.horizontal(|ui| {
+                    ui.label("No repeat within the last");
+                    ui.add(egui::DragValue::new(&mut constraints.avoid_last).range(0..=11))
+                        .on_hover_text("1 avoids immediate repeats, 0 allows them.");
+                    ui.label("key(s)");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Consecutive keys at least");
+                    ui.add(egui::DragValue::new(&mut constraints.min_interval).range(0..=6))
+                        .on_hover_text("Semitones up or down between one key and the next.");
+                    ui.label("semitone(s) apart");
+                });
+                egui::Grid::new("key_controls").show(ui, |ui| {
+                    let names = &self.session.note_name_list;
+                    self.session
+                        .session_data
+                        .all_keys_map
+                        .0
+                        .iter_mut()
+                        .for_each(|key| {
+                            ui.label(&names[key.nid]);
+
+                            let mut included = key.weight > 0;
+                            if ui
+                                .checkbox(&mut included, "")
+                                .on_hover_text("Select the key")
+                                .changed()
+                            {
+                                key.weight = if included { 100 } else { 0 };
+                                key.pinned &= included;
+                            }
+
+                            if ui
+                                .toggle_value(&mut key.pinned, "📌")
+                                .on_hover_text("Select the key in every session")
+                                .changed()
+                                && key.pinned
+                                && key.weight == 0
+                            {
+                                key.weight = 100;
+                            }
+
+                            ui.add(egui::Slider::new(&mut key.weight, 0..=500))
+                                .on_hover_text("How often the key is selected");
+                            ui.end_row();
+                        });
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Weight added per skip:");
+                    ui.add(
+                        egui::DragValue::new(&mut self.session.session_data.skip_bonus)
+                            .range(0..=100),
+                    )
+                    .on_hover_text(
+                        "Bring skipped keys back more often in the session, 0 to turn off.",
+                    );
+                });
+            })
+            .response
+            .on_disabled_hover_text("The assignment being practiced decides the keys.");
+        });
+    }
+
+    // The weights of an assignment only last while it is practiced.
+    fn store_weights(&mut self) {
+        if self.session.session_data.assignment.is_none() {
+            self.profile.store_weights(&self.session.session_data);
+        }
+    }
+
+    fn save_profile(&mut self, storage: &mut dyn eframe::Storage) {
+        self.record_finished_sessions();
+        self.store_weights();
+        profiles::save(storage, &self.profile, &self.settings);
+
+        // An interrupted session nobody answered for yet is kept for the next launch.
+        let checkpoint = self
+            .session
+            .checkpoint()
+            .or_else(|| self.interrupted.clone());
+        profiles::save_checkpoint(storage, &self.profile.name, checkpoint.as_ref());
+    }
+
+    // A running session is finished first, so it counts for the profile it was played with.
+    fn leave_profile(&mut self, storage: Option<&mut (dyn eframe::Storage + 'static)>) {
+        if !matches!(self.session.session_state, SessionStates::Waiting) {
+            self.session.finish();
+        }
+        if let Some(storage) = storage {
+            self.save_profile(storage);
+        }
+    }
+
+    fn handle_profile_action(
+        &mut self,
+        action: ProfileAction,
+        mut storage: Option<&mut (dyn eframe::Storage + 'static)>,
+    ) {
+        match action {
+            ProfileAction::Switch(name) => {
+                self.leave_profile(storage.as_deref_mut());
+                let (loaded, interrupted) = match storage {
+                    Some(storage) => (
+                        profiles::load(storage, &name),
+                        profiles::load_checkpoint(storage, &name),
+                    ),
+                    None => (None, None),
+                };
+                let (profile, settings) =
+                    loaded.unwrap_or_else(|| (Profile::new(&name), Settings::default()));
+                self.open_profile(profile, settings, interrupted);
+                self.profile_status = None;
+            }
+            ProfileAction::Create => {
+                let name = profiles::unique_name(&self.new_profile_name, &self.profile_names);
+                self.leave_profile(storage);
+                self.open_profile(Profile::new(&name), Settings::default(), None);
+                self.new_profile_name.clear();
+                self.profile_status = Some(format!("Created profile {name}."));
+            }
+            ProfileAction::Delete => {
+                let deleted = self.active_profile.clone();
+                self.profile_names.retain(|name| *name != deleted);
+                let Some(next) = self.profile_names.first().cloned() else {
+                    self.profile_names.push(deleted);
+                    return;
+                };
+
+                let (loaded, interrupted) = match storage {
+                    Some(storage) => {
+                        profiles::remove(storage, &deleted);
+                        (
+                            profiles::load(storage, &next),
+                            profiles::load_checkpoint(storage, &next),
+                        )
+                    }
+                    None => (None, None),
+                };
+                let (profile, settings) =
+                    loaded.unwrap_or_else(|| (Profile::new(&next), Settings::default()));
+                self.open_profile(profile, settings, interrupted);
+                self.profile_status = Some(format!("Deleted profile {deleted}."));
+            }
+            ProfileAction::Export => {
+                self.record_finished_sessions();
+                self.store_weights();
+                self.profile_status =
+                    Some(match profiles::to_json(&self.profile, &self.settings) {
+                        Ok(json) => export::save(&self.profile_path, json.as_bytes()),
+                        Err(e) => format!("Unable to export the profile: {e}"),
+                    });
+            }
+            ProfileAction::Import => {
+                let imported = profiles::read_file(&self.profile_path)
+                    .and_then(|text| profiles::from_json(&text));
+                match imported {
+                    Ok((mut profile, settings)) => {
+                        profile.name = profiles::unique_name(&profile.name, &self.profile_names);
+                        self.profile_status = Some(format!("Imported profile {}.", profile.name));
+                        self.leave_profile(storage);
+                        self.open_profile(profile, settings, None);
+                    }
+                    Err(e) => {
+                        log::error!("{e}");
+                        self.profile_status = Some(e);
+                    }
+                }
+            }
+        }
+    }
+
+    fn profile_menu(&mut self, ui: &mut egui::Ui) -> Option<ProfileAction> {
+        let mut action: Option<ProfileAction> = None;
+
+        self.profile_names.iter().for_each(|name| {
+            if ui
+                .selectable_label(*name == self.active_profile, name)
+                .clicked()
+                && *name != self.active_profile
+            {
+                action = Some(ProfileAction::Switch(name.clone()));
+            }
+        });
+
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.new_profile_name)
+                    .hint_text("New profile")
+                    .desired_width(120.0),
+            );
+            if ui
+                .add_enabled(
+                    !self.new_profile_name.trim().is_empty(),
+                    egui::Button::new("Create"),
+                )
+                .clicked()
+            {
+                action = Some(ProfileAction::Create);
+            }
+        });
+        if ui
+            .add_enabled(
+                self.profile_names.len() > 1,
+                egui::Button::new(format!("Delete {}", self.active_profile)),
+            )
+            .clicked()
+        {
+            action = Some(ProfileAction::Delete);
+        }
+
+        ui.separator();
+
+        let today = chrono::Local::now().date_naive();
+        let goals = &mut self.profile.goals;
+        egui::Grid::new("goals_grid").num_columns(2).show(ui, |ui| {
+            ui.label("Daily minutes:");
+            ui.add(egui::DragValue::new(&mut goals.daily_minutes).range(1..=600));
+            ui.end_row();
+            ui.label("Weekly sessions:");
+            ui.add(egui::DragValue::new(&mut goals.weekly_sessions).range(1..=50));
+            ui.end_row();
+        });
+        ui.checkbox(&mut goals.count_manual, "Count logged practice")
+            .on_hover_text("Include practice logged by hand in the goals and statistics.");
+        ui.label(format!(
+            "Today: {} of {} minutes.",
+            self.profile.working_seconds_on(today) / 60,
+            self.profile.goals.daily_minutes
+        ));
+        ui.label(format!(
+            "This week: {} of {} sessions.",
+            self.profile.sessions_in_week_of(today),
+            self.profile.goals.weekly_sessions
+        ));
+        ui.label(match self.profile.streak(today) {
+            1 => "Streak: 1 day.".to_string(),
+            days => format!("Streak: {days} days."),
+        });
+
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            ui.label("File:");
+            ui.text_edit_singleline(&mut self.profile_path);
+        });
+        ui.horizontal(|ui| {
+            if ui.button("Export").clicked() {
+                action = Some(ProfileAction::Export);
+            }
+            if ui.button("Import").clicked() {
+                action = Some(ProfileAction::Import);
+            }
+        });
+        if let Some(status) = &self.profile_status {
+            ui.label(status);
+        }
+
+        action
+    }
+
+    // Anything that is not a whole number leaves the key selection random.
+    fn apply_seed(&mut self) {
+        let seed: Option<u64> = self.settings.seed_text.trim().parse().ok();
+        self.session.session_data.set_seed(seed);
+    }
+
+    fn handle_shortcuts(&mut self, ctx: &egui::Context) {
+        if let Some(action) = self.rebinding {
+            if let Some(key) = first_pressed_key(ctx) {
+                self.settings.key_bindings.bind(action, key);
+                self.rebinding = None;
+            }
+            return;
         }
 
-        Default::default()
+        if ctx.input(|i| i.key_pressed(egui::Key::F1)) {
+            self.show_shortcuts = !self.show_shortcuts;
+        }
+
+        // Leave the keyboard to text fields while they are being edited.
+        if ctx.wants_keyboard_input() {
+            return;
+        }
+
+        // Redo first, Ctrl+Z alone would also match Ctrl+Shift+Z.
+        if ctx.input_mut(|i| i.consume_shortcut(&REDO_SHORTCUT)) {
+            self.session.redo();
+        } else if ctx.input_mut(|i| i.consume_shortcut(&UNDO_SHORTCUT)) {
+            self.session.undo();
+        }
+
+        if let Some(action) = self.settings.key_bindings.pressed_action(ctx) {
+            self.session.apply(action.command());
+        }
+    }
+
+    fn shortcuts_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_shortcuts;
+        egui::Window::new("Keyboard Shortcuts")
+            .open(&mut open)
+            .resizable(false)
+            .collapsible(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                egui::Grid::new("shortcuts_grid")
+                    .num_columns(3)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ShortcutAction::ALL.iter().for_each(|action| {
+                            ui.label(action.label());
+                            if self.rebinding == Some(*action) {
+                                ui.label("Press a key...");
+                                if ui.button("Cancel").clicked() {
+                                    self.rebinding = None;
+                                }
+                            } else {
+                                match self.settings.key_bindings.key(*action) {
+                                    Some(key) => ui.monospace(key.name()),
+                                    None => ui.weak("Unbound"),
+                                };
+                                if ui.button("Rebind").clicked() {
+                                    self.rebinding = Some(*action);
+                                }
+                            }
+                            ui.end_row();
+                        });
+                    });
+
+                ui.separator();
+                egui::Grid::new("undo_shortcuts_grid")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Undo");
+                        ui.monospace(ctx.format_shortcut(&UNDO_SHORTCUT));
+                        ui.end_row();
+                        ui.label("Redo");
+                        ui.monospace(ctx.format_shortcut(&REDO_SHORTCUT));
+                        ui.end_row();
+                    });
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.label("Press F1 to show or hide this window.");
+                    if ui.button("Reset to defaults").clicked() {
+                        self.settings.key_bindings = KeyBindings::default();
+                        self.rebinding = None;
+                    }
+                });
+            });
+        self.show_shortcuts = open;
     }
 }
 
-impl eframe::App for PracticeSessionState {
+impl eframe::App for JovianCyclesApp {
     /// Called each time the UI needs repainting, which may be many times per second.
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        self.handle_shortcuts(ctx);
+        let played_midi = self.settings.midi.poll(&mut self.session);
+        let played_mic = self.settings.mic.poll(&self.session);
+        self.detect_idle(ctx, played_midi || played_mic);
+        if self.settings.midi.is_connected() || self.settings.mic.is_listening() {
+            // MIDI messages and audio input do not wake egui up on their own.
+            ctx.request_repaint_after(std::time::Duration::from_millis(50));
+        }
+        self.record_finished_sessions();
+        self.settings.sync.poll(ctx, &mut self.profile);
+        self.score_weakness();
+        if !matches!(self.session.session_state, SessionStates::Waiting) {
+            // Keep the timers running.
+            ctx.request_repaint_after(std::time::Duration::from_secs(1));
+        }
+
+        let mut profile_action: Option<ProfileAction> = None;
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
             egui::menu::bar(ui, |ui| {
                 // NOTE: no File->Quit on web pages!
@@ -47,38 +865,126 @@ impl eframe::App for PracticeSessionState {
                 if ui.button("Organize windows").clicked() {
                     ui.ctx().memory_mut(|mem| mem.reset_areas());
                 };
+
+                ui.menu_button(format!("👤 {}", self.active_profile), |ui| {
+                    profile_action = self.profile_menu(ui);
+                });
+
+                ui.toggle_value(&mut self.show_shortcuts, "⌨ Shortcuts");
+                ui.toggle_value(&mut self.show_midi, "🎹 MIDI");
+                ui.toggle_value(&mut self.show_mic, "🎤 Microphone");
+                ui.toggle_value(&mut self.show_tuner, "🎵 Tuner");
+                ui.toggle_value(&mut self.show_export, "💾 Export");
+                ui.toggle_value(&mut self.show_assignments, "📋 Assignments");
+                ui.toggle_value(&mut self.show_sync, "🔄 Sync");
+                ui.toggle_value(&mut self.show_log, "📝 Log practice");
+                ui.toggle_value(&mut self.show_weakest, "💡 Weakest keys");
             });
         });
 
-        egui::CentralPanel::default().show(ctx, |ui| {
+        self.idle_banner(ctx);
+        self.interrupted_session_window(ctx);
+
+        if let Some(action) = profile_action {
+            self.handle_profile_action(action, frame.storage_mut());
+        }
+
+        self.shortcuts_window(ctx);
+
+        egui::Window::new("MIDI Input")
+            .open(&mut self.show_midi)
+            .resizable(false)
+            .default_pos(egui::Pos2 { x: 420.0, y: 40.0 })
+            .show(ctx, |ui| {
+                self.settings.midi.window_contents(ui, &self.session);
+            });
+
+        egui::Window::new("Microphone Input")
+            .open(&mut self.show_mic)
+            .resizable(false)
+            .default_pos(egui::Pos2 { x: 720.0, y: 40.0 })
+            .show(ctx, |ui| {
+                self.settings
+                    .mic
+                    .window_contents(ui, &self.session, self.settings.midi.scale_kind);
+            });
+
+        egui::Window::new("Tuner")
+            .open(&mut self.show_tuner)
+            .resizable(false)
+            .default_pos(egui::Pos2 { x: 720.0, y: 300.0 })
+            .show(ctx, |ui| {
+                self.settings.mic.tuner_contents(ui, &self.session);
+            });
+
+        egui::Window::new("Export MIDI File")
+            .open(&mut self.show_export)
+            .resizable(false)
+            .default_pos(egui::Pos2 { x: 420.0, y: 300.0 })
+            .show(ctx, |ui| {
+                self.settings.export.window_contents(
+                    ui,
+                    &self.session,
+                    &self.settings.midi,
+                    self.settings.tempo_bpm,
+                );
+            });
+
+        egui::Window::new("Assignments")
+            .open(&mut self.show_assignments)
+            .resizable(false)
+            .default_pos(egui::Pos2 { x: 720.0, y: 460.0 })
+            .show(ctx, |ui| {
+                self.settings
+                    .assignments
+                    .window_contents(ui, &mut self.session, &mut self.profile);
+            });
+
+        egui::Window::new("Sync")
+            .open(&mut self.show_sync)
+            .resizable(false)
+            .default_pos(egui::Pos2 { x: 420.0, y: 460.0 })
+            .show(ctx, |ui| {
+                self.settings.sync.window_contents(ui, &mut self.profile);
+            });
+
+        egui::Window::new("Log Past Practice")
+            .open(&mut self.show_log)
+            .resizable(false)
+            .default_pos(egui::Pos2 { x: 1020.0, y: 40.0 })
+            .show(ctx, |ui| {
+                if self.practice_log.window_contents(
+                    ui,
+                    &self.session.note_name_list,
+                    &mut self.profile,
+                ) {
+                    self.settings.sync.sync_soon();
+                }
+            });
+
+        egui::Window::new("Weakest Keys")
+            .open(&mut self.show_weakest)
+            .resizable(false)
+            .default_pos(egui::Pos2 {
+                x: 1020.0,
+                y: 300.0,
+            })
+            .show(ctx, |ui| {
+                weakest::window_contents(ui, &self.session);
+            });
+
+        egui::CentralPanel::default().show(ctx, |_ui| {
             egui::Window::new("Practice Report")
                 .resizable([true, true])
                 .default_width(100.0)
-                .default_pos(egui::Pos2 {x: 15.0, y: 275.0})
+                .default_pos(egui::Pos2 { x: 15.0, y: 275.0 })
                 .show(ctx, |ui| {
-                    egui::Grid::new("center_pane")
-                        .min_col_width(80.0)
-                        .max_col_width(100.0)
-                        .show(ui, |ui| {
-                            ui.label("Key Name");
-                            ui.label("Key Repetitions");
-                            ui.label("Duration");
-                            ui.end_row();
-                            match self.session_data.receipt.clone() {
-                                None => {}
-                                Some(receipt) => match receipt.key_data_archive.clone() {
-                                    None => {}
-                                    Some(history) => {
-                                        history.0.iter().for_each(|key| {
-                                            ui.label(format!("{}", self.note_name_list[key.nid]));
-                                            ui.label(format!("{}", key.repetitions));
-                                            ui.label(format!("{}", "TBA..."));
-                                            ui.end_row();
-                                        });
-                                    }
-                                },
-                            };
-                        });
+                    if self
+                        .report
+                        .window_contents(ui, &self.session, &mut self.profile)
+                    {
+                        self.settings.sync.sync_soon();
+                    }
                 });
 
             egui::Window::new("Session Controls")
@@ -86,63 +992,92 @@ impl eframe::App for PracticeSessionState {
                 .resizable([true, true])
                 .default_width(100.0)
                 .max_height(150.0)
-                .default_pos(egui::Pos2 {x: 15.0, y: 40.0})
+                .default_pos(egui::Pos2 { x: 15.0, y: 40.0 })
                 .show(ctx, |ui| {
                     ui.vertical_centered_justified(|ui| {
                         ui.with_layout(egui::Layout::top_down_justified(Align::LEFT), |ui| {
-                            match self.session_data.current_key_data {
+                            match self.session.session_data.current_key_data {
                                 Some(data) => {
                                     ui.heading(format!(
                                         "Your current key is: {}",
-                                        self.note_name_list[data.nid].clone()
+                                        self.session.note_name_list[data.nid].clone()
                                     ));
                                 }
                                 None => {
                                     ui.heading("No current key.");
                                 }
                             }
+                            if let Some(title) = &self.session.session_data.assignment {
+                                ui.label(format!("Assignment: {title}"));
+                            }
+                            self.rating(ui);
+                            self.skip_reason(ui);
+                            self.rest_timer(ui);
                         });
 
+                        ui.horizontal(|ui| {
+                            ui.label("Seed:");
+                            let seed_input =
+                                egui::TextEdit::singleline(&mut self.settings.seed_text)
+                                    .hint_text("random")
+                                    .desired_width(120.0);
+                            if ui.add(seed_input).changed() {
+                                self.apply_seed();
+                            }
+
+                            ui.label("Tempo:");
+                            ui.add(
+                                egui::DragValue::new(&mut self.settings.tempo_bpm)
+                                    .range(20.0..=300.0)
+                                    .suffix(" bpm"),
+                            );
+                        });
+
+                        ui.horizontal(|ui| {
+                            ui.label("Auto-pause after:");
+                            ui.add(
+                                egui::DragValue::new(&mut self.settings.idle_minutes)
+                                    .range(0..=60)
+                                    .suffix(" min"),
+                            )
+                            .on_hover_text(
+                                "Pause when nothing is pressed or played, or the window stays in \
+                                 the background, this long. 0 for never.",
+                            );
+
+                            ui.label("Rest cap:");
+                            ui.add(
+                                egui::DragValue::new(&mut self.settings.rest_cap_minutes)
+                                    .range(0..=60)
+                                    .suffix(" min"),
+                            )
+                            .on_hover_text(
+                                "Remind me to resume after resting this long, 0 for never.",
+                            );
+                        });
+
+                        self.settings.playback.controls(
+                            ui,
+                            &self.session,
+                            &self.settings.midi,
+                            self.settings.tempo_bpm,
+                        );
+
+                        self.key_controls(ui);
+
                         egui::Grid::new("center_pane")
                             .min_col_width(120.0)
                             .max_col_width(150.0)
                             .show(ui, |ui| {
-                                let mut working_button_on = false;
-                                let mut resting_button_on = false;
-                                let mut skip_button_on = false;
-                                match self.session_state {
-                                    SessionStates::RequestingNewKey => {
-                                        working_button_on = true;
-                                        resting_button_on = false;
-                                        skip_button_on = false;
-                                    }
-                                    SessionStates::Working => {
-                                        working_button_on = false;
-                                        resting_button_on = true;
-                                        skip_button_on = true;
-                                    }
-                                    SessionStates::Resting => {
-                                        working_button_on = true;
-                                        resting_button_on = false;
-                                        skip_button_on = true;
-                                    }
-                                    SessionStates::Waiting => {
-                                        working_button_on = false;
-                                        resting_button_on = false;
-                                        skip_button_on = false;
-                                    }
-                                    _ => {}
-                                };
+                                let working_button_on = self.session.can_resume();
+                                let resting_button_on = self.session.can_pause();
+                                let skip_button_on = self.session.can_skip();
 
                                 ui.with_layout(
                                     egui::Layout::top_down_justified(Align::LEFT),
                                     |ui| {
                                         if ui.button("Request New Key").clicked() {
-                                            self.to_requesting_new_key();
-                                            match_states(self);
-
-                                            self.to_working();
-                                            match_states(self);
+                                            self.session.apply(SessionCommand::NewKey);
                                         }
                                     },
                                 );
@@ -158,14 +1093,7 @@ impl eframe::App for PracticeSessionState {
                                             .clicked()
                                         {
                                             if skip_button_on {
-                                                self.to_skipping_key();
-                                                match_states(self);
-
-                                                self.to_requesting_new_key();
-                                                match_states(self);
-
-                                                self.to_working();
-                                                match_states(self);
+                                                self.session.apply(SessionCommand::Skip);
                                             } else {
                                                 debug!(
                                                     "Button not currently functional in this state"
@@ -188,8 +1116,7 @@ impl eframe::App for PracticeSessionState {
                                             .clicked()
                                         {
                                             if resting_button_on {
-                                                self.to_resting();
-                                                match_states(self);
+                                                self.session.apply(SessionCommand::Pause);
                                             } else {
                                                 debug!(
                                                     "Button not currently functional in this state"
@@ -210,8 +1137,7 @@ impl eframe::App for PracticeSessionState {
                                             .clicked()
                                         {
                                             if working_button_on {
-                                                self.to_working();
-                                                match_states(self);
+                                                self.session.apply(SessionCommand::Resume);
                                             } else {
                                                 debug!(
                                                     "Button not currently functional in this state"
@@ -227,14 +1153,32 @@ impl eframe::App for PracticeSessionState {
                                     egui::Layout::top_down_justified(Align::LEFT),
                                     |ui| {
                                         if ui.button("End Practice Session").clicked() {
-                                            self.to_finishing();
-                                            match_states(self);
+                                            self.session.apply(SessionCommand::Finish);
 
                                             // Send command to exit
                                             // ctx.send_viewport_cmd(egui::ViewportCommand::Close);
                                         }
                                     },
                                 );
+
+                                ui.horizontal(|ui| {
+                                    let undo = self.session.undo_stack.next_undo();
+                                    let redo = self.session.undo_stack.next_redo();
+                                    if ui
+                                        .add_enabled(undo.is_some(), egui::Button::new("⟲ Undo"))
+                                        .on_hover_text(undo.map_or("", |command| command.label()))
+                                        .clicked()
+                                    {
+                                        self.session.undo();
+                                    }
+                                    if ui
+                                        .add_enabled(redo.is_some(), egui::Button::new("⟳ Redo"))
+                                        .on_hover_text(redo.map_or("", |command| command.label()))
+                                        .clicked()
+                                    {
+                                        self.session.redo();
+                                    }
+                                });
                             });
 
                         ui.separator();
@@ -254,9 +1198,11 @@ impl eframe::App for PracticeSessionState {
         });
     }
 
-    /// Called by the framework to save state before shutdown.
+    /// Called by the framework to save state before shutdown, and every 30 seconds in between,
+    /// which also checkpoints a running session.
     fn save(&mut self, storage: &mut dyn eframe::Storage) {
         eframe::set_value(storage, eframe::APP_KEY, self);
+        self.save_profile(storage);
     }
 }
 
@@ -0,0 +1,210 @@
+use crate::export;
+use crate::profiles::read_file;
+use jovian_cycles_core::assignment::{AssignmentReport, PlanFormat, PracticePlan};
+use jovian_cycles_core::{PracticeSessionState, Profile, SessionStates};
+
+use serde::{Deserialize, Serialize};
+
+/*
+ * Assignments
+ *
+ * Imports practice plans written by a teacher into the active profile and starts sessions for
+ * their assignments. Sessions started without an assignment are the student's own practice and
+ * use the weights of the profile.
+ */
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct AssignmentsState {
+    pub plan_path: String,
+    pub report_path: String,
+    #[serde(skip)]
+    status: Option<String>,
+}
+
+impl Default for AssignmentsState {
+    fn default() -> Self {
+        AssignmentsState {
+            plan_path: "plan.toml".to_string(),
+            report_path: "jovian-cycles-report.json".to_string(),
+            status: None,
+        }
+    }
+}
+
+impl AssignmentsState {
+    fn import_plan(&mut self, profile: &mut Profile) {
+        let plan = read_file(&self.plan_path).and_then(|text| {
+            let format = PlanFormat::from_path(&self.plan_path)
+                .ok_or_else(|| "Practice plans must be .json or .toml files.".to_string())?;
+            PracticePlan::parse(&text, format).map_err(|e| e.to_string())
+        });
+
+        self.status = Some(match plan {
+            Ok(plan) => {
+                let message = format!(
+                    "Imported {} assignment(s) from {}.",
+                    plan.assignments.len(),
+                    plan.title
+                );
+                profile.assign(plan.assignments);
+                message
+            }
+            Err(e) => {
+                log::error!("{e}");
+                e
+            }
+        });
+    }
+
+    fn export_report(&mut self, profile: &Profile) {
+        self.status = Some(match AssignmentReport::new(profile).to_json() {
+            Ok(json) => export::save(&self.report_path, json.as_bytes()),
+            Err(e) => format!("Unable to export the report: {e}"),
+        });
+    }
+
+    pub fn window_contents(
+        &mut self,
+        ui: &mut egui::Ui,
+        session: &mut PracticeSessionState,
+        profile: &mut Profile,
+    ) {
+        // Switching assignments in the middle of a session would mislabel its receipt.
+        let idle = matches!(session.session_state, SessionStates::Waiting);
+        let today = chrono::Local::now().date_naive();
+
+        match session.session_data.assignment.clone() {
+            Some(title) => {
+                ui.horizontal(|ui| {
+                    ui.strong(format!("Practicing: {title}"));
+                    if ui
+                        .add_enabled(idle, egui::Button::new("Stop"))
+                        .on_disabled_hover_text("Finish the current session first.")
+                        .clicked()
+                    {
+                        profile.stop_assignment(&mut session.session_data);
+                    }
+                });
+            }
+            None => {
+                ui.label("Practicing on your own.");
+            }
+        }
+
+        ui.separator();
+
+        if profile.assignments.is_empty() {
+            ui.weak("No assignments yet. Import a plan from your teacher.");
+        }
+
+        let mut started: Option<usize> = None;
+        let mut removed: Option<usize> = None;
+        egui::Grid::new("assignments_grid")
+            .num_columns(4)
+            .striped(true)
+            .show(ui, |ui| {
+                profile
+                    .assignments
+                    .iter()
+                    .enumerate()
+                    .for_each(|(index, assignment)| {
+                        ui.vertical(|ui| {
+                            ui.strong(&assignment.title);
+                            let keys: Vec<&str> = assignment
+                                .pool_keys()
+                                .unwrap_or_default()
+                                .iter()
+                                .map(|key| session.note_name_list[*key].as_str())
+                                .collect();
+                            ui.label(format!(
+                                "{} ({}), {} min per session",
+                                keys.join(" "),
+                                assignment.strategy.label().to_lowercase(),
+                                assignment.minutes
+                            ));
+                            if !assignment.teacher.is_empty() {
+                                ui.weak(format!("From {}", assignment.teacher));
+                            }
+                            if !assignment.notes.is_empty() {
+                                ui.weak(&assignment.notes);
+                            }
+                        });
+
+                        let completed = assignment.completed_sessions(&profile.history);
+                        ui.vertical(|ui| {
+                            let progress =
+                                format!("{} / {} sessions", completed, assignment.sessions);
+                            if completed >= assignment.sessions {
+                                ui.colored_label(egui::Color32::GREEN, progress);
+                            } else {
+                                ui.label(progress);
+                            }
+                            match assignment.due {
+                                Some(due) if due < today && completed < assignment.sessions => {
+                                    ui.colored_label(
+                                        egui::Color32::RED,
+                                        format!("Overdue ({due})"),
+                                    );
+                                }
+                                Some(due) => {
+                                    ui.label(format!("Due {due}"));
+                                }
+                                None => {}
+                            }
+                        });
+
+                        if ui
+                            .add_enabled(idle, egui::Button::new("Start"))
+                            .on_disabled_hover_text("Finish the current session first.")
+                            .clicked()
+                        {
+                            started = Some(index);
+                        }
+                        // The running session keeps the keys of its assignment until it finishes.
+                        let practicing = session.session_data.assignment.as_deref()
+                            == Some(assignment.title.as_str());
+                        if ui
+                            .add_enabled(idle || !practicing, egui::Button::new("Remove"))
+                            .on_disabled_hover_text("Finish the current session first.")
+                            .clicked()
+                        {
+                            removed = Some(index);
+                        }
+                        ui.end_row();
+                    });
+            });
+
+        if let Some(index) = started {
+            if let Err(e) = profile.start_assignment(index, &mut session.session_data) {
+                self.status = Some(e.to_string());
+            }
+        }
+        if let Some(index) = removed {
+            let assignment = profile.assignments.remove(index);
+            if session.session_data.assignment.as_deref() == Some(&assignment.title) {
+                profile.stop_assignment(&mut session.session_data);
+            }
+        }
+
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            ui.label("Plan:");
+            ui.text_edit_singleline(&mut self.plan_path);
+            if ui.button("Import").clicked() {
+                self.import_plan(profile);
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Report:");
+            ui.text_edit_singleline(&mut self.report_path);
+            if ui.button("Export").clicked() {
+                self.export_report(profile);
+            }
+        });
+
+        if let Some(status) = &self.status {
+            ui.label(status);
+        }
+    }
+}
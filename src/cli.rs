@@ -0,0 +1,151 @@
+use jovian_cycles_core::replay::{parse_script, replay as replay_script};
+use jovian_cycles_core::statistics::format_duration;
+use jovian_cycles_core::{PracticeSessionState, Receipt};
+
+use chrono::DateTime;
+use color_eyre::eyre::{Result, WrapErr};
+use std::io::{self, BufRead, Write};
+
+/*
+ * Headless Mode
+ *
+ * Runs the practice session state machine in the terminal. Commands are read one line at a time
+ * and only the first character of each line is considered.
+ */
+pub fn run(seed: Option<u64>) -> io::Result<()> {
+    let mut state = PracticeSessionState::default();
+    state.session_data.set_seed(seed);
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+
+    print_help();
+
+    loop {
+        print_status(&state);
+        print!("> ");
+        stdout.flush()?;
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            // Treat end of input as the end of the practice session.
+            state.finish();
+            break;
+        }
+
+        match line.trim().chars().next() {
+            Some('n') => state.request_new_key(),
+            Some('s') => {
+                if state.can_skip() {
+                    state.skip_key();
+                } else {
+                    println!("There is no key to skip yet.");
+                }
+            }
+            Some('p') => {
+                if state.can_pause() {
+                    state.pause();
+                } else {
+                    println!("The session can only be paused while working.");
+                }
+            }
+            Some('r') => {
+                if state.can_resume() {
+                    state.resume();
+                } else {
+                    println!("The session can only be resumed while paused.");
+                }
+            }
+            Some('q') => {
+                state.finish();
+                break;
+            }
+            Some('h') | Some('?') => print_help(),
+            None => {}
+            Some(c) => println!("Unknown command '{c}'. Type 'h' for help."),
+        }
+    }
+
+    match &state.session_data.receipt {
+        Some(receipt) => print_receipt(&state.note_name_list, receipt),
+        None => println!("No receipt found."),
+    }
+
+    Ok(())
+}
+
+// Replays the script at `path` and prints the receipt it produces.
+pub fn replay(path: &str) -> Result<()> {
+    let script =
+        std::fs::read_to_string(path).wrap_err_with(|| format!("Unable to read '{path}'."))?;
+    let receipt = replay_script(&parse_script(&script)?)?;
+
+    print_receipt(&PracticeSessionState::default().note_name_list, &receipt);
+
+    Ok(())
+}
+
+fn print_help() {
+    println!("Commands:");
+    println!("  n  request a new key");
+    println!("  s  skip the selected key");
+    println!("  p  pause the practice session");
+    println!("  r  resume the practice session");
+    println!("  q  end the practice session and print the receipt");
+    println!("  h  show this help");
+}
+
+fn print_status(state: &PracticeSessionState) {
+    match state.session_data.current_key_data {
+        Some(data) => println!(
+            "Your current key is: {} ({:?})",
+            state.note_name_list[data.nid], state.session_state
+        ),
+        None => println!("No current key. ({:?})", state.session_state),
+    }
+}
+
+pub fn print_receipt(note_name_list: &[String], receipt: &Receipt) {
+    println!();
+    println!("Practice Report");
+    if let Some(seed) = receipt.seed {
+        println!("Seed: {seed}");
+    }
+    if let Some(assignment) = &receipt.assignment {
+        println!("Assignment: {assignment}");
+    }
+    println!(
+        "{:<10}{:>16}{:>10}{:>8}",
+        "Key Name", "Key Repetitions", "Duration", "Skips"
+    );
+    if let Some(keys) = &receipt.key_data_archive {
+        keys.0.iter().for_each(|key| {
+            println!(
+                "{:<10}{:>16}{:>10}{:>8}",
+                note_name_list[key.nid],
+                key.repetitions,
+                format_duration(key.working_seconds),
+                key.skips
+            );
+        });
+
+        let working: i64 = keys.0.iter().map(|key| key.working_seconds).sum();
+        println!();
+        println!("Active: {}", format_duration(working));
+        println!(
+            "Resting: {} ({} rest(s))",
+            format_duration(receipt.rests.iter().sum()),
+            receipt.rests.len()
+        );
+    }
+
+    if let Some(time_stamps) = &receipt.time_stamp_archive {
+        println!();
+        println!("Timeline");
+        time_stamps.iter().for_each(|(state_name, timestamp)| {
+            let time = DateTime::from_timestamp(*timestamp, 0)
+                .map(|dt| dt.format("%H:%M:%S").to_string())
+                .unwrap_or_else(|| timestamp.to_string());
+            println!("{time}  {state_name}");
+        });
+    }
+}
@@ -0,0 +1,133 @@
+use crate::midi_input::MidiState;
+use jovian_cycles_core::smf::{render_scale_sequence, ScaleSequence};
+use jovian_cycles_core::PracticeSessionState;
+
+use serde::{Deserialize, Serialize};
+
+/*
+ * MIDI File Export
+ *
+ * Renders the scales of a practice session as a Standard MIDI File, using the scale settings of
+ * the MIDI Input window.
+ */
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportSource {
+    /// Keys selected so far, or by the last finished session.
+    Session,
+    /// Keys the session is about to select.
+    Plan,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct ExportState {
+    pub source: ExportSource,
+    pub plan_length: usize,
+    pub drone: bool,
+    pub path: String,
+    #[serde(skip)]
+    status: Option<String>,
+}
+
+impl Default for ExportState {
+    fn default() -> Self {
+        ExportState {
+            source: ExportSource::Session,
+            plan_length: 12,
+            drone: false,
+            path: "jovian-cycles.mid".to_string(),
+            status: None,
+        }
+    }
+}
+
+impl ExportState {
+    fn keys(&self, session: &PracticeSessionState) -> Vec<usize> {
+        match self.source {
+            ExportSource::Session => {
+                let history = &session.session_data.key_history;
+                match &session.session_data.receipt {
+                    Some(receipt) if history.is_empty() => receipt.key_sequence.clone(),
+                    _ => history.clone(),
+                }
+            }
+            ExportSource::Plan => session.session_data.plan_keys(self.plan_length),
+        }
+    }
+
+    pub fn window_contents(
+        &mut self,
+        ui: &mut egui::Ui,
+        session: &PracticeSessionState,
+        midi: &MidiState,
+        bpm: f32,
+    ) {
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut self.source, ExportSource::Session, "Session keys");
+            ui.radio_value(&mut self.source, ExportSource::Plan, "Upcoming keys");
+            if self.source == ExportSource::Plan {
+                ui.add(egui::DragValue::new(&mut self.plan_length).range(1..=48));
+            }
+        });
+
+        let keys = self.keys(session);
+        let names: Vec<&str> = keys
+            .iter()
+            .map(|key| session.note_name_list[*key].as_str())
+            .collect();
+        if names.is_empty() {
+            ui.weak("No keys to export yet.");
+        } else {
+            ui.label(names.join(" "));
+        }
+
+        ui.checkbox(&mut self.drone, "Drone track");
+        ui.label(format!(
+            "{} scales, {} octave(s), {}, at {} bpm.",
+            midi.scale_kind.label(),
+            midi.octaves,
+            midi.direction.label().to_lowercase(),
+            bpm
+        ));
+
+        ui.horizontal(|ui| {
+            ui.label("File:");
+            ui.text_edit_singleline(&mut self.path);
+        });
+
+        if ui
+            .add_enabled(!keys.is_empty(), egui::Button::new("Export MIDI file"))
+            .clicked()
+        {
+            let bytes = render_scale_sequence(&ScaleSequence {
+                keys,
+                kind: midi.scale_kind,
+                direction: midi.direction,
+                octaves: midi.octaves,
+                bpm,
+                drone: self.drone,
+            });
+            self.status = Some(save(&self.path, &bytes));
+        }
+
+        if let Some(status) = &self.status {
+            ui.label(status);
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save(path: &str, bytes: &[u8]) -> String {
+    match std::fs::write(path, bytes) {
+        Ok(()) => format!("Saved {path}."),
+        Err(e) => {
+            log::error!("Unable to write '{path}': {e}");
+            format!("Unable to write {path}: {e}")
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn save(_path: &str, _bytes: &[u8]) -> String {
+    "Saving files is only available in the desktop app.".to_string()
+}
@@ -1,15 +1,70 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 
 mod app;
-mod models;
-mod transitions;
-mod utils;
+mod assignments;
+#[cfg(not(target_arch = "wasm32"))]
+mod cli;
+mod export;
+mod mic_input;
+mod midi_input;
+mod playback;
+mod practice_log;
+mod profiles;
+mod report;
+mod shortcuts;
+mod sync;
+#[cfg(not(target_arch = "wasm32"))]
+mod tui;
+mod weakest;
 
 // When compiling natively:
 #[cfg(not(target_arch = "wasm32"))]
 fn main() -> eframe::Result {
     env_logger::init(); // Log to stderr (if you run with `RUST_LOG=debug`).
 
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    // Select the same sequence of keys as every other session with this seed:
+    let seed: Option<u64> = match args.iter().position(|arg| arg == "--seed") {
+        Some(index) => match args.get(index + 1).map(|seed| seed.parse()) {
+            Some(Ok(seed)) => Some(seed),
+            _ => {
+                log::error!("--seed requires a non-negative whole number.");
+                return Ok(());
+            }
+        },
+        None => None,
+    };
+
+    // Run the practice session in the terminal instead of opening a window:
+    if args.iter().any(|arg| arg == "--cli") {
+        if let Err(e) = cli::run(seed) {
+            log::error!("Terminal session failed: {e}");
+        }
+        return Ok(());
+    }
+
+    // Run the practice session as a full-screen terminal UI:
+    if args.iter().any(|arg| arg == "--tui") {
+        if let Err(e) = tui::run(seed) {
+            log::error!("Terminal UI failed: {e}");
+        }
+        return Ok(());
+    }
+
+    // Replay a scripted session and print its receipt:
+    if let Some(index) = args.iter().position(|arg| arg == "--replay") {
+        match args.get(index + 1) {
+            Some(path) => {
+                if let Err(e) = cli::replay(path) {
+                    log::error!("Replay failed: {e:?}");
+                }
+            }
+            None => log::error!("--replay requires the path to a script."),
+        }
+        return Ok(());
+    }
+
     let native_options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_inner_size([800.0, 600.0])
@@ -24,7 +79,7 @@ fn main() -> eframe::Result {
     eframe::run_native(
         "jovian cycles",
         native_options,
-        Box::new(|cc| Ok(Box::new(transitions::PracticeSessionState::new(cc)))),
+        Box::new(move |cc| Ok(Box::new(app::JovianCyclesApp::new(cc).with_seed(seed)))),
     )
 }
 
@@ -41,7 +96,7 @@ fn main() {
             .start(
                 "the_canvas_id",
                 web_options,
-                Box::new(|cc| Ok(Box::new(transitions::PracticeSessionState::new(cc)))),
+                Box::new(|cc| Ok(Box::new(app::JovianCyclesApp::new(cc).with_seed(None)))),
             )
             .await;
 
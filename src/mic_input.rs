@@ -0,0 +1,358 @@
+use crate::midi_input::key_indicator;
+use jovian_cycles_core::pitch::{detect_pitch, nearest_midi_note, tune, PitchEstimate};
+use jovian_cycles_core::scales::{is_in_key, pitch_class, ScaleKind};
+use jovian_cycles_core::PracticeSessionState;
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/*
+ * Microphone Input
+ *
+ * Listens to an acoustic instrument and checks the detected notes against the current key, using
+ * the scale chosen in the MIDI Input window. A note only counts once it was detected in two
+ * analysis windows in a row, which keeps attacks and string noise out of the indicator. The same
+ * detections drive the tuner. Listening requires the `audio` feature.
+ */
+
+// Samples analysed at once; at 48 kHz this reaches down to about 47 Hz.
+const WINDOW: usize = 2048;
+// Samples received between two analyses.
+const HOP: usize = 1024;
+// Detections less periodic than this are ignored.
+const MIN_CLARITY: f32 = 0.9;
+// Number of detected notes kept for the in key indicator.
+const RECENT_NOTES: usize = 16;
+// Deviation in cents that still counts as in tune.
+const IN_TUNE_CENTS: f32 = 5.0;
+
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
+pub struct MicState {
+    /// Reference frequency of A4 for both note detection and the tuner.
+    pub a4_frequency: f32,
+    #[serde(skip)]
+    samples: VecDeque<f32>,
+    #[serde(skip)]
+    sample_rate: u32,
+    #[serde(skip)]
+    unanalysed: usize,
+    #[serde(skip)]
+    estimate: Option<PitchEstimate>,
+    #[serde(skip)]
+    candidate: Option<u8>,
+    #[serde(skip)]
+    note: Option<u8>,
+    #[serde(skip)]
+    recent_notes: VecDeque<u8>,
+    #[serde(skip)]
+    last_key: Option<usize>,
+    #[cfg(feature = "audio")]
+    #[serde(skip)]
+    error: Option<String>,
+    #[cfg(feature = "audio")]
+    #[serde(skip)]
+    microphone: Option<device::Microphone>,
+}
+
+impl Default for MicState {
+    fn default() -> Self {
+        MicState {
+            a4_frequency: 440.0,
+            samples: VecDeque::new(),
+            sample_rate: 0,
+            unanalysed: 0,
+            estimate: None,
+            candidate: None,
+            note: None,
+            recent_notes: VecDeque::new(),
+            last_key: None,
+            #[cfg(feature = "audio")]
+            error: None,
+            #[cfg(feature = "audio")]
+            microphone: None,
+        }
+    }
+}
+
+impl MicState {
+    pub fn is_listening(&self) -> bool {
+        #[cfg(feature = "audio")]
+        return self.microphone.is_some();
+
+        #[cfg(not(feature = "audio"))]
+        return false;
+    }
+
+    // Analyses the samples recorded since the last frame and tells whether a note is sounding.
+    pub fn poll(&mut self, session: &PracticeSessionState) -> bool {
+        #[cfg(feature = "audio")]
+        if let Some(microphone) = &self.microphone {
+            let (samples, sample_rate) = (microphone.samples(), microphone.sample_rate);
+            self.push_samples(&samples, sample_rate);
+        }
+
+        // Notes played for the previous key say nothing about the new one.
+        let current_key = session.session_data.current_key_data.map(|data| data.nid);
+        if current_key != self.last_key {
+            self.recent_notes.clear();
+            self.last_key = current_key;
+        }
+
+        self.note.is_some()
+    }
+
+    // Keeps the last window of samples and analyses it every hop.
+    #[cfg_attr(not(feature = "audio"), allow(dead_code))]
+    fn push_samples(&mut self, samples: &[f32], sample_rate: u32) {
+        self.sample_rate = sample_rate;
+        samples.iter().for_each(|sample| {
+            if self.samples.len() == WINDOW {
+                self.samples.pop_front();
+            }
+            self.samples.push_back(*sample);
+            self.unanalysed += 1;
+
+            if self.samples.len() == WINDOW && self.unanalysed >= HOP {
+                self.unanalysed = 0;
+                self.analyse();
+            }
+        });
+    }
+
+    #[cfg_attr(not(feature = "audio"), allow(dead_code))]
+    fn analyse(&mut self) {
+        let window: Vec<f32> = self.samples.iter().copied().collect();
+        self.estimate = detect_pitch(&window, self.sample_rate)
+            .filter(|estimate| estimate.clarity >= MIN_CLARITY);
+
+        let detected = self
+            .estimate
+            .and_then(|estimate| nearest_midi_note(estimate.frequency, self.a4_frequency));
+        if detected == self.candidate && detected != self.note {
+            self.note = detected;
+            if let Some(note) = detected {
+                if self.recent_notes.len() == RECENT_NOTES {
+                    self.recent_notes.pop_front();
+                }
+                self.recent_notes.push_back(note);
+            }
+        }
+        self.candidate = detected;
+    }
+
+    #[cfg_attr(not(feature = "audio"), allow(dead_code))]
+    fn reset(&mut self) {
+        self.samples.clear();
+        self.unanalysed = 0;
+        self.estimate = None;
+        self.candidate = None;
+        self.note = None;
+    }
+
+    pub fn window_contents(
+        &mut self,
+        ui: &mut egui::Ui,
+        session: &PracticeSessionState,
+        kind: ScaleKind,
+    ) {
+        self.device_controls(ui);
+
+        ui.separator();
+
+        match (self.estimate, self.note) {
+            (Some(estimate), Some(note)) => {
+                ui.label(format!(
+                    "Hearing {} ({:.1} Hz).",
+                    session.note_name_list[pitch_class(note)],
+                    estimate.frequency
+                ));
+            }
+            _ => {
+                ui.weak("No pitch detected.");
+            }
+        }
+
+        key_indicator(ui, session, kind, &self.recent_notes);
+
+        // Pitch classes heard for the current key, coloured by whether they belong to it.
+        ui.horizontal(|ui| {
+            ui.spacing_mut().item_spacing.x = 4.0;
+            (0..12).for_each(|class| {
+                let name = &session.note_name_list[class];
+                let heard = self
+                    .recent_notes
+                    .iter()
+                    .any(|note| pitch_class(*note) == class);
+                match (heard, session.session_data.current_key_data) {
+                    (false, _) => {
+                        ui.weak(name);
+                    }
+                    (true, Some(data)) if !is_in_key(data.nid, kind, class as u8) => {
+                        ui.colored_label(egui::Color32::RED, name);
+                    }
+                    (true, _) => {
+                        ui.colored_label(egui::Color32::GREEN, name);
+                    }
+                }
+            });
+        });
+    }
+
+    pub fn tuner_contents(&mut self, ui: &mut egui::Ui, session: &PracticeSessionState) {
+        self.device_controls(ui);
+
+        ui.separator();
+
+        let reading = self
+            .estimate
+            .and_then(|estimate| tune(estimate.frequency, self.a4_frequency));
+        match reading {
+            Some(reading) => {
+                ui.heading(format!(
+                    "{}{}",
+                    session.note_name_list[pitch_class(reading.note)],
+                    reading.note as i32 / 12 - 1
+                ));
+                ui.label(format!(
+                    "{:.1} Hz, {:+.0} cents",
+                    reading.frequency, reading.cents
+                ));
+            }
+            None => {
+                ui.heading("-");
+                ui.weak("Play a single note to tune it.");
+            }
+        }
+        cents_meter(ui, reading.map(|reading| reading.cents));
+
+        ui.horizontal(|ui| {
+            ui.label("A4:");
+            ui.add(
+                egui::DragValue::new(&mut self.a4_frequency)
+                    .range(415.0..=466.0)
+                    .speed(0.1)
+                    .suffix(" Hz"),
+            );
+            if ui.button("Reset").clicked() {
+                self.a4_frequency = 440.0;
+            }
+        });
+    }
+
+    #[cfg(feature = "audio")]
+    fn device_controls(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if self.microphone.is_some() {
+                ui.label("Listening to the default input device.");
+                if ui.button("Stop").clicked() {
+                    self.microphone = None;
+                    self.reset();
+                }
+            } else if ui.button("Start listening").clicked() {
+                match device::open() {
+                    Ok(microphone) => {
+                        self.microphone = Some(microphone);
+                        self.error = None;
+                    }
+                    Err(e) => {
+                        log::error!("{e}");
+                        self.error = Some(e);
+                    }
+                }
+            }
+        });
+
+        if let Some(error) = &self.error {
+            ui.colored_label(egui::Color32::RED, error);
+        }
+    }
+
+    #[cfg(not(feature = "audio"))]
+    fn device_controls(&mut self, ui: &mut egui::Ui) {
+        ui.weak("Rebuild with `--features audio` to listen to a microphone.");
+    }
+}
+
+// Needle between -50 and 50 cents, green while the note is in tune.
+fn cents_meter(ui: &mut egui::Ui, cents: Option<f32>) {
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(240.0, 24.0), egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    let visuals = ui.visuals();
+
+    painter.rect_filled(rect, 4.0, visuals.extreme_bg_color);
+    painter.vline(
+        rect.center().x,
+        rect.y_range(),
+        egui::Stroke::new(1.0_f32, visuals.weak_text_color()),
+    );
+
+    if let Some(cents) = cents {
+        let x = rect.center().x + rect.width() / 2.0 * (cents / 50.0).clamp(-1.0, 1.0);
+        let color = if cents.abs() <= IN_TUNE_CENTS {
+            egui::Color32::GREEN
+        } else {
+            egui::Color32::RED
+        };
+        painter.vline(x, rect.y_range(), egui::Stroke::new(3.0_f32, color));
+    }
+}
+
+#[cfg(feature = "audio")]
+mod device {
+    use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+    use std::sync::mpsc::{channel, Receiver};
+
+    // Recording stops when the microphone is dropped.
+    pub struct Microphone {
+        pub sample_rate: u32,
+        receiver: Receiver<Vec<f32>>,
+        _stream: cpal::Stream,
+    }
+
+    impl Microphone {
+        pub fn samples(&self) -> Vec<f32> {
+            self.receiver.try_iter().flatten().collect()
+        }
+    }
+
+    // Opens the default input device, mixing its channels down to mono.
+    pub fn open() -> Result<Microphone, String> {
+        let device = cpal::default_host()
+            .default_input_device()
+            .ok_or_else(|| "No audio input device was found.".to_string())?;
+        let config = device.default_input_config().map_err(|e| e.to_string())?;
+        if config.sample_format() != cpal::SampleFormat::F32 {
+            return Err(format!(
+                "The audio input uses an unsupported sample format ({:?}).",
+                config.sample_format()
+            ));
+        }
+
+        let channels = config.channels() as usize;
+        let sample_rate = config.sample_rate().0;
+        let (sender, receiver) = channel();
+        let stream = device
+            .build_input_stream(
+                &config.into(),
+                move |data: &[f32], _: &cpal::InputCallbackInfo| {
+                    let mono: Vec<f32> = data
+                        .chunks(channels)
+                        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+                        .collect();
+                    // The receiver only disappears together with the stream.
+                    let _ = sender.send(mono);
+                },
+                |e| log::error!("Audio input failed: {e}"),
+                None,
+            )
+            .map_err(|e| e.to_string())?;
+        stream.play().map_err(|e| e.to_string())?;
+
+        Ok(Microphone {
+            sample_rate,
+            receiver,
+            _stream: stream,
+        })
+    }
+}
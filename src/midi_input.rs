@@ -0,0 +1,518 @@
+use jovian_cycles_core::evaluation::{
+    evaluate, Evaluation, NoteEvent, RunDirection, ScaleExercise,
+};
+use jovian_cycles_core::midi::{trigger_for, MidiController, MidiMessage, MidiParser, MidiTrigger};
+use jovian_cycles_core::scales::{in_key_ratio, is_in_key, ScaleKind};
+use jovian_cycles_core::{PracticeSessionState, SessionCommand};
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/*
+ * MIDI Input
+ *
+ * Routes MIDI messages into the session: bound notes and pedals run session commands and every
+ * other note played is checked against the current key. Notes are also collected into runs that
+ * are graded against the expected scale once complete. Talking to devices requires the `midi`
+ * feature. On Linux and macOS the app can also open a virtual input port named "jovian-cycles",
+ * which is handy for manual testing, e.g. `amidi -p virtual -S "B0 40 7F"` after connecting it
+ * with `aconnect`.
+ */
+
+// Number of played notes kept for the in key indicator.
+#[cfg_attr(not(feature = "midi"), allow(dead_code))]
+const RECENT_NOTES: usize = 16;
+
+// A run that stops for this long is graded with whatever was played.
+const RUN_TIMEOUT: Duration = Duration::from_secs(2);
+
+const LEARNABLE_COMMANDS: [SessionCommand; 4] = [
+    SessionCommand::NewKey,
+    SessionCommand::Skip,
+    SessionCommand::TogglePause,
+    SessionCommand::Finish,
+];
+
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
+pub struct MidiState {
+    pub controller: MidiController,
+    pub scale_kind: ScaleKind,
+    pub direction: RunDirection,
+    pub octaves: u8,
+    pub count_correct_runs: bool,
+    #[serde(skip)]
+    run: Vec<NoteEvent>,
+    #[serde(skip)]
+    last_note_at: Option<Instant>,
+    #[serde(skip)]
+    last_evaluation: Option<Evaluation>,
+    #[serde(skip)]
+    parser: MidiParser,
+    #[serde(skip)]
+    recent_notes: VecDeque<u8>,
+    #[serde(skip)]
+    last_key: Option<usize>,
+    #[serde(skip)]
+    learning: Option<SessionCommand>,
+    #[serde(skip)]
+    error: Option<String>,
+    #[cfg(feature = "midi")]
+    #[serde(skip)]
+    connection: Option<device::MidiConnection>,
+    #[cfg(feature = "midi")]
+    #[serde(skip)]
+    port_names: Vec<String>,
+    #[cfg(feature = "midi")]
+    #[serde(skip)]
+    selected_port: String,
+}
+
+impl Default for MidiState {
+    fn default() -> Self {
+        MidiState {
+            controller: MidiController::default(),
+            scale_kind: ScaleKind::default(),
+            direction: RunDirection::default(),
+            octaves: 1,
+            count_correct_runs: true,
+            run: Vec::new(),
+            last_note_at: None,
+            last_evaluation: None,
+            parser: MidiParser::default(),
+            recent_notes: VecDeque::new(),
+            last_key: None,
+            learning: None,
+            error: None,
+            #[cfg(feature = "midi")]
+            connection: None,
+            #[cfg(feature = "midi")]
+            port_names: Vec::new(),
+            #[cfg(feature = "midi")]
+            selected_port: String::new(),
+        }
+    }
+}
+
+impl MidiState {
+    // Played notes are remembered and the commands of bound triggers are returned.
+    #[cfg_attr(not(feature = "midi"), allow(dead_code))]
+    pub fn receive(&mut self, timestamp_ms: u64, bytes: &[u8]) -> Vec<SessionCommand> {
+        let messages: Vec<MidiMessage> = self.parser.parse(bytes);
+        let mut commands: Vec<SessionCommand> = Vec::new();
+
+        messages.iter().for_each(|message| {
+            let command = self.controller.command_for(message);
+
+            if let Some(learning) = self.learning {
+                if let Some(trigger) = trigger_for(message) {
+                    self.controller.bindings.bind(trigger, learning);
+                    self.learning = None;
+                }
+                return;
+            }
+
+            match (command, message) {
+                (Some(command), _) => commands.push(command),
+                (None, MidiMessage::NoteOn { note, .. }) => {
+                    if self.recent_notes.len() == RECENT_NOTES {
+                        self.recent_notes.pop_front();
+                    }
+                    self.recent_notes.push_back(*note);
+                    self.run.push(NoteEvent {
+                        timestamp_ms,
+                        note: *note,
+                    });
+                    self.last_note_at = Some(Instant::now());
+                }
+                _ => {}
+            }
+        });
+
+        commands
+    }
+
+    // Applies everything received from the connected device since the last frame and tells
+    // whether notes were played.
+    pub fn poll(&mut self, session: &mut PracticeSessionState) -> bool {
+        let last_note_at = self.last_note_at;
+
+        #[cfg(feature = "midi")]
+        if let Some(connection) = &self.connection {
+            connection
+                .messages()
+                .into_iter()
+                .flat_map(|(timestamp_ms, bytes)| self.receive(timestamp_ms, &bytes))
+                .collect::<Vec<SessionCommand>>()
+                .into_iter()
+                .for_each(|command| session.apply(command));
+        }
+
+        // Notes played for the previous key say nothing about the new one.
+        let current_key = session.session_data.current_key_data.map(|data| data.nid);
+        if current_key != self.last_key {
+            self.recent_notes.clear();
+            self.run.clear();
+            self.last_evaluation = None;
+            self.last_key = current_key;
+        }
+
+        self.grade_run(session);
+
+        self.last_note_at != last_note_at
+    }
+
+    fn exercise(&self, root: usize) -> ScaleExercise {
+        ScaleExercise {
+            root,
+            kind: self.scale_kind,
+            direction: self.direction,
+            octaves: self.octaves,
+        }
+    }
+
+    // Grades the current run once it is long enough or the player has stopped.
+    fn grade_run(&mut self, session: &mut PracticeSessionState) {
+        let Some(data) = session.session_data.current_key_data else {
+            return;
+        };
+        if self.run.is_empty() {
+            return;
+        }
+
+        let exercise = self.exercise(data.nid);
+        let stopped = self
+            .last_note_at
+            .map(|at| at.elapsed() > RUN_TIMEOUT)
+            .unwrap_or(false);
+        if self.run.len() < exercise.note_count() && !stopped {
+            return;
+        }
+
+        let evaluation = evaluate(&exercise, &self.run);
+        if evaluation.is_correct() && self.count_correct_runs {
+            session.apply(SessionCommand::CompleteRepetition);
+        }
+        self.last_evaluation = Some(evaluation);
+        self.run.clear();
+    }
+
+    pub fn is_learning(&self) -> bool {
+        self.learning.is_some()
+    }
+
+    pub fn is_connected(&self) -> bool {
+        #[cfg(feature = "midi")]
+        return self.connection.is_some();
+
+        #[cfg(not(feature = "midi"))]
+        return false;
+    }
+
+    pub fn window_contents(&mut self, ui: &mut egui::Ui, session: &PracticeSessionState) {
+        self.device_controls(ui);
+
+        ui.separator();
+
+        egui::ComboBox::from_label("Scale")
+            .selected_text(self.scale_kind.label())
+            .show_ui(ui, |ui| {
+                ScaleKind::ALL.iter().for_each(|kind| {
+                    ui.selectable_value(&mut self.scale_kind, *kind, kind.label());
+                });
+            });
+
+        key_indicator(ui, session, self.scale_kind, &self.recent_notes);
+
+        ui.separator();
+
+        self.run_controls(ui, session);
+
+        ui.separator();
+
+        egui::Grid::new("midi_bindings_grid")
+            .num_columns(3)
+            .striped(true)
+            .show(ui, |ui| {
+                LEARNABLE_COMMANDS.iter().for_each(|command| {
+                    ui.label(format!("{command:?}"));
+                    match self
+                        .controller
+                        .bindings
+                        .bindings
+                        .iter()
+                        .find(|(_, bound)| bound == command)
+                    {
+                        Some((MidiTrigger::Note(note), _)) => ui.monospace(format!("Note {note}")),
+                        Some((MidiTrigger::Control(controller), _)) => {
+                            ui.monospace(format!("CC {controller}"))
+                        }
+                        None => ui.weak("Unbound"),
+                    };
+                    if self.learning == Some(*command) {
+                        if ui.button("Cancel").clicked() {
+                            self.learning = None;
+                        }
+                    } else if ui.button("Learn").clicked() {
+                        self.learning = Some(*command);
+                    }
+                    ui.end_row();
+                });
+            });
+
+        if self.is_learning() {
+            ui.label("Press a key or pedal on your MIDI device...");
+        }
+    }
+
+    fn run_controls(&mut self, ui: &mut egui::Ui, session: &PracticeSessionState) {
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_source("run_direction")
+                .selected_text(self.direction.label())
+                .show_ui(ui, |ui| {
+                    RunDirection::ALL.iter().for_each(|direction| {
+                        ui.selectable_value(&mut self.direction, *direction, direction.label());
+                    });
+                });
+            ui.add(
+                egui::DragValue::new(&mut self.octaves)
+                    .range(1..=4)
+                    .suffix(" octave(s)"),
+            );
+        });
+        ui.checkbox(
+            &mut self.count_correct_runs,
+            "Count correct runs as repetitions",
+        );
+
+        if !self.run.is_empty() {
+            ui.label(format!("Playing run: {} notes so far.", self.run.len()));
+        }
+
+        let Some(evaluation) = &self.last_evaluation else {
+            return;
+        };
+        let note_name = |note: u8| {
+            format!(
+                "{}{}",
+                session.note_name_list[note as usize % 12],
+                note as i32 / 12 - 1
+            )
+        };
+
+        if evaluation.is_correct() {
+            ui.colored_label(egui::Color32::GREEN, "✔ Correct run");
+        } else {
+            ui.colored_label(
+                egui::Color32::RED,
+                format!(
+                    "✘ {} wrong, {} missing",
+                    evaluation.wrong_notes.len(),
+                    evaluation.missing_notes
+                ),
+            );
+            evaluation.wrong_notes.iter().take(4).for_each(|wrong| {
+                let expected = match wrong.expected {
+                    Some(note) => note_name(note),
+                    None => "nothing".to_string(),
+                };
+                ui.label(format!(
+                    "Note {}: played {}, expected {}.",
+                    wrong.index + 1,
+                    note_name(wrong.played),
+                    expected
+                ));
+            });
+        }
+        if let (Some(tempo), Some(evenness)) = (evaluation.tempo_bpm, evaluation.evenness) {
+            ui.label(format!(
+                "Tempo: {tempo:.0} notes/min, evenness: {:.0}%",
+                evenness * 100.0
+            ));
+        }
+    }
+
+    #[cfg(feature = "midi")]
+    fn device_controls(&mut self, ui: &mut egui::Ui) {
+        match &self.connection {
+            Some(connection) => {
+                ui.label(format!("Connected to {}.", connection.port_name));
+                if ui.button("Disconnect").clicked() {
+                    self.connection = None;
+                }
+            }
+            None => {
+                ui.horizontal(|ui| {
+                    if ui.button("⟳").on_hover_text("Refresh MIDI ports").clicked() {
+                        self.port_names = device::port_names();
+                    }
+                    egui::ComboBox::from_id_source("midi_port")
+                        .selected_text(self.selected_port.as_str())
+                        .show_ui(ui, |ui| {
+                            self.port_names.iter().for_each(|name| {
+                                ui.selectable_value(&mut self.selected_port, name.clone(), name);
+                            });
+                        });
+                    if ui
+                        .add_enabled(!self.selected_port.is_empty(), egui::Button::new("Connect"))
+                        .clicked()
+                    {
+                        self.set_connection(device::connect(&self.selected_port));
+                    }
+                });
+
+                #[cfg(unix)]
+                if ui.button("Open virtual port").clicked() {
+                    self.set_connection(device::create_virtual());
+                }
+            }
+        }
+
+        if let Some(error) = &self.error {
+            ui.colored_label(egui::Color32::RED, error);
+        }
+    }
+
+    #[cfg(feature = "midi")]
+    fn set_connection(&mut self, connection: Result<device::MidiConnection, String>) {
+        match connection {
+            Ok(connection) => {
+                self.connection = Some(connection);
+                self.error = None;
+            }
+            Err(e) => {
+                log::error!("{e}");
+                self.error = Some(e);
+            }
+        }
+    }
+
+    #[cfg(not(feature = "midi"))]
+    fn device_controls(&mut self, ui: &mut egui::Ui) {
+        ui.label("This build has no MIDI device support. Rebuild with `--features midi`.");
+        if let Some(error) = &self.error {
+            ui.colored_label(egui::Color32::RED, error);
+        }
+    }
+}
+
+/// Shows whether the last played note, and the notes before it, belong to the current key.
+pub fn key_indicator(
+    ui: &mut egui::Ui,
+    session: &PracticeSessionState,
+    kind: ScaleKind,
+    recent_notes: &VecDeque<u8>,
+) {
+    match (session.session_data.current_key_data, recent_notes.back()) {
+        (Some(data), Some(note)) => {
+            if is_in_key(data.nid, kind, *note) {
+                ui.colored_label(egui::Color32::GREEN, "✔ In key");
+            } else {
+                ui.colored_label(egui::Color32::RED, "✘ Out of key");
+            }
+
+            let notes: Vec<u8> = recent_notes.iter().copied().collect();
+            if let Some(ratio) = in_key_ratio(data.nid, kind, &notes) {
+                ui.label(format!(
+                    "{:.0}% of the last {} notes were in key.",
+                    ratio * 100.0,
+                    notes.len()
+                ));
+            }
+        }
+        (Some(_), None) => {
+            ui.weak("Play something to check the key.");
+        }
+        (None, _) => {
+            ui.weak("No current key.");
+        }
+    }
+}
+
+#[cfg(feature = "midi")]
+mod device {
+    use midir::{Ignore, MidiInput, MidiInputConnection};
+    use std::sync::mpsc::{channel, Receiver, Sender};
+
+    const CLIENT_NAME: &str = "jovian-cycles";
+
+    // Messages with the time in milliseconds at which the device sent them.
+    type TimedMessage = (u64, Vec<u8>);
+
+    pub struct MidiConnection {
+        pub port_name: String,
+        receiver: Receiver<TimedMessage>,
+        _connection: MidiInputConnection<Sender<TimedMessage>>,
+    }
+
+    impl MidiConnection {
+        pub fn messages(&self) -> Vec<TimedMessage> {
+            self.receiver.try_iter().collect()
+        }
+    }
+
+    fn forward(timestamp_us: u64, message: &[u8], sender: &mut Sender<TimedMessage>) {
+        // The receiver only disappears together with the connection.
+        let _ = sender.send((timestamp_us / 1000, message.to_vec()));
+    }
+
+    fn new_input() -> Result<MidiInput, String> {
+        let mut input = MidiInput::new(CLIENT_NAME).map_err(|e| e.to_string())?;
+        input.ignore(Ignore::All);
+        Ok(input)
+    }
+
+    pub fn port_names() -> Vec<String> {
+        match new_input() {
+            Ok(input) => input
+                .ports()
+                .iter()
+                .filter_map(|port| input.port_name(port).ok())
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    pub fn connect(port_name: &str) -> Result<MidiConnection, String> {
+        let input = new_input()?;
+        let port = input
+            .ports()
+            .into_iter()
+            .find(|port| {
+                input
+                    .port_name(port)
+                    .map(|name| name == port_name)
+                    .unwrap_or(false)
+            })
+            .ok_or_else(|| format!("MIDI port '{port_name}' was not found."))?;
+
+        let (sender, receiver) = channel();
+        let connection = input
+            .connect(&port, CLIENT_NAME, forward, sender)
+            .map_err(|e| e.to_string())?;
+
+        Ok(MidiConnection {
+            port_name: port_name.to_string(),
+            receiver,
+            _connection: connection,
+        })
+    }
+
+    #[cfg(unix)]
+    pub fn create_virtual() -> Result<MidiConnection, String> {
+        use midir::os::unix::VirtualInput;
+
+        let input = new_input()?;
+        let (sender, receiver) = channel();
+        let connection = input
+            .create_virtual(CLIENT_NAME, forward, sender)
+            .map_err(|e| e.to_string())?;
+
+        Ok(MidiConnection {
+            port_name: format!("the virtual port '{CLIENT_NAME}'"),
+            receiver,
+            _connection: connection,
+        })
+    }
+}
@@ -0,0 +1,155 @@
+use crate::midi_input::MidiState;
+use jovian_cycles_core::synth::{Pattern, Waveform};
+use jovian_cycles_core::PracticeSessionState;
+
+use serde::{Deserialize, Serialize};
+
+/*
+ * Play-Along
+ *
+ * Plays the scale or arpeggio of the current key at the session tempo, using the scale settings
+ * of the MIDI Input window. Sound output requires the `audio` feature.
+ */
+#[derive(Deserialize, Serialize, Default)]
+#[serde(default)]
+pub struct PlaybackState {
+    pub pattern: Pattern,
+    pub waveform: Waveform,
+    #[serde(skip)]
+    error: Option<String>,
+    #[cfg(feature = "audio")]
+    #[serde(skip)]
+    player: Option<output::Player>,
+}
+
+impl PlaybackState {
+    pub fn controls(
+        &mut self,
+        ui: &mut egui::Ui,
+        session: &PracticeSessionState,
+        midi: &MidiState,
+        bpm: f32,
+    ) {
+        ui.horizontal(|ui| {
+            let key = session.session_data.current_key_data;
+            let play_button = ui.add_enabled(
+                key.is_some() && cfg!(feature = "audio"),
+                egui::Button::new("▶ Play example"),
+            );
+            let play_button = if cfg!(feature = "audio") {
+                play_button.on_disabled_hover_text("Request a key first.")
+            } else {
+                play_button
+                    .on_disabled_hover_text("Rebuild with `--features audio` to play examples.")
+            };
+            if let (true, Some(data)) = (play_button.clicked(), key) {
+                self.play(data.nid, midi, bpm);
+            }
+
+            egui::ComboBox::from_id_source("play_along_pattern")
+                .selected_text(self.pattern.label())
+                .show_ui(ui, |ui| {
+                    Pattern::ALL.iter().for_each(|pattern| {
+                        ui.selectable_value(&mut self.pattern, *pattern, pattern.label());
+                    });
+                });
+            egui::ComboBox::from_id_source("play_along_waveform")
+                .selected_text(self.waveform.label())
+                .show_ui(ui, |ui| {
+                    Waveform::ALL.iter().for_each(|waveform| {
+                        ui.selectable_value(&mut self.waveform, *waveform, waveform.label());
+                    });
+                });
+        });
+
+        if let Some(error) = &self.error {
+            ui.colored_label(egui::Color32::RED, error);
+        }
+    }
+
+    #[cfg(feature = "audio")]
+    fn play(&mut self, root: usize, midi: &MidiState, bpm: f32) {
+        use jovian_cycles_core::synth::{pattern_notes, Synth};
+
+        let notes = pattern_notes(
+            self.pattern,
+            root,
+            midi.scale_kind,
+            midi.direction,
+            midi.octaves,
+            bpm,
+        );
+        let waveform = self.waveform;
+        let render = move |sample_rate: u32| {
+            Synth {
+                sample_rate,
+                waveform,
+                ..Default::default()
+            }
+            .render(&notes)
+        };
+
+        // Replacing the player stops whatever was playing before.
+        self.player = None;
+        match output::play(render) {
+            Ok(player) => {
+                self.player = Some(player);
+                self.error = None;
+            }
+            Err(e) => {
+                log::error!("{e}");
+                self.error = Some(e);
+            }
+        }
+    }
+
+    #[cfg(not(feature = "audio"))]
+    fn play(&mut self, _root: usize, _midi: &MidiState, _bpm: f32) {
+        self.error = Some("This build has no audio support.".to_string());
+    }
+}
+
+#[cfg(feature = "audio")]
+mod output {
+    use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+
+    // Playback stops when the player is dropped.
+    pub struct Player {
+        _stream: cpal::Stream,
+    }
+
+    // Renders at the sample rate of the default output device and starts playing.
+    pub fn play(render: impl FnOnce(u32) -> Vec<f32>) -> Result<Player, String> {
+        let device = cpal::default_host()
+            .default_output_device()
+            .ok_or_else(|| "No audio output device was found.".to_string())?;
+        let config = device.default_output_config().map_err(|e| e.to_string())?;
+        if config.sample_format() != cpal::SampleFormat::F32 {
+            return Err(format!(
+                "The audio output uses an unsupported sample format ({:?}).",
+                config.sample_format()
+            ));
+        }
+
+        let channels = config.channels() as usize;
+        let samples = render(config.sample_rate().0);
+        let mut position = 0;
+        let stream = device
+            .build_output_stream(
+                &config.into(),
+                move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
+                    data.chunks_mut(channels).for_each(|frame| {
+                        let sample = samples.get(position).copied().unwrap_or(0.0);
+                        position += 1;
+                        frame.iter_mut().for_each(|out| *out = sample);
+                    });
+                },
+                |e| log::error!("Audio output failed: {e}"),
+                None,
+            )
+            .map_err(|e| e.to_string())?;
+        stream.play().map_err(|e| e.to_string())?;
+
+        Ok(Player { _stream: stream })
+    }
+}
@@ -0,0 +1,150 @@
+use jovian_cycles_core::profile::MAX_LOGGED_REPETITIONS;
+use jovian_cycles_core::Profile;
+
+use chrono::{Local, NaiveDate, NaiveTime, TimeZone};
+
+/*
+ * Practice Log
+ *
+ * Adds practice done without the app to the history of the active profile. Logged sessions are
+ * flagged as manual: they count towards statistics and goals like any other session, unless the
+ * goals of the profile leave them out.
+ */
+
+pub struct PracticeLogState {
+    date_text: String,
+    time_text: String,
+    minutes: u32,
+    // Key ids and their repetitions.
+    keys: Vec<(usize, u32)>,
+    status: Option<String>,
+}
+
+impl Default for PracticeLogState {
+    fn default() -> Self {
+        let now = Local::now();
+        PracticeLogState {
+            date_text: now.format("%Y-%m-%d").to_string(),
+            time_text: now.format("%H:%M").to_string(),
+            minutes: 20,
+            keys: vec![(0, 1)],
+            status: None,
+        }
+    }
+}
+
+impl PracticeLogState {
+    // Unix timestamp of the end of the practice, in local time.
+    fn finished_at(&self) -> Result<i64, String> {
+        let date = NaiveDate::parse_from_str(self.date_text.trim(), "%Y-%m-%d")
+            .map_err(|_| "Dates are written as YYYY-MM-DD.".to_string())?;
+        let time = NaiveTime::parse_from_str(self.time_text.trim(), "%H:%M")
+            .map_err(|_| "Times are written as HH:MM.".to_string())?;
+        let finished = Local
+            .from_local_datetime(&date.and_time(time))
+            .earliest()
+            .ok_or_else(|| "That time does not exist in the local time zone.".to_string())?;
+        if finished > Local::now() {
+            return Err("Only past practice can be logged.".to_string());
+        }
+        Ok(finished.timestamp())
+    }
+
+    /// Shows the dialog and returns whether a session was logged to `profile`.
+    pub fn window_contents(
+        &mut self,
+        ui: &mut egui::Ui,
+        note_names: &[String],
+        profile: &mut Profile,
+    ) -> bool {
+        egui::Grid::new("practice_log").show(ui, |ui| {
+            ui.label("Finished on:");
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.date_text)
+                        .hint_text("YYYY-MM-DD")
+                        .desired_width(90.0),
+                );
+                ui.label("at");
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.time_text)
+                        .hint_text("HH:MM")
+                        .desired_width(50.0),
+                );
+            });
+            ui.end_row();
+            ui.label("Duration:");
+            ui.add(
+                egui::DragValue::new(&mut self.minutes)
+                    .range(1..=600)
+                    .suffix(" min"),
+            );
+            ui.end_row();
+        });
+
+        ui.separator();
+        let mut removed = None;
+        egui::Grid::new("practice_log_keys").show(ui, |ui| {
+            ui.label("Key Name");
+            ui.label("Key Repetitions");
+            ui.end_row();
+            self.keys
+                .iter_mut()
+                .enumerate()
+                .for_each(|(index, (key, repetitions))| {
+                    egui::ComboBox::from_id_source(("practice_log_key", index))
+                        .selected_text(&note_names[*key])
+                        .show_ui(ui, |ui| {
+                            note_names.iter().enumerate().for_each(|(nid, name)| {
+                                ui.selectable_value(key, nid, name);
+                            });
+                        });
+                    ui.add(egui::DragValue::new(repetitions).range(1..=MAX_LOGGED_REPETITIONS));
+                    if ui.small_button("🗑").clicked() {
+                        removed = Some(index);
+                    }
+                    ui.end_row();
+                });
+        });
+        if let Some(index) = removed {
+            self.keys.remove(index);
+        }
+        if ui.button("➕ Add key").clicked() {
+            let next = self
+                .keys
+                .last()
+                .map_or(0, |(key, _)| (key + 1) % note_names.len());
+            self.keys.push((next, 1));
+        }
+
+        ui.separator();
+        let mut logged = false;
+        if ui
+            .add_enabled(!self.keys.is_empty(), egui::Button::new("Log practice"))
+            .clicked()
+        {
+            let seconds = self.minutes as i64 * 60;
+            let result = self.finished_at().and_then(|finished_at| {
+                profile
+                    .log_practice(finished_at, seconds, &self.keys)
+                    .map_err(|e| e.to_string())
+            });
+            self.status = Some(match result {
+                Ok(_) => {
+                    logged = true;
+                    format!(
+                        "Logged {} minutes of practice on {} at {}.",
+                        self.minutes,
+                        self.date_text.trim(),
+                        self.time_text.trim()
+                    )
+                }
+                Err(e) => e,
+            });
+        }
+        if let Some(status) = &self.status {
+            ui.label(status);
+        }
+        logged
+    }
+}
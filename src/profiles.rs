@@ -0,0 +1,150 @@
+use crate::assignments::AssignmentsState;
+use crate::export::ExportState;
+use crate::mic_input::MicState;
+use crate::midi_input::MidiState;
+use crate::playback::PlaybackState;
+use crate::shortcuts::KeyBindings;
+use crate::sync::SyncState;
+use jovian_cycles_core::{Profile, SessionCheckpoint};
+
+use serde::{Deserialize, Serialize};
+
+/*
+ * Profiles
+ *
+ * Every profile keeps its history, key weights, goals and settings under its own storage key, so
+ * students sharing a machine never see each other's data. Only the list of profiles is stored
+ * with the app itself.
+ */
+
+/// Settings that belong to a profile.
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
+pub struct Settings {
+    pub seed_text: String,
+    /// Tempo of play-along examples and exported files, in beats per minute.
+    pub tempo_bpm: f32,
+    /// Minutes a rest may last before the player is reminded to resume, 0 for no limit.
+    pub rest_cap_minutes: u32,
+    /// Minutes without activity after which a session pauses itself, 0 to never pause.
+    pub idle_minutes: u32,
+    pub key_bindings: KeyBindings,
+    pub midi: MidiState,
+    pub export: ExportState,
+    pub playback: PlaybackState,
+    pub mic: MicState,
+    pub assignments: AssignmentsState,
+    pub sync: SyncState,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            seed_text: String::new(),
+            tempo_bpm: 80.0,
+            rest_cap_minutes: 0,
+            idle_minutes: 5,
+            key_bindings: KeyBindings::default(),
+            midi: MidiState::default(),
+            export: ExportState::default(),
+            playback: PlaybackState::default(),
+            mic: MicState::default(),
+            assignments: AssignmentsState::default(),
+            sync: SyncState::default(),
+        }
+    }
+}
+
+// Everything stored, exported and imported for one profile.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ProfileData {
+    profile: Profile,
+    settings: Settings,
+}
+
+// Same layout as `ProfileData`, without taking the app's state apart to write it.
+#[derive(Serialize)]
+struct ProfileDataRef<'a> {
+    profile: &'a Profile,
+    settings: &'a Settings,
+}
+
+fn storage_key(name: &str) -> String {
+    format!("profile/{name}")
+}
+
+/// Profile and settings stored under `name`, if that profile was ever saved.
+pub fn load(storage: &dyn eframe::Storage, name: &str) -> Option<(Profile, Settings)> {
+    let data: ProfileData = eframe::get_value(storage, &storage_key(name))?;
+    Some((data.profile, data.settings))
+}
+
+pub fn save(storage: &mut dyn eframe::Storage, profile: &Profile, settings: &Settings) {
+    eframe::set_value(
+        storage,
+        &storage_key(&profile.name),
+        &ProfileDataRef { profile, settings },
+    );
+}
+
+pub fn remove(storage: &mut dyn eframe::Storage, name: &str) {
+    // Storage has no removal, an empty value no longer loads.
+    storage.set_string(&storage_key(name), String::new());
+    save_checkpoint(storage, name, None);
+}
+
+// The running session is kept apart from the profile, so exported profiles never carry one.
+fn checkpoint_key(name: &str) -> String {
+    format!("session/{name}")
+}
+
+/// Session of the profile `name` that was still running when the app was last saved.
+pub fn load_checkpoint(storage: &dyn eframe::Storage, name: &str) -> Option<SessionCheckpoint> {
+    eframe::get_value(storage, &checkpoint_key(name))
+}
+
+pub fn save_checkpoint(
+    storage: &mut dyn eframe::Storage,
+    name: &str,
+    checkpoint: Option<&SessionCheckpoint>,
+) {
+    match checkpoint {
+        Some(checkpoint) => eframe::set_value(storage, &checkpoint_key(name), checkpoint),
+        None => storage.set_string(&checkpoint_key(name), String::new()),
+    }
+}
+
+pub fn to_json(profile: &Profile, settings: &Settings) -> Result<String, String> {
+    serde_json::to_string_pretty(&ProfileDataRef { profile, settings }).map_err(|e| e.to_string())
+}
+
+pub fn from_json(text: &str) -> Result<(Profile, Settings), String> {
+    let data: ProfileData = serde_json::from_str(text).map_err(|e| e.to_string())?;
+    Some(data.profile.name.trim())
+        .filter(|name| !name.is_empty())
+        .ok_or_else(|| "The profile has no name.".to_string())?;
+    Ok((data.profile, data.settings))
+}
+
+/// `name`, or `name` followed by the first number that is not taken by another profile.
+pub fn unique_name(name: &str, taken: &[String]) -> String {
+    let name = name.trim();
+    if !taken.iter().any(|other| other == name) {
+        return name.to_string();
+    }
+    (2..)
+        .map(|number| format!("{name} ({number})"))
+        .find(|candidate| !taken.contains(candidate))
+        .unwrap_or_default()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read_file(path: &str) -> Result<String, String> {
+    std::fs::read_to_string(path).map_err(|e| format!("Unable to read {path}: {e}"))
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn read_file(_path: &str) -> Result<String, String> {
+    Err("Opening files is only available in the desktop app.".to_string())
+}
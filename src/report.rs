@@ -0,0 +1,468 @@
+use jovian_cycles_core::edits::ReceiptChange;
+use jovian_cycles_core::statistics::format_duration;
+use jovian_cycles_core::sync::session_id;
+use jovian_cycles_core::{KeyData, PracticeSessionState, Profile, Receipt, SessionStates};
+
+/*
+ * Practice Report
+ *
+ * Shows the keys of the running session as they are practiced, and once no session runs, any
+ * finished session of the history, next to the weight of every key and its chance of being
+ * selected next. Finished sessions can be corrected: every saved correction is appended to the
+ * receipt (see `jovian_cycles_core::edits`) and listed under it, the recorded data is never
+ * overwritten.
+ */
+
+#[derive(Default)]
+pub struct ReportState {
+    // Session id of the finished session shown, the latest one when unset.
+    selected: Option<String>,
+    draft: Option<Draft>,
+}
+
+// Corrections to the shown session that are not saved yet.
+struct Draft {
+    session_id: String,
+    keys: Vec<DraftKey>,
+    excluded: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+struct DraftKey {
+    repetitions: i32,
+    working_seconds: i64,
+    // Shown only, skips are not corrected.
+    skips: i32,
+    removed: bool,
+}
+
+impl Draft {
+    fn new(receipt: &Receipt) -> Self {
+        let corrected = receipt.corrected();
+        Draft {
+            session_id: session_id(receipt),
+            keys: draft_keys(&corrected),
+            excluded: receipt.is_excluded(),
+        }
+    }
+
+    // Corrections that turn `receipt` into the draft.
+    fn changes(&self, receipt: &Receipt) -> Vec<ReceiptChange> {
+        let mut changes = Vec::new();
+        draft_keys(&receipt.corrected())
+            .iter()
+            .zip(self.keys.iter())
+            .enumerate()
+            .filter(|(_, (saved, draft))| saved != draft)
+            .for_each(|(key, (saved, draft))| {
+                if draft.removed {
+                    changes.push(ReceiptChange::RemoveKey { key });
+                    return;
+                }
+                if saved.repetitions != draft.repetitions {
+                    changes.push(ReceiptChange::Repetitions {
+                        key,
+                        repetitions: draft.repetitions,
+                    });
+                }
+                if saved.working_seconds != draft.working_seconds {
+                    changes.push(ReceiptChange::WorkingSeconds {
+                        key,
+                        seconds: draft.working_seconds,
+                    });
+                }
+            });
+        if self.excluded != receipt.is_excluded() {
+            changes.push(ReceiptChange::Excluded {
+                excluded: self.excluded,
+            });
+        }
+        changes
+    }
+}
+
+fn draft_keys(corrected: &Receipt) -> Vec<DraftKey> {
+    corrected
+        .key_data_archive
+        .iter()
+        .flat_map(|keys| keys.0.iter())
+        .map(|key| DraftKey {
+            repetitions: key.repetitions,
+            working_seconds: key.working_seconds,
+            skips: key.skips,
+            removed: is_removed(corrected, key.nid),
+        })
+        .collect()
+}
+
+// A removed key is left in the archive with nothing recorded for it.
+fn is_removed(receipt: &Receipt, key: usize) -> bool {
+    receipt.edits.iter().any(|edit| match edit.change {
+        ReceiptChange::RemoveKey { key: removed } => removed == key,
+        _ => false,
+    })
+}
+
+impl ReportState {
+    /// Shows the report and returns whether a correction was saved to the history of `profile`.
+    pub fn window_contents(
+        &mut self,
+        ui: &mut egui::Ui,
+        session: &PracticeSessionState,
+        profile: &mut Profile,
+    ) -> bool {
+        if !matches!(session.session_state, SessionStates::Waiting) {
+            self.draft = None;
+            if let Some(receipt) = &session.session_data.receipt {
+                live_report(ui, session, receipt);
+            }
+            return false;
+        }
+
+        // Positions in the history of the sessions that were not withdrawn.
+        let shown: Vec<usize> = (0..profile.history.len())
+            .filter(|index| !profile.history[*index].is_withdrawn())
+            .collect();
+        let Some(latest) = shown.last().copied() else {
+            ui.weak("Finished sessions are reported here.");
+            return false;
+        };
+
+        // The shown session may have been undone, or the profile switched.
+        let index = self
+            .selected
+            .as_ref()
+            .and_then(|id| {
+                shown
+                    .iter()
+                    .copied()
+                    .find(|index| session_id(&profile.history[*index]) == *id)
+            })
+            .unwrap_or(latest);
+        let id = session_id(&profile.history[index]);
+        if self
+            .draft
+            .as_ref()
+            .is_some_and(|draft| draft.session_id != id)
+        {
+            self.draft = None;
+        }
+
+        self.session_picker(ui, &profile.history, &shown, index);
+        let receipt = &mut profile.history[index];
+        let selection = Selection::new(session);
+
+        let mut saved = false;
+        match &mut self.draft {
+            None => {
+                finished_report(ui, &session.note_name_list, &selection, receipt);
+                if ui.button("✏ Edit").clicked() {
+                    self.draft = Some(Draft::new(receipt));
+                }
+            }
+            Some(draft) => {
+                draft_report(ui, &session.note_name_list, &selection, draft);
+                let (save, cancel) = ui
+                    .horizontal(|ui| (ui.button("Save").clicked(), ui.button("Cancel").clicked()))
+                    .inner;
+                if save {
+                    let edited_at = session.session_data.clock.now();
+                    draft
+                        .changes(receipt)
+                        .into_iter()
+                        .for_each(|change| receipt.edit(change, edited_at));
+                }
+                if save || cancel {
+                    self.draft = None;
+                }
+                saved = save;
+            }
+        }
+
+        skips_list(ui, &session.note_name_list, &receipt.corrected());
+        changes_list(ui, &session.note_name_list, receipt);
+        skip_analytics(ui, &session.note_name_list, profile);
+        saved
+    }
+
+    fn session_picker(
+        &mut self,
+        ui: &mut egui::Ui,
+        history: &[Receipt],
+        shown: &[usize],
+        index: usize,
+    ) {
+        egui::ComboBox::from_label("Session")
+            .selected_text(session_label(&history[index]))
+            .show_ui(ui, |ui| {
+                shown.iter().rev().for_each(|shown| {
+                    let receipt = &history[*shown];
+                    if ui
+                        .selectable_label(*shown == index, session_label(receipt))
+                        .clicked()
+                    {
+                        self.selected = Some(session_id(receipt));
+                    }
+                });
+            });
+    }
+}
+
+fn session_label(receipt: &Receipt) -> String {
+    let finished = receipt
+        .time_stamp_archive
+        .as_ref()
+        .and_then(|time_stamps| time_stamps.last())
+        .and_then(|(_, timestamp)| local_time(*timestamp))
+        .unwrap_or_else(|| "Unknown time".to_string());
+    let logged = match receipt.manual {
+        true => " (logged)",
+        false => "",
+    };
+    let excluded = match receipt.is_excluded() {
+        true => " (excluded)",
+        false => "",
+    };
+    format!("{finished}{logged}{excluded}")
+}
+
+fn local_time(timestamp: i64) -> Option<String> {
+    chrono::DateTime::from_timestamp(timestamp, 0).map(|time| {
+        time.with_timezone(&chrono::Local)
+            .format("%Y-%m-%d %H:%M")
+            .to_string()
+    })
+}
+
+// Live while the session runs.
+fn live_report(ui: &mut egui::Ui, session: &PracticeSessionState, receipt: &Receipt) {
+    let data = &session.session_data;
+    let selection = Selection::new(session);
+    let working = |key: &KeyData| data.working_seconds(key.nid);
+    key_grid(ui, |ui| {
+        receipt
+            .key_data_archive
+            .iter()
+            .flat_map(|keys| keys.0.iter())
+            .for_each(|key| {
+                ui.label(&session.note_name_list[key.nid]);
+                ui.label(format!("{}", key.repetitions));
+                ui.label(format_duration(working(key)));
+                ui.label(format!("{}", key.skips));
+                selection.cells(ui, key.nid);
+                ui.end_row();
+            });
+    });
+
+    // The rest in progress counts too.
+    let (mut resting, mut rests) = (receipt.rests.iter().sum(), receipt.rests.len());
+    if matches!(session.session_state, SessionStates::Resting) {
+        resting += data.elapsed_seconds();
+        rests += 1;
+    }
+    let active = receipt
+        .key_data_archive
+        .iter()
+        .flat_map(|keys| keys.0.iter())
+        .map(working)
+        .sum();
+    totals(ui, active, resting, rests);
+    skips_list(ui, &session.note_name_list, receipt);
+}
+
+fn finished_report(
+    ui: &mut egui::Ui,
+    note_names: &[String],
+    selection: &Selection,
+    receipt: &Receipt,
+) {
+    let corrected = receipt.corrected();
+    key_grid(ui, |ui| {
+        corrected
+            .key_data_archive
+            .iter()
+            .flat_map(|keys| keys.0.iter())
+            .filter(|key| !is_removed(receipt, key.nid))
+            .for_each(|key| {
+                ui.label(&note_names[key.nid]);
+                ui.label(format!("{}", key.repetitions));
+                ui.label(format_duration(key.working_seconds));
+                ui.label(format!("{}", key.skips));
+                selection.cells(ui, key.nid);
+                ui.end_row();
+            });
+    });
+
+    let active = corrected
+        .key_data_archive
+        .iter()
+        .flat_map(|keys| keys.0.iter())
+        .map(|key| key.working_seconds)
+        .sum();
+    totals(ui, active, receipt.rests.iter().sum(), receipt.rests.len());
+    if receipt.is_excluded() {
+        ui.weak("Excluded from statistics, goals and assignments.");
+    }
+}
+
+fn draft_report(
+    ui: &mut egui::Ui,
+    note_names: &[String],
+    selection: &Selection,
+    draft: &mut Draft,
+) {
+    key_grid(ui, |ui| {
+        draft
+            .keys
+            .iter_mut()
+            .enumerate()
+            .filter(|(_, key)| !key.removed)
+            .for_each(|(nid, key)| {
+                ui.horizontal(|ui| {
+                    if ui
+                        .small_button("🗑")
+                        .on_hover_text("Remove the key from the session")
+                        .clicked()
+                    {
+                        key.removed = true;
+                    }
+                    ui.label(&note_names[nid]);
+                });
+                ui.add(egui::DragValue::new(&mut key.repetitions).range(0..=999));
+                ui.add(
+                    egui::DragValue::new(&mut key.working_seconds)
+                        .range(0..=24 * 60 * 60)
+                        .custom_formatter(|seconds, _| format_duration(seconds as i64))
+                        .custom_parser(parse_duration),
+                );
+                ui.label(format!("{}", key.skips));
+                selection.cells(ui, nid);
+                ui.end_row();
+            });
+    });
+    ui.separator();
+    ui.checkbox(&mut draft.excluded, "Exclude from statistics")
+        .on_hover_text("Leave the session out of totals, goals and assignments.");
+}
+
+fn skips_list(ui: &mut egui::Ui, note_names: &[String], receipt: &Receipt) {
+    if receipt.skips.is_empty() {
+        return;
+    }
+    egui::CollapsingHeader::new(format!("Skips ({})", receipt.skips.len())).show(ui, |ui| {
+        receipt.skips.iter().for_each(|skip| {
+            let skipped_at = chrono::DateTime::from_timestamp(skip.skipped_at, 0)
+                .map(|time| {
+                    time.with_timezone(&chrono::Local)
+                        .format("%H:%M")
+                        .to_string()
+                })
+                .unwrap_or_default();
+            let reason = skip.reason.as_deref().unwrap_or("no reason given");
+            ui.label(format!("{skipped_at} {}: {reason}", note_names[skip.key]));
+        });
+    });
+}
+
+// Keys skipped most over the whole history, and why.
+fn skip_analytics(ui: &mut egui::Ui, note_names: &[String], profile: &Profile) {
+    let mut skipped: Vec<(usize, i32)> = profile
+        .skips_per_key()
+        .into_iter()
+        .enumerate()
+        .filter(|(_, skips)| *skips > 0)
+        .collect();
+    if skipped.is_empty() {
+        return;
+    }
+    skipped.sort_by_key(|(_, skips)| std::cmp::Reverse(*skips));
+    egui::CollapsingHeader::new("Skipped keys across sessions").show(ui, |ui| {
+        egui::Grid::new("skipped_keys").show(ui, |ui| {
+            skipped.iter().for_each(|(key, skips)| {
+                ui.label(&note_names[*key]);
+                ui.label(format!("{skips} skip(s)"));
+                ui.end_row();
+            });
+        });
+        let reasons = profile.skip_reasons();
+        if !reasons.is_empty() {
+            ui.separator();
+            reasons.iter().for_each(|(reason, count)| {
+                ui.label(format!("{reason}: {count}"));
+            });
+        }
+    });
+}
+
+fn changes_list(ui: &mut egui::Ui, note_names: &[String], receipt: &Receipt) {
+    if receipt.edits.is_empty() {
+        return;
+    }
+    egui::CollapsingHeader::new(format!("Changes ({})", receipt.edits.len())).show(ui, |ui| {
+        receipt.edits.iter().for_each(|edit| {
+            let edited_at = local_time(edit.edited_at).unwrap_or_default();
+            ui.label(format!("{edited_at}: {}", edit.change.describe(note_names)));
+        });
+    });
+}
+
+fn key_grid(ui: &mut egui::Ui, add_rows: impl FnOnce(&mut egui::Ui)) {
+    egui::Grid::new("center_pane")
+        .min_col_width(80.0)
+        .max_col_width(100.0)
+        .show(ui, |ui| {
+            ui.label("Key Name");
+            ui.label("Key Repetitions");
+            ui.label("Duration");
+            ui.label("Skips");
+            ui.label("Weight");
+            ui.label("Chance");
+            ui.end_row();
+            add_rows(ui);
+        });
+}
+
+// Weight of every key and its chance of being selected next, as the session stands now.
+struct Selection {
+    keys: Vec<KeyData>,
+    chances: Vec<f32>,
+}
+
+impl Selection {
+    fn new(session: &PracticeSessionState) -> Self {
+        Selection {
+            keys: session.session_data.all_keys_map.0.clone(),
+            chances: session.session_data.next_key_probabilities(),
+        }
+    }
+
+    fn cells(&self, ui: &mut egui::Ui, key: usize) {
+        match self.keys.get(key) {
+            Some(data) if data.pinned => ui.label(format!("{} 📌", data.weight)),
+            Some(data) => ui.label(format!("{}", data.weight)),
+            None => ui.label(""),
+        };
+        let chance = self.chances.get(key).copied().unwrap_or_default();
+        ui.label(format!("{:.0}%", chance * 100.0));
+    }
+}
+
+fn totals(ui: &mut egui::Ui, active: i64, resting: i64, rests: usize) {
+    ui.separator();
+    ui.label(format!("Active: {}", format_duration(active)));
+    ui.label(format!(
+        "Resting: {} ({} rest(s))",
+        format_duration(resting),
+        rests
+    ));
+}
+
+// Reads durations as `format_duration` writes them, or as plain seconds.
+fn parse_duration(text: &str) -> Option<f64> {
+    text.trim().split(':').try_fold(0.0, |seconds, part| {
+        part.trim()
+            .parse::<u32>()
+            .ok()
+            .map(|part| seconds * 60.0 + part as f64)
+    })
+}
@@ -0,0 +1,113 @@
+use jovian_cycles_core::SessionCommand;
+
+use egui::{Key, Modifiers};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/*
+ * Keyboard Shortcuts
+ *
+ * Every session action can be triggered from the keyboard, which also makes the app usable with
+ * foot pedals that emulate keystrokes.
+ */
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ShortcutAction {
+    NextKey,
+    Skip,
+    PauseResume,
+    Finish,
+}
+
+impl ShortcutAction {
+    pub const ALL: [ShortcutAction; 4] = [
+        ShortcutAction::NextKey,
+        ShortcutAction::Skip,
+        ShortcutAction::PauseResume,
+        ShortcutAction::Finish,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ShortcutAction::NextKey => "Request New Key",
+            ShortcutAction::Skip => "Skip Selected Key",
+            ShortcutAction::PauseResume => "Pause / Resume Practice Session",
+            ShortcutAction::Finish => "End Practice Session",
+        }
+    }
+
+    pub fn command(&self) -> SessionCommand {
+        match self {
+            ShortcutAction::NextKey => SessionCommand::NewKey,
+            ShortcutAction::Skip => SessionCommand::Skip,
+            ShortcutAction::PauseResume => SessionCommand::TogglePause,
+            ShortcutAction::Finish => SessionCommand::Finish,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct KeyBindings {
+    pub bindings: BTreeMap<ShortcutAction, Key>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyBindings {
+            bindings: BTreeMap::from([
+                (ShortcutAction::NextKey, Key::Space),
+                (ShortcutAction::Skip, Key::S),
+                (ShortcutAction::PauseResume, Key::P),
+                (ShortcutAction::Finish, Key::Escape),
+            ]),
+        }
+    }
+}
+
+impl KeyBindings {
+    pub fn key(&self, action: ShortcutAction) -> Option<Key> {
+        self.bindings.get(&action).copied()
+    }
+
+    // Binding a key that is already in use swaps it with the previous key of `action`.
+    pub fn bind(&mut self, action: ShortcutAction, key: Key) {
+        let previous = self.bindings.insert(action, key);
+
+        let clashing: Vec<ShortcutAction> = self
+            .bindings
+            .iter()
+            .filter(|(other, bound)| **other != action && **bound == key)
+            .map(|(other, _)| *other)
+            .collect();
+        clashing.into_iter().for_each(|other| match previous {
+            Some(previous) => {
+                self.bindings.insert(other, previous);
+            }
+            None => {
+                self.bindings.remove(&other);
+            }
+        });
+    }
+
+    // Consumes the first bound key pressed this frame, so it does not also reach the widgets.
+    pub fn pressed_action(&self, ctx: &egui::Context) -> Option<ShortcutAction> {
+        ctx.input_mut(|input| {
+            self.bindings
+                .iter()
+                .find(|(_, key)| input.consume_key(Modifiers::NONE, **key))
+                .map(|(action, _)| *action)
+        })
+    }
+}
+
+// Returns the first key pressed this frame, used while waiting for a new binding.
+pub fn first_pressed_key(ctx: &egui::Context) -> Option<Key> {
+    ctx.input(|input| {
+        input.events.iter().find_map(|event| match event {
+            egui::Event::Key {
+                key, pressed: true, ..
+            } => Some(*key),
+            _ => None,
+        })
+    })
+}
@@ -0,0 +1,147 @@
+use jovian_cycles_core::sync::{self, receipts_url};
+use jovian_cycles_core::{Profile, Receipt};
+
+use serde::{Deserialize, Serialize};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+
+/*
+ * Sync
+ *
+ * Shares the history of the active profile with a sync server (the `jovian-cycles-sync` binary).
+ * Every sync sends the whole history and merges back whatever the server has, so nothing is lost
+ * while the server cannot be reached: the app keeps recording on its own and the next sync that
+ * gets through catches both sides up.
+ */
+
+// Seconds between two syncs, and before retrying one that failed.
+const SYNC_INTERVAL: f64 = 30.0;
+
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
+pub struct SyncState {
+    pub enabled: bool,
+    /// Address of the sync server, e.g. `http://127.0.0.1:7878`.
+    pub server_url: String,
+    #[serde(skip)]
+    status: Option<String>,
+    #[serde(skip)]
+    pending: Option<Receiver<Result<Vec<Receipt>, String>>>,
+    #[serde(skip)]
+    next_sync: f64,
+}
+
+impl Default for SyncState {
+    fn default() -> Self {
+        SyncState {
+            enabled: false,
+            server_url: "http://127.0.0.1:7878".to_string(),
+            status: None,
+            pending: None,
+            next_sync: 0.0,
+        }
+    }
+}
+
+impl SyncState {
+    /// Merges the answer of a finished sync into `profile` and starts the next one when it is due.
+    pub fn poll(&mut self, ctx: &egui::Context, profile: &mut Profile) {
+        if let Some(pending) = &self.pending {
+            match pending.try_recv() {
+                Ok(Ok(receipts)) => {
+                    let changed = sync::merge(&mut profile.history, receipts);
+                    self.status = Some(format!(
+                        "Synced at {}, {} new or corrected session(s).",
+                        chrono::Local::now().format("%H:%M"),
+                        changed
+                    ));
+                    self.pending = None;
+                }
+                Ok(Err(e)) => {
+                    log::warn!("Sync failed: {e}");
+                    self.status =
+                        Some(format!("Not synced, the history stays on this device: {e}"));
+                    self.pending = None;
+                }
+                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Disconnected) => self.pending = None,
+            }
+        }
+
+        if !self.enabled {
+            return;
+        }
+        let now = ctx.input(|i| i.time);
+        if self.pending.is_none() && now >= self.next_sync {
+            self.start(ctx, profile);
+            self.next_sync = now + SYNC_INTERVAL;
+        }
+        // Syncs are due even when nothing else wakes egui up.
+        ctx.request_repaint_after(std::time::Duration::from_secs_f64(SYNC_INTERVAL));
+    }
+
+    /// Syncs on the next frame, e.g. right after a session finished.
+    pub fn sync_soon(&mut self) {
+        self.next_sync = 0.0;
+    }
+
+    fn start(&mut self, ctx: &egui::Context, profile: &Profile) {
+        if profile.id.trim().is_empty() {
+            self.status = Some("Give the profile an id to sync it.".to_string());
+            return;
+        }
+        let body = match sync::to_json(&profile.history) {
+            Ok(body) => body,
+            Err(e) => {
+                self.status = Some(format!("Unable to send the history: {e}"));
+                return;
+            }
+        };
+        let url = receipts_url(&self.server_url, profile.id.trim());
+
+        let (sender, receiver) = channel();
+        let ctx = ctx.clone();
+        ehttp::fetch(
+            ehttp::Request::post(url, body.into_bytes()),
+            move |result| {
+                let receipts = result.and_then(|response| match response.text() {
+                    Some(text) if response.ok => sync::from_json(text).map_err(|e| e.to_string()),
+                    _ => Err(format!("{} {}", response.status, response.status_text)),
+                });
+                // The receiver is gone when the profile was switched in the meantime.
+                let _ = sender.send(receipts);
+                ctx.request_repaint();
+            },
+        );
+        self.pending = Some(receiver);
+    }
+
+    pub fn window_contents(&mut self, ui: &mut egui::Ui, profile: &mut Profile) {
+        ui.checkbox(&mut self.enabled, "Sync this profile");
+        ui.horizontal(|ui| {
+            ui.label("Server:");
+            ui.text_edit_singleline(&mut self.server_url);
+        });
+        ui.horizontal(|ui| {
+            ui.label("Profile id:");
+            ui.text_edit_singleline(&mut profile.id).on_hover_text(
+                "Use the same id on every device of this profile, and a different one for anyone else.",
+            );
+        });
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(self.pending.is_none(), egui::Button::new("Sync now"))
+                .clicked()
+            {
+                self.start(ui.ctx(), profile);
+            }
+            if self.pending.is_some() {
+                ui.spinner();
+            }
+        });
+
+        match &self.status {
+            Some(status) => ui.label(status),
+            None => ui.weak("Run jovian-cycles-sync on a machine every device can reach."),
+        };
+    }
+}
@@ -0,0 +1,192 @@
+use crate::cli::print_receipt;
+use jovian_cycles_core::statistics::format_duration;
+use jovian_cycles_core::PracticeSessionState;
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Alignment, Constraint, Layout};
+use ratatui::style::{Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Paragraph, Row, Table};
+use ratatui::{DefaultTerminal, Frame};
+use std::io;
+use std::time::Duration;
+
+/*
+ * Terminal UI
+ *
+ * Full-screen front-end for the practice session. Drives the same session actions as the egui
+ * app and prints the receipt once the terminal has been restored.
+ */
+pub fn run(seed: Option<u64>) -> io::Result<()> {
+    let mut state = PracticeSessionState::default();
+    state.session_data.set_seed(seed);
+
+    let mut terminal = ratatui::init();
+    let result = run_session(&mut terminal, &mut state);
+    ratatui::restore();
+    result?;
+
+    match &state.session_data.receipt {
+        Some(receipt) => print_receipt(&state.note_name_list, receipt),
+        None => println!("No receipt found."),
+    }
+
+    Ok(())
+}
+
+fn run_session(terminal: &mut DefaultTerminal, state: &mut PracticeSessionState) -> io::Result<()> {
+    loop {
+        terminal.draw(|frame| draw(frame, state))?;
+
+        // Poll with a timeout so the timer keeps ticking without input.
+        if !event::poll(Duration::from_millis(250))? {
+            continue;
+        }
+
+        if let Event::Key(key) = event::read()? {
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+
+            match key.code {
+                KeyCode::Char('n') => state.request_new_key(),
+                KeyCode::Char('s') => state.skip_key(),
+                KeyCode::Char('p') => state.pause(),
+                KeyCode::Char('r') => state.resume(),
+                KeyCode::Char('q') | KeyCode::Esc => {
+                    state.finish();
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+fn draw(frame: &mut Frame, state: &PracticeSessionState) {
+    let [header_area, key_area, report_area, footer_area] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Length(10),
+        Constraint::Min(0),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    frame.render_widget(
+        Line::from(vec![
+            Span::from("jovian cycles").bold(),
+            Span::from(format!("  {:?}", state.session_state)),
+        ]),
+        header_area,
+    );
+
+    let mut key_lines = match state.session_data.current_key_data {
+        Some(data) => big_text(&state.note_name_list[data.nid]),
+        None => vec![
+            Line::from(""),
+            Line::from("No current key."),
+            Line::from(""),
+        ],
+    };
+    key_lines.push(Line::from(""));
+    key_lines.push(Line::from(timer_text(state)).bold());
+    frame.render_widget(
+        Paragraph::new(key_lines)
+            .alignment(Alignment::Center)
+            .block(Block::bordered().title("Current Key")),
+        key_area,
+    );
+
+    let rows: Vec<Row> = match &state.session_data.receipt {
+        Some(receipt) => match &receipt.key_data_archive {
+            Some(history) => history
+                .0
+                .iter()
+                .map(|key| {
+                    Row::new(vec![
+                        state.note_name_list[key.nid].clone(),
+                        key.repetitions.to_string(),
+                        format_duration(state.session_data.working_seconds(key.nid)),
+                    ])
+                })
+                .collect(),
+            None => Vec::new(),
+        },
+        None => Vec::new(),
+    };
+    frame.render_widget(
+        Table::new(
+            rows,
+            [
+                Constraint::Length(10),
+                Constraint::Length(16),
+                Constraint::Length(10),
+            ],
+        )
+        .header(Row::new(vec!["Key Name", "Key Repetitions", "Duration"]).bold())
+        .block(Block::bordered().title("Practice Report")),
+        report_area,
+    );
+
+    let command = |key: &'static str, label: &'static str, enabled: bool| {
+        let style = if enabled {
+            Style::default()
+        } else {
+            Style::default().dim()
+        };
+        vec![
+            Span::styled(key, style.bold()),
+            Span::styled(format!(" {label}  "), style),
+        ]
+    };
+    let footer: Vec<Span> = [
+        command("n", "new key", true),
+        command("s", "skip", state.can_skip()),
+        command("p", "pause", state.can_pause()),
+        command("r", "resume", state.can_resume()),
+        command("q", "finish", true),
+    ]
+    .concat();
+    frame.render_widget(Line::from(footer), footer_area);
+}
+
+fn timer_text(state: &PracticeSessionState) -> String {
+    match state.session_data.start_timestamp() {
+        Some((state_name, started)) => {
+            let elapsed = (state.session_data.clock.now() - started).max(0);
+            format!("{state_name} {:02}:{:02}", elapsed / 60, elapsed % 60)
+        }
+        None => "--:--".to_string(),
+    }
+}
+
+// Renders a note name with a five row block font so it can be read from the instrument.
+fn big_text(name: &str) -> Vec<Line<'static>> {
+    let glyphs: Vec<[&str; 5]> = name.chars().map(glyph).collect();
+
+    (0..5)
+        .map(|row| {
+            let line = glyphs
+                .iter()
+                .map(|glyph| glyph[row])
+                .collect::<Vec<&str>>()
+                .join(" ");
+            Line::from(line)
+        })
+        .collect()
+}
+
+fn glyph(c: char) -> [&'static str; 5] {
+    match c {
+        'A' => [" ███ ", "█   █", "█████", "█   █", "█   █"],
+        'B' => ["████ ", "█   █", "████ ", "█   █", "████ "],
+        'C' => [" ████", "█    ", "█    ", "█    ", " ████"],
+        'D' => ["████ ", "█   █", "█   █", "█   █", "████ "],
+        'E' => ["█████", "█    ", "████ ", "█    ", "█████"],
+        'F' => ["█████", "█    ", "████ ", "█    ", "█    "],
+        'G' => [" ████", "█    ", "█  ██", "█   █", " ████"],
+        '#' => [" █ █ ", "█████", " █ █ ", "█████", " █ █ "],
+        'b' => ["█    ", "█    ", "████ ", "█   █", "████ "],
+        _ => ["     ", "     ", "     ", "     ", "     "],
+    }
+}
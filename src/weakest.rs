@@ -0,0 +1,66 @@
+use jovian_cycles_core::selection::Strategy;
+use jovian_cycles_core::PracticeSessionState;
+
+/*
+ * Weakest Keys
+ *
+ * Explains the weakest keys strategy: why the current key was likely to come up, and how every
+ * key scores on the history of the profile.
+ */
+
+pub fn window_contents(ui: &mut egui::Ui, session: &PracticeSessionState) {
+    let data = &session.session_data;
+    let names = &session.note_name_list;
+    if data.weakness.is_empty() {
+        ui.weak("The keys have not been scored yet.");
+        return;
+    }
+    if data.strategy != Strategy::Weakest {
+        ui.weak("Select keys by \"Weakest keys\" in the Session Controls to favor these keys.");
+    }
+
+    // Chances of coming up next, with pins and constraints as the session selects keys.
+    let chances = data.next_key_probabilities();
+
+    if let Some(current) = data.current_key_data {
+        if let Some(weakness) = data.weakness.get(current.nid) {
+            ui.heading(format!("Why {}?", names[current.nid]));
+            if current.pinned {
+                ui.label("The key is pinned, so it comes up in every session.");
+            }
+            if data.strategy == Strategy::Weakest {
+                ui.label(format!(
+                    "Weakness {:.0}%, which makes it {:.1} times as likely as its weight alone.",
+                    weakness.score * 100.0,
+                    weakness.factor()
+                ));
+            } else {
+                ui.label(format!("Weakness {:.0}%.", weakness.score * 100.0));
+            }
+            weakness.reasons().iter().for_each(|reason| {
+                ui.label(format!("• {reason}"));
+            });
+            ui.separator();
+        }
+    }
+
+    let mut ranked = data.weakness.clone();
+    ranked.sort_by(|a, b| b.score.total_cmp(&a.score));
+    egui::Grid::new("weakest_keys")
+        .striped(true)
+        .show(ui, |ui| {
+            ui.label("Key Name");
+            ui.label("Weakness");
+            ui.label("Chance");
+            ui.label("Strongest reason");
+            ui.end_row();
+            ranked.iter().for_each(|weakness| {
+                let chance = chances.get(weakness.key).copied().unwrap_or(0.0);
+                ui.label(&names[weakness.key]);
+                ui.label(format!("{:.0}%", weakness.score * 100.0));
+                ui.label(format!("{:.0}%", chance * 100.0));
+                ui.label(weakness.reasons().first().cloned().unwrap_or_default());
+                ui.end_row();
+            });
+        });
+}